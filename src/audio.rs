@@ -0,0 +1,69 @@
+use std::mem::size_of;
+
+use sdl2::{
+    audio::{AudioQueue, AudioSpecDesired},
+    AudioSubsystem,
+};
+
+// Audio kept queued ahead of the device, in frames
+static TARGET_LATENCY_FRAMES: f32 = 3.0;
+// Largest change to the emulated sample rate used to steer the queue to its target
+static MAX_RATE_ADJUSTMENT: f32 = 0.005;
+
+// Streams APU samples to the audio device. The emulated sample rate is nudged up or down
+// depending on how full the device queue is, so it neither runs dry nor keeps growing.
+pub struct AudioOutput {
+    queue: AudioQueue<f32>,
+    target_queued_samples: u32,
+}
+
+impl AudioOutput {
+    // The device may pick another sample rate than the one requested
+    pub fn open(
+        audio_subsystem: &AudioSubsystem,
+        sample_rate: f32,
+        frame_rate: f32,
+    ) -> Result<Self, String> {
+        let desired = AudioSpecDesired {
+            freq: Some(sample_rate as i32),
+            channels: Some(1),
+            samples: Some(512),
+        };
+        let queue = audio_subsystem.open_queue::<f32, _>(None, &desired)?;
+        queue.resume();
+
+        let target_queued_samples =
+            (queue.spec().freq as f32 * TARGET_LATENCY_FRAMES / frame_rate) as u32;
+
+        Ok(Self {
+            queue,
+            target_queued_samples,
+        })
+    }
+
+    // The rate the device plays at, which the APU should produce samples at
+    pub fn sample_rate(&self) -> f32 {
+        self.queue.spec().freq as f32
+    }
+
+    pub fn queue(&self, samples: &[f32]) -> Result<(), String> {
+        self.queue.queue_audio(samples)
+    }
+
+    pub fn queued_samples(&self) -> u32 {
+        self.queue.size() / size_of::<f32>() as u32
+    }
+
+    // Whether the device has more queued than the target latency, so the emulator is
+    // running ahead and should wait
+    pub fn is_ahead(&self) -> bool {
+        self.queued_samples() > self.target_queued_samples
+    }
+
+    // Sample rate the APU should produce at for the queue to settle on its target
+    pub fn adjusted_sample_rate(&self) -> f32 {
+        let fill = self.queued_samples() as f32 / self.target_queued_samples as f32;
+        let adjustment = (1.0 - fill).clamp(-1.0, 1.0) * MAX_RATE_ADJUSTMENT;
+        self.sample_rate() * (1.0 + adjustment)
+    }
+}
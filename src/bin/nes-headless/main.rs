@@ -0,0 +1,148 @@
+mod png;
+mod script;
+
+use std::{env, fs, process::ExitCode};
+
+use nes_emulator::{
+    nes::{cartridge::Cartridge, ppu, region::Region},
+    Nes,
+};
+use script::{Condition, InputEvent};
+
+static USAGE: &str = "usage: nes-headless [options] <rom>
+  --frames <n>         frames to run, or to wait for --until [default: 600]
+  --until <condition>  stop early once pc=<address> or <address>=<value> (hex)
+  --input <script>     controller input script
+  --region <region>    ntsc, pal or dendy [default: from the ROM header]
+  --png <path>         where to write the final frame [default: frame.png]
+  --ram <path>         where to dump the 2 KiB of internal RAM [default: ram.bin]";
+
+struct Options {
+    rom_path: String,
+    frames: u64,
+    until: Option<Condition>,
+    input: Vec<InputEvent>,
+    region: Option<Region>,
+    png_path: String,
+    ram_path: String,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut rom_path = None;
+        let mut options = Self {
+            rom_path: String::new(),
+            frames: 600,
+            until: None,
+            input: Vec::new(),
+            region: None,
+            png_path: "frame.png".into(),
+            ram_path: "ram.bin".into(),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} expects a value", arg));
+            match arg.as_str() {
+                "--frames" => {
+                    options.frames = value()?
+                        .parse()
+                        .map_err(|_| "--frames expects a whole number")?
+                }
+                "--until" => options.until = Some(Condition::parse(&value()?)?),
+                "--input" => options.input = script::load_input(&value()?)?,
+                "--region" => {
+                    options.region = Some(
+                        Region::from_name(&value()?)
+                            .ok_or("--region expects ntsc, pal or dendy")?,
+                    )
+                }
+                "--png" => options.png_path = value()?,
+                "--ram" => options.ram_path = value()?,
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ => rom_path = Some(arg),
+            }
+        }
+
+        options.rom_path = rom_path.ok_or(USAGE)?;
+        Ok(options)
+    }
+}
+
+// Runs the ROM without a window and reports whether --until was met, if given
+fn run(options: Options) -> Result<bool, String> {
+    let cartridge =
+        Cartridge::load(&options.rom_path).map_err(|e| format!("{}: {}", options.rom_path, e))?;
+    let mut nes = Nes::new(cartridge, options.region);
+
+    let mut input = options.input.iter().peekable();
+    let condition_met = loop {
+        let frame = nes.frame();
+        while let Some(event) = input.next_if(|event| event.frame <= frame) {
+            nes.set_controller(event.player, &event.buttons);
+        }
+        if frame >= options.frames {
+            break false;
+        }
+
+        nes.step_instruction();
+        if options
+            .until
+            .is_some_and(|condition| condition.is_met(&nes))
+        {
+            break true;
+        }
+    };
+
+    let cpu = nes.cpu();
+    println!(
+        "PC:${:04X} A:${:02X} X:${:02X} Y:${:02X} SP:${:02X} P:${:02X} [{}] Cycles:{} Frame:{}",
+        cpu.pc,
+        cpu.a,
+        cpu.x,
+        cpu.y,
+        cpu.stack_ptr,
+        cpu.status_flags.bits(),
+        cpu.status_flags,
+        cpu.total_cycles,
+        nes.frame()
+    );
+
+    png::write_rgb(
+        &options.png_path,
+        ppu::SCREEN_WIDTH as u32,
+        ppu::SCREEN_HEIGHT as u32,
+        &nes.frame_buffer_rgb(),
+    )
+    .map_err(|e| format!("{}: {}", options.png_path, e))?;
+
+    let ram = (0x0000..0x0800)
+        .map(|addr| nes.peek(addr))
+        .collect::<Vec<u8>>();
+    fs::write(&options.ram_path, ram).map_err(|e| format!("{}: {}", options.ram_path, e))?;
+
+    Ok(condition_met)
+}
+
+// Exits with 1 on errors, and with 2 when --until was not met in time
+fn main() -> ExitCode {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(1);
+        }
+    };
+    let waits_for_condition = options.until.is_some();
+
+    match run(options) {
+        Ok(condition_met) if waits_for_condition && !condition_met => {
+            eprintln!("Condition not met");
+            ExitCode::from(2)
+        }
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::from(1)
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::{fs, io};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+// Largest payload of an uncompressed deflate block
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+// Writes an 8-bit RGB image. Pixel data goes into uncompressed deflate blocks, which
+// keeps the encoder tiny at the cost of file size.
+pub fn write_rgb(path: &str, width: u32, height: u32, pixels: &[u8]) -> io::Result<()> {
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, colour type RGB, default compression, filter and interlace methods
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    // Every row starts with its filter type, 0 meaning none
+    let mut raw = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks(width as usize * 3) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    fs::write(path, png)
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32 KiB window, no preset dictionary
+    let mut zlib = vec![0x78, 0x01];
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        zlib.push(last as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+
+    zlib.extend_from_slice(&adler32(data).to_be_bytes());
+    zlib
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_the_reference_values() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        assert_eq!(adler32(&[]), 1);
+    }
+
+    #[test]
+    fn chunks_are_length_kind_data_and_crc() {
+        let mut png = Vec::new();
+        write_chunk(&mut png, b"IEND", &[]);
+        assert_eq!(
+            png,
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]
+        );
+    }
+
+    #[test]
+    fn zlib_header_is_valid() {
+        let zlib = zlib_stored(b"abc");
+        // The header check bits make CMF and FLG a multiple of 31
+        assert_eq!(u16::from_be_bytes([zlib[0], zlib[1]]) % 31, 0);
+        assert_eq!(zlib[0] & 0x0F, 8);
+        assert_eq!(
+            zlib[2..],
+            [0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c', 0x02, 0x4D, 0x01, 0x27]
+        );
+    }
+
+    #[test]
+    fn empty_data_still_has_a_final_block() {
+        assert_eq!(
+            zlib_stored(&[]),
+            [0x78, 0x01, 0x01, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x01]
+        );
+    }
+
+    #[test]
+    fn long_data_is_split_into_stored_blocks() {
+        let data = vec![0x55; MAX_STORED_BLOCK + 10];
+        let zlib = zlib_stored(&data);
+        assert_eq!(zlib.len(), 2 + 5 + MAX_STORED_BLOCK + 5 + 10 + 4);
+
+        assert_eq!(zlib[2..7], [0x00, 0xFF, 0xFF, 0x00, 0x00]);
+        let second = 7 + MAX_STORED_BLOCK;
+        assert_eq!(zlib[second..second + 5], [0x01, 0x0A, 0x00, 0xF5, 0xFF]);
+        assert_eq!(zlib[zlib.len() - 4..], adler32(&data).to_be_bytes());
+    }
+}
@@ -0,0 +1,164 @@
+use std::fs;
+
+use nes_emulator::{nes::controller::Button, Nes};
+
+// Buttons a player holds from a given frame on, until their next event
+pub struct InputEvent {
+    pub frame: u64,
+    pub player: usize,
+    pub buttons: Vec<Button>,
+}
+
+// Reads an input script, one event per line: `<frame> <player> <buttons>`, with the
+// buttons joined by `+`, or `-` to release them all:
+//
+//     # Player 1 presses Start on frame 60, then walks right from frame 90
+//     60 1 start
+//     61 1 -
+//     90 1 right+b
+pub fn load_input(path: &str) -> Result<Vec<InputEvent>, String> {
+    let script = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+
+    let mut events = Vec::new();
+    for (number, line) in script.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let event =
+            parse_event(line).map_err(|e| format!("{}: line {}: {}", path, number + 1, e))?;
+        events.push(event);
+    }
+
+    // Stable, so events on the same frame keep the script's order
+    events.sort_by_key(|event| event.frame);
+    Ok(events)
+}
+
+fn parse_event(line: &str) -> Result<InputEvent, String> {
+    let [frame, player, buttons] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+        return Err("expected `<frame> <player> <buttons>`".into());
+    };
+
+    let frame = frame
+        .parse()
+        .map_err(|_| format!("invalid frame `{}`", frame))?;
+    let player = match player.parse::<usize>() {
+        Ok(player @ 1..=2) => player - 1,
+        _ => return Err(format!("unknown player `{}`", player)),
+    };
+    let buttons = match buttons {
+        "-" => Vec::new(),
+        _ => buttons
+            .split('+')
+            .map(|button| {
+                Button::from_name(button).ok_or_else(|| format!("unknown button `{}`", button))
+            })
+            .collect::<Result<_, _>>()?,
+    };
+
+    Ok(InputEvent {
+        frame,
+        player,
+        buttons,
+    })
+}
+
+// Stops the run early, checked after every instruction
+#[derive(Clone, Copy, Debug)]
+pub enum Condition {
+    // `pc=<address>`
+    Pc(u16),
+    // `<address>=<value>`, e.g. the status byte test ROMs leave at $6000
+    Memory { addr: u16, value: u8 },
+}
+
+impl Condition {
+    pub fn parse(condition: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid condition `{}`", condition);
+        let (target, value) = condition.split_once('=').ok_or_else(invalid)?;
+
+        if target.eq_ignore_ascii_case("pc") {
+            return Ok(Condition::Pc(parse_hex(value).ok_or_else(invalid)?));
+        }
+        Ok(Condition::Memory {
+            addr: parse_hex(target).ok_or_else(invalid)?,
+            value: parse_hex(value)
+                .and_then(|value| u8::try_from(value).ok())
+                .ok_or_else(invalid)?,
+        })
+    }
+
+    pub fn is_met(self, nes: &Nes) -> bool {
+        match self {
+            Condition::Pc(pc) => nes.cpu().pc == pc,
+            Condition::Memory { addr, value } => nes.peek(addr) == value,
+        }
+    }
+}
+
+// Hexadecimal, with or without a leading `$`
+fn parse_hex(number: &str) -> Option<u16> {
+    u16::from_str_radix(number.trim().trim_start_matches('$'), 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_input_events() {
+        let event = parse_event("90 2 right+b").unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(event.frame, 90);
+        assert_eq!(event.player, 1);
+        assert_eq!(event.buttons, [Button::Right, Button::B]);
+
+        let event = parse_event("61  1\t-").unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!((event.frame, event.player), (61, 0));
+        assert!(event.buttons.is_empty());
+    }
+
+    #[test]
+    fn input_event_errors() {
+        let cases = [
+            ("60 1", "expected `<frame> <player> <buttons>`"),
+            ("60 1 start extra", "expected `<frame> <player> <buttons>`"),
+            ("sixty 1 start", "invalid frame `sixty`"),
+            ("60 3 start", "unknown player `3`"),
+            ("60 0 start", "unknown player `0`"),
+            ("60 1 start+turbo", "unknown button `turbo`"),
+        ];
+        for (line, error) in cases {
+            assert_eq!(parse_event(line).err().as_deref(), Some(error), "{}", line);
+        }
+    }
+
+    #[test]
+    fn parses_conditions() {
+        assert!(matches!(
+            Condition::parse("pc=8000"),
+            Ok(Condition::Pc(0x8000))
+        ));
+        assert!(matches!(
+            Condition::parse("PC=$C5F5"),
+            Ok(Condition::Pc(0xC5F5))
+        ));
+        assert!(matches!(
+            Condition::parse("$6000=80"),
+            Ok(Condition::Memory {
+                addr: 0x6000,
+                value: 0x80
+            })
+        ));
+    }
+
+    #[test]
+    fn condition_errors() {
+        for condition in ["pc", "pc=", "pc=10000", "6000=100", "6000=$zz", "x=1", "=1"] {
+            assert_eq!(
+                Condition::parse(condition).err(),
+                Some(format!("invalid condition `{}`", condition))
+            );
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::{collections::HashMap, fs, io};
+
+use sdl2::{controller::Button as PadButton, keyboard::Keycode};
+
+use nes_emulator::nes::controller::Button;
+
+pub static CONFIG_PATH: &str = "input.cfg";
+pub const PLAYERS: usize = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Keyboard,
+    Gamepad,
+}
+
+// Maps keys and game controller buttons to the buttons of both players' controllers.
+// Game controllers are handed to players in the order they are connected.
+//
+// The config file has one binding per line, `<player>.<button> = key:<name>` or
+// `<player>.<button> = pad:<name>`, using SDL's key and controller button names:
+//
+//     # Player 1 fires with C instead of X, or the right shoulder button
+//     1.a = key:C
+//     1.a = pad:rightshoulder
+//
+// A button bound in the file loses its default bindings from the same source, and
+// can be bound several times.
+pub struct InputBindings {
+    keys: HashMap<Keycode, (usize, Button)>,
+    pad_buttons: [HashMap<PadButton, Button>; PLAYERS],
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let keys = [
+            (Keycode::X, (0, Button::A)),
+            (Keycode::Z, (0, Button::B)),
+            (Keycode::RShift, (0, Button::Select)),
+            (Keycode::Return, (0, Button::Start)),
+            (Keycode::Up, (0, Button::Up)),
+            (Keycode::Down, (0, Button::Down)),
+            (Keycode::Left, (0, Button::Left)),
+            (Keycode::Right, (0, Button::Right)),
+            (Keycode::H, (1, Button::A)),
+            (Keycode::G, (1, Button::B)),
+            (Keycode::T, (1, Button::Select)),
+            (Keycode::Y, (1, Button::Start)),
+            (Keycode::W, (1, Button::Up)),
+            (Keycode::S, (1, Button::Down)),
+            (Keycode::A, (1, Button::Left)),
+            (Keycode::D, (1, Button::Right)),
+        ];
+
+        // Laid out like the NES pad, B on the left and A on the right
+        let pad_buttons = HashMap::from([
+            (PadButton::B, Button::A),
+            (PadButton::A, Button::B),
+            (PadButton::Back, Button::Select),
+            (PadButton::Start, Button::Start),
+            (PadButton::DPadUp, Button::Up),
+            (PadButton::DPadDown, Button::Down),
+            (PadButton::DPadLeft, Button::Left),
+            (PadButton::DPadRight, Button::Right),
+        ]);
+
+        Self {
+            keys: HashMap::from(keys),
+            pad_buttons: [pad_buttons.clone(), pad_buttons],
+        }
+    }
+}
+
+impl InputBindings {
+    // Falls back to the default bindings when there is no config file
+    pub fn load(path: &str) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(config) => Self::parse(&config).map_err(|e| format!("{}: {}", path, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {}", path, e)),
+        }
+    }
+
+    fn parse(config: &str) -> Result<Self, String> {
+        let mut bindings = Self::default();
+        let mut rebound = Vec::new();
+
+        for (number, line) in config.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            bindings
+                .parse_binding(line, &mut rebound)
+                .map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+
+        Ok(bindings)
+    }
+
+    fn parse_binding(
+        &mut self,
+        line: &str,
+        rebound: &mut Vec<(usize, Button, Source)>,
+    ) -> Result<(), String> {
+        let (target, input) = line
+            .split_once('=')
+            .ok_or("expected `<player>.<button> = <input>`")?;
+        let (player, button) = target
+            .trim()
+            .split_once('.')
+            .ok_or("expected `<player>.<button>`")?;
+        let player = match player.parse::<usize>() {
+            Ok(player @ 1..=PLAYERS) => player - 1,
+            _ => return Err(format!("unknown player `{}`", player)),
+        };
+        let button =
+            Button::from_name(button).ok_or_else(|| format!("unknown button `{}`", button))?;
+
+        let (source, name) = input
+            .trim()
+            .split_once(':')
+            .ok_or("expected `key:<name>` or `pad:<name>`")?;
+        let source = match source {
+            "key" => Source::Keyboard,
+            "pad" => Source::Gamepad,
+            _ => return Err(format!("unknown input `{}`", source)),
+        };
+
+        // The first binding of a button in the file replaces its defaults
+        if !rebound.contains(&(player, button, source)) {
+            rebound.push((player, button, source));
+            match source {
+                Source::Keyboard => self.keys.retain(|_, bound| *bound != (player, button)),
+                Source::Gamepad => self.pad_buttons[player].retain(|_, bound| *bound != button),
+            }
+        }
+
+        match source {
+            Source::Keyboard => {
+                let keycode =
+                    Keycode::from_name(name).ok_or_else(|| format!("unknown key `{}`", name))?;
+                self.keys.insert(keycode, (player, button));
+            }
+            Source::Gamepad => {
+                let pad_button = PadButton::from_string(name)
+                    .ok_or_else(|| format!("unknown controller button `{}`", name))?;
+                self.pad_buttons[player].insert(pad_button, button);
+            }
+        }
+        Ok(())
+    }
+
+    // The player and button a key is bound to
+    pub fn key(&self, keycode: Keycode) -> Option<(usize, Button)> {
+        self.keys.get(&keycode).copied()
+    }
+
+    pub fn pad_button(&self, player: usize, pad_button: PadButton) -> Option<Button> {
+        self.pad_buttons.get(player)?.get(&pad_button).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(config: &str) -> Option<String> {
+        InputBindings::parse(config).err()
+    }
+
+    #[test]
+    fn an_empty_config_keeps_the_defaults() {
+        let bindings = InputBindings::parse("# nothing\n\n").unwrap();
+        assert_eq!(bindings.key(Keycode::X), Some((0, Button::A)));
+        assert_eq!(bindings.key(Keycode::H), Some((1, Button::A)));
+        assert_eq!(bindings.pad_button(1, PadButton::B), Some(Button::A));
+    }
+
+    #[test]
+    fn a_binding_replaces_the_defaults_from_its_source() {
+        let bindings = InputBindings::parse("1.a = key:C\n1.a = key:V # turbo").unwrap();
+        assert_eq!(bindings.key(Keycode::C), Some((0, Button::A)));
+        assert_eq!(bindings.key(Keycode::V), Some((0, Button::A)));
+        assert_eq!(bindings.key(Keycode::X), None);
+        assert_eq!(bindings.key(Keycode::H), Some((1, Button::A)));
+        assert_eq!(bindings.pad_button(0, PadButton::B), Some(Button::A));
+    }
+
+    #[test]
+    fn pad_bindings_belong_to_one_player() {
+        let bindings = InputBindings::parse("2.start = pad:rightshoulder").unwrap();
+        assert_eq!(
+            bindings.pad_button(1, PadButton::RightShoulder),
+            Some(Button::Start)
+        );
+        assert_eq!(bindings.pad_button(1, PadButton::Start), None);
+        assert_eq!(
+            bindings.pad_button(0, PadButton::Start),
+            Some(Button::Start)
+        );
+        assert_eq!(bindings.key(Keycode::Y), Some((1, Button::Start)));
+    }
+
+    #[test]
+    fn errors_name_the_line_and_the_problem() {
+        let cases = [
+            ("1.a key:C", "expected `<player>.<button> = <input>`"),
+            ("a = key:C", "expected `<player>.<button>`"),
+            ("3.a = key:C", "unknown player `3`"),
+            ("1.turbo = key:C", "unknown button `turbo`"),
+            ("1.a = C", "expected `key:<name>` or `pad:<name>`"),
+            ("1.a = mouse:left", "unknown input `mouse`"),
+            ("1.a = key:Nope", "unknown key `Nope`"),
+            ("1.a = pad:nope", "unknown controller button `nope`"),
+        ];
+        for (line, error) in cases {
+            assert_eq!(parse_error(line), Some(format!("line 1: {}", error)));
+        }
+
+        assert_eq!(
+            parse_error("# player 3\n\n3.a = key:C"),
+            Some("line 3: unknown player `3`".into())
+        );
+    }
+}
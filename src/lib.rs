@@ -0,0 +1,172 @@
+pub mod nes;
+
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use nes::{
+    apu::Apu,
+    cartridge::{Cartridge, CartridgeError},
+    controller::{Button, Controllers},
+    mos_6502::Mos6502,
+    palette,
+    ppu::Ppu,
+    region::Region,
+    system::System,
+};
+
+// Samples per second produced until set_sample_rate says otherwise
+pub const DEFAULT_SAMPLE_RATE: f32 = 44_100.0;
+
+// A whole console with a cartridge inserted, for driving the emulator without a
+// frontend. The lower level parts are still reachable through the nes module.
+pub struct Nes {
+    system: System,
+    ppu: Rc<RefCell<Ppu>>,
+    apu: Rc<RefCell<Apu>>,
+    controllers: Rc<RefCell<Controllers>>,
+}
+
+impl Nes {
+    // The region comes from the cartridge header
+    pub fn load_rom(path: impl AsRef<Path>) -> Result<Self, CartridgeError> {
+        Ok(Self::new(Cartridge::load(path)?, None))
+    }
+
+    pub fn from_rom_bytes(bytes: &[u8]) -> Result<Self, CartridgeError> {
+        Ok(Self::new(Cartridge::from_bytes(bytes)?, None))
+    }
+
+    // Powers on a console with the cartridge inserted. None picks the region from the
+    // cartridge header.
+    pub fn new(cartridge: Cartridge, region: Option<Region>) -> Self {
+        let region = region.unwrap_or_else(|| Region::from_header(cartridge.header.timing));
+        let system = System::new(cartridge, region, DEFAULT_SAMPLE_RATE);
+        Self {
+            ppu: system.ppu.clone().expect("the system has a cartridge"),
+            apu: system.apu.clone().expect("the system has a cartridge"),
+            controllers: system
+                .controllers
+                .clone()
+                .expect("the system has a cartridge"),
+            system,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.system.reset();
+    }
+
+    pub fn step_instruction(&mut self) {
+        self.system.step_instruction();
+    }
+
+    // Runs until the start of the next vertical blank, when the frame buffer holds a
+    // whole picture
+    pub fn run_frame(&mut self) {
+        self.system.run_frame();
+    }
+
+    pub fn region(&self) -> Region {
+        self.system.region()
+    }
+
+    pub fn frame_rate(&self) -> f64 {
+        self.system.region().frame_rate()
+    }
+
+    pub fn cpu(&self) -> &Mos6502 {
+        &self.system.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut Mos6502 {
+        &mut self.system.cpu
+    }
+
+    // Frames completed since power-on
+    pub fn frame(&self) -> u64 {
+        self.ppu.borrow().frame()
+    }
+
+    // The last picture as palette indices, one byte per pixel, 256x240
+    pub fn frame_buffer(&self) -> Vec<u8> {
+        self.ppu.borrow().frame_buffer().to_vec()
+    }
+
+    // The last picture as packed RGB24, 256x240
+    pub fn frame_buffer_rgb(&self) -> Vec<u8> {
+        palette::to_rgb(self.ppu.borrow().frame_buffer())
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.apu.borrow_mut().set_sample_rate(sample_rate);
+    }
+
+    // Mono samples in -1.0..1.0 produced since the last call. About a second is kept
+    // when they are never taken.
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+        self.apu.borrow_mut().take_samples()
+    }
+
+    // Players are 0 and 1, any other player is ignored
+    pub fn set_button(&mut self, player: usize, button: Button, pressed: bool) {
+        self.controllers
+            .borrow_mut()
+            .set_button(player, button, pressed);
+    }
+
+    // Replaces everything a player holds, releasing the buttons not listed
+    pub fn set_controller(&mut self, player: usize, buttons: &[Button]) {
+        self.controllers.borrow_mut().set_buttons(player, buttons);
+    }
+
+    // Reads CPU memory without side effects
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.system.cpu.peek_byte(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NROM with 16 KiB of PRG ROM, 8 KiB of CHR ROM and a reset vector at $8000
+    fn nrom() -> Vec<u8> {
+        let mut rom = b"NES\x1A\x01\x01".to_vec();
+        rom.resize(16, 0);
+        let mut prg_rom = vec![0xEA; 16 * 1024];
+        prg_rom[0x3FFC..=0x3FFD].copy_from_slice(&[0x00, 0x80]);
+        rom.extend(prg_rom);
+        rom.extend([0; 8 * 1024]);
+        rom
+    }
+
+    fn read_port(nes: &mut Nes, addr: u16) -> Vec<u8> {
+        nes.cpu_mut().write(0x4016, 1);
+        nes.cpu_mut().write(0x4016, 0);
+        (0..10)
+            .map(|_| nes.cpu_mut().read_byte(addr) & 0x01)
+            .collect()
+    }
+
+    #[test]
+    fn controllers_shift_out_the_buttons_held() {
+        let mut nes = Nes::from_rom_bytes(&nrom()).unwrap();
+        nes.set_controller(0, &[Button::A, Button::Start, Button::Right]);
+        nes.set_button(1, Button::B, true);
+
+        assert_eq!(read_port(&mut nes, 0x4016), [1, 0, 0, 1, 0, 0, 0, 1, 1, 1]);
+        assert_eq!(read_port(&mut nes, 0x4017), [0, 1, 0, 0, 0, 0, 0, 0, 1, 1]);
+
+        nes.set_controller(0, &[Button::Select]);
+        assert_eq!(read_port(&mut nes, 0x4016), [0, 0, 1, 0, 0, 0, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn buttons_of_unknown_players_are_ignored() {
+        let mut nes = Nes::from_rom_bytes(&nrom()).unwrap();
+        nes.set_controller(2, &[Button::A]);
+        nes.set_button(usize::MAX, Button::B, true);
+
+        assert_eq!(read_port(&mut nes, 0x4016), [0, 0, 0, 0, 0, 0, 0, 0, 1, 1]);
+        assert_eq!(read_port(&mut nes, 0x4017), [0, 0, 0, 0, 0, 0, 0, 0, 1, 1]);
+    }
+}
@@ -1,24 +1,38 @@
-mod nes;
-use nes::{bus::Bus, disassembler::disassemble, mos_6502::Mos6502};
+mod audio;
+mod input;
+use audio::AudioOutput;
+use input::{InputBindings, CONFIG_PATH, PLAYERS};
+use nes_emulator::{
+    nes::{
+        bus::Bus, cartridge::Cartridge, controller::Button, disassembler::disassemble,
+        mos_6502::ExecutionMode, palette, ppu, region::Region, system::System,
+    },
+    DEFAULT_SAMPLE_RATE,
+};
 
-use std::{cell::RefCell, rc::Rc, time::Duration};
+use std::{
+    env, thread,
+    time::{Duration, Instant},
+};
 
 use sdl2::{
+    controller::{Button as PadButton, GameController},
     event::Event,
     keyboard::Keycode,
-    pixels::Color,
+    pixels::{Color, PixelFormatEnum},
     rect::Rect,
-    render::{Canvas, TextureCreator, TextureQuery},
-    sys::KeyCode,
-    ttf::{Font, Sdl2TtfContext},
-    video::{Window, WindowContext},
-    Sdl,
+    render::{BlendMode, Canvas, Texture, TextureCreator, TextureQuery},
+    ttf::Sdl2TtfContext,
+    video::{FullscreenType, Window, WindowContext},
+    GameControllerSubsystem, Sdl,
 };
 
 extern crate sdl2;
 
-static SCREEN_WIDTH: u32 = 640;
-static SCREEN_HEIGHT: u32 = 480;
+static FRAME_WIDTH: u32 = ppu::SCREEN_WIDTH as u32;
+static FRAME_HEIGHT: u32 = ppu::SCREEN_HEIGHT as u32;
+// Lines at the top and bottom of the frame that most TVs hide
+static OVERSCAN_LINES: u32 = 8;
 
 macro_rules! rect {
     ($x:expr, $y:expr, $w:expr, $h:expr) => {
@@ -26,27 +40,121 @@ macro_rules! rect {
     };
 }
 
+// How the NES frame is laid out in the window
+#[derive(Clone, Copy)]
+struct VideoOptions {
+    scale: u32,
+    // Stretches pixels to the 8:7 shape they have on a TV
+    aspect_correction: bool,
+    crop_overscan: bool,
+    fullscreen: bool,
+}
+
+impl Default for VideoOptions {
+    fn default() -> Self {
+        Self {
+            scale: 3,
+            aspect_correction: true,
+            crop_overscan: false,
+            fullscreen: false,
+        }
+    }
+}
+
+impl VideoOptions {
+    fn source_rect(&self) -> Rect {
+        if self.crop_overscan {
+            rect!(0, OVERSCAN_LINES, FRAME_WIDTH, self.visible_height())
+        } else {
+            rect!(0, 0, FRAME_WIDTH, FRAME_HEIGHT)
+        }
+    }
+
+    fn visible_height(&self) -> u32 {
+        if self.crop_overscan {
+            FRAME_HEIGHT - 2 * OVERSCAN_LINES
+        } else {
+            FRAME_HEIGHT
+        }
+    }
+
+    fn display_size(&self, scale: u32) -> (u32, u32) {
+        let width = if self.aspect_correction {
+            (FRAME_WIDTH * scale * 8 + 3) / 7
+        } else {
+            FRAME_WIDTH * scale
+        };
+        (width, self.visible_height() * scale)
+    }
+
+    // The frame at the largest whole scale that fits the output, centred
+    fn target_rect(&self, output_width: u32, output_height: u32) -> Rect {
+        let scale = (2..)
+            .take_while(|&scale| {
+                let (width, height) = self.display_size(scale);
+                width <= output_width && height <= output_height
+            })
+            .last()
+            .unwrap_or(1);
+        let (width, height) = self.display_size(scale);
+        rect!(
+            (output_width as i32 - width as i32) / 2,
+            (output_height as i32 - height as i32) / 2,
+            width,
+            height
+        )
+    }
+}
+
 struct SDLEngine {
     canvas: Canvas<Window>,
     texture_creator: TextureCreator<WindowContext>,
     ttf_context: Sdl2TtfContext,
     sdl_context: Sdl,
+    game_controller_subsystem: GameControllerSubsystem,
+    // Connected game controllers, in player order
+    game_controllers: Vec<GameController>,
+    input_bindings: InputBindings,
+    video: VideoOptions,
+    show_overlay: bool,
+    // None when no audio device could be opened
+    audio: Option<AudioOutput>,
+    frame_duration: Duration,
 }
 impl SDLEngine {
-    pub fn new() -> Result<Self, String> {
+    pub fn new(
+        input_bindings: InputBindings,
+        video: VideoOptions,
+        frame_rate: f64,
+    ) -> Result<Self, String> {
         let sdl_context = sdl2::init().map_err(|e| e.to_string())?;
 
         let video_subsystem = sdl_context.video().map_err(|e| e.to_string())?;
 
+        // Controllers already plugged in are reported as added by the first poll
+        let game_controller_subsystem = sdl_context.game_controller()?;
+
+        let audio_subsystem = sdl_context.audio()?;
+        let audio = AudioOutput::open(&audio_subsystem, DEFAULT_SAMPLE_RATE, frame_rate as f32)
+            .map_err(|e| println!("Could not open audio device: {}", e))
+            .ok();
+
         let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
 
-        let window = video_subsystem
-            .window("Rust NES Emulator", SCREEN_WIDTH, SCREEN_HEIGHT)
+        let (window_width, window_height) = video.display_size(video.scale);
+        let mut window = video_subsystem
+            .window("Rust NES Emulator", window_width, window_height)
             .position_centered()
+            .resizable()
             .build()
             .map_err(|e| e.to_string())?;
+        if video.fullscreen {
+            window.set_fullscreen(FullscreenType::Desktop)?;
+        }
 
-        let canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+        let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+        // Lets the debug overlay darken the frame behind it
+        canvas.set_blend_mode(BlendMode::Blend);
 
         let texture_creator = canvas.texture_creator();
 
@@ -55,11 +163,24 @@ impl SDLEngine {
             texture_creator,
             ttf_context,
             sdl_context,
+            game_controller_subsystem,
+            game_controllers: Vec::new(),
+            input_bindings,
+            video,
+            show_overlay: true,
+            audio,
+            frame_duration: Duration::from_secs_f64(1.0 / frame_rate),
         })
     }
 
     pub fn draw(&mut self, app: &mut App) -> Result<(), String> {
         let mut event_pump = self.sdl_context.event_pump().map_err(|e| e.to_string())?;
+        // Owned here rather than in the engine, since the texture borrows its creator
+        let texture_creator = self.canvas.texture_creator();
+        let mut frame_texture = texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGB24, FRAME_WIDTH, FRAME_HEIGHT)
+            .map_err(|e| e.to_string())?;
+        let mut next_frame = Instant::now();
         'running: loop {
             for event in event_pump.poll_iter() {
                 match event {
@@ -68,10 +189,41 @@ impl SDLEngine {
                         keycode: Some(Keycode::Escape),
                         ..
                     } => break 'running,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F1),
+                        ..
+                    } => self.show_overlay = !self.show_overlay,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F11),
+                        ..
+                    } => self.toggle_fullscreen()?,
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        ..
+                    } => {
+                        if let Some((player, button)) = self.input_bindings.key(keycode) {
+                            app.set_button(player, button, true);
+                        }
+                    }
                     Event::KeyUp {
-                        keycode: Some(Keycode::Space),
+                        keycode: Some(keycode),
                         ..
-                    } => app.key_up(Keycode::Space),
+                    } => match self.input_bindings.key(keycode) {
+                        Some((player, button)) => app.set_button(player, button, false),
+                        None => app.key_up(keycode),
+                    },
+                    Event::ControllerDeviceAdded { which, .. } => {
+                        self.connect_game_controller(which)
+                    }
+                    Event::ControllerDeviceRemoved { which, .. } => self
+                        .game_controllers
+                        .retain(|controller| controller.instance_id() != which),
+                    Event::ControllerButtonDown { which, button, .. } => {
+                        self.game_controller_button(app, which, button, true)
+                    }
+                    Event::ControllerButtonUp { which, button, .. } => {
+                        self.game_controller_button(app, which, button, false)
+                    }
                     _ => {}
                 }
             }
@@ -79,22 +231,127 @@ impl SDLEngine {
             self.canvas.set_draw_color(Color::BLACK);
             self.canvas.clear();
 
-            app.draw(self)?;
+            app.update();
 
-            self.canvas.present();
+            if let Some(ppu) = &app.system.ppu {
+                self.draw_frame(&mut frame_texture, ppu.borrow().frame_buffer())?;
+            }
+
+            if self.show_overlay {
+                self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+                self.canvas.fill_rect(None)?;
+                app.draw(self)?;
+            }
 
             self.canvas.present();
-            ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60))
+
+            let audio_playing = self.play_audio(app)?;
+            self.wait_for_next_frame(&mut next_frame, audio_playing);
         }
         Ok(())
     }
 
+    fn draw_frame(&mut self, texture: &mut Texture, frame: &[u8]) -> Result<(), String> {
+        texture
+            .update(None, &palette::to_rgb(frame), FRAME_WIDTH as usize * 3)
+            .map_err(|e| e.to_string())?;
+
+        let (output_width, output_height) = self.canvas.output_size()?;
+        let target = self.video.target_rect(output_width, output_height);
+        self.canvas.copy(texture, self.video.source_rect(), target)
+    }
+
+    // Queues what the APU produced since the last frame, returning whether it produced
+    // anything
+    fn play_audio(&self, app: &App) -> Result<bool, String> {
+        let (Some(audio), Some(apu)) = (&self.audio, &app.system.apu) else {
+            return Ok(false);
+        };
+        let mut apu = apu.borrow_mut();
+        let samples = apu.take_samples();
+        audio.queue(&samples)?;
+        apu.set_sample_rate(audio.adjusted_sample_rate());
+        Ok(!samples.is_empty())
+    }
+
+    // While the APU produces samples the audio device is the clock: the loop waits for
+    // it to play the queue down to its target. Otherwise a timer holds the frame rate.
+    fn wait_for_next_frame(&self, next_frame: &mut Instant, audio_playing: bool) {
+        if let Some(audio) = self.audio.as_ref().filter(|_| audio_playing) {
+            while audio.is_ahead() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            *next_frame = Instant::now();
+            return;
+        }
+
+        *next_frame += self.frame_duration;
+        let now = Instant::now();
+        if *next_frame > now {
+            thread::sleep(*next_frame - now);
+        } else {
+            // Too far behind to catch up
+            *next_frame = now;
+        }
+    }
+
+    fn toggle_fullscreen(&mut self) -> Result<(), String> {
+        self.video.fullscreen = !self.video.fullscreen;
+        let fullscreen = if self.video.fullscreen {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Off
+        };
+        self.canvas.window_mut().set_fullscreen(fullscreen)
+    }
+
+    fn output_size(&self) -> Result<(u32, u32), String> {
+        self.canvas.output_size()
+    }
+
+    fn connect_game_controller(&mut self, joystick_index: u32) {
+        if self.game_controllers.len() == PLAYERS {
+            return;
+        }
+        match self.game_controller_subsystem.open(joystick_index) {
+            Ok(controller) => {
+                println!(
+                    "Player {}: {}",
+                    self.game_controllers.len() + 1,
+                    controller.name()
+                );
+                self.game_controllers.push(controller);
+            }
+            Err(e) => println!("Could not open game controller {}: {}", joystick_index, e),
+        }
+    }
+
+    fn game_controller_button(
+        &self,
+        app: &mut App,
+        instance_id: u32,
+        pad_button: PadButton,
+        pressed: bool,
+    ) {
+        let Some(player) = self
+            .game_controllers
+            .iter()
+            .position(|controller| controller.instance_id() == instance_id)
+        else {
+            return;
+        };
+        if let Some(button) = self.input_bindings.pad_button(player, pad_button) {
+            app.set_button(player, button, pressed);
+        }
+    }
+
     fn draw_text(&mut self, text: String, x: isize, y: isize) -> Result<(), String> {
+        let (output_width, _) = self.output_size()?;
         let mut font = self.ttf_context.load_font("SourceCodePro-Light.otf", 16)?;
         font.set_style(sdl2::ttf::FontStyle::BOLD);
         let surface = font
             .render(text.as_str())
-            .blended_wrapped(Color::WHITE, SCREEN_WIDTH)
+            .blended_wrapped(Color::WHITE, output_width)
             .map_err(|e| e.to_string())?;
         let texture = self
             .texture_creator
@@ -110,15 +367,38 @@ impl SDLEngine {
 }
 
 struct App {
-    cpu: Mos6502,
+    system: System,
+    // Whether a frame is emulated on every loop, rather than stepping with Space
+    running: bool,
 }
 
 impl App {
-    fn new() -> Self {
-        let bus = Rc::new(RefCell::new(Bus::new()));
-        let mut cpu = Mos6502::new(Rc::clone(&bus));
-        cpu.pc = 0x8000;
+    // The region defaults to the one in the cartridge header
+    fn new(cartridge: Option<Cartridge>, region: Option<Region>) -> Self {
+        match cartridge {
+            Some(cartridge) => {
+                let region = region.unwrap_or_else(|| Region::from_header(cartridge.header.timing));
+                Self {
+                    system: System::new(cartridge, region, DEFAULT_SAMPLE_RATE),
+                    running: true,
+                }
+            }
+            // The demo program runs on a bare 6502 with RAM across the whole address
+            // space, one instruction at a time
+            None => {
+                let mut bus = Bus::bare();
+                Self::load_demo_program(&mut bus);
+                let mut system = System::bare(bus);
+                system.reset();
+                Self {
+                    system,
+                    running: false,
+                }
+            }
+        }
+    }
 
+    fn load_demo_program(bus: &mut Bus) {
         for (i, item) in [
             0xa9, 0x01, 0x8d, 0x00, 0x02, 0xa9, 0x05, 0x8d, 0x01, 0x02, 0xa9, 0x08, 0x8d, 0x02,
             0x02,
@@ -126,27 +406,53 @@ impl App {
         .iter()
         .enumerate()
         {
-            bus.borrow_mut().write(0x8000 + i as u16, *item);
+            bus.write(0x8000 + i as u16, *item);
         }
 
-        Self { cpu }
+        // Reset vector
+        bus.write(0xFFFC, 0x00);
+        bus.write(0xFFFD, 0x80);
+    }
+
+    fn set_button(&mut self, player: usize, button: Button, pressed: bool) {
+        if let Some(controllers) = &self.system.controllers {
+            controllers.borrow_mut().set_button(player, button, pressed);
+        }
     }
 
     fn key_up(&mut self, keycode: Keycode) {
         match keycode {
-            Keycode::Space => {
-                self.cpu.clock();
-                while self.cpu.cycles != 0 {
-                    self.cpu.clock();
-                }
+            Keycode::Space if !self.running => {
+                self.system.step_instruction();
                 println!("Step!")
             }
-            _ => {}
+            Keycode::P => self.running = !self.running,
+            Keycode::R => self.system.reset(),
+            Keycode::I => self.system.cpu.irq(),
+            Keycode::N => self.system.cpu.nmi(),
+            Keycode::C => {
+                let cpu = &mut self.system.cpu;
+                cpu.execution_mode = match cpu.execution_mode {
+                    ExecutionMode::Fast => ExecutionMode::CycleAccurate,
+                    ExecutionMode::CycleAccurate => ExecutionMode::Fast,
+                }
+            }
+            _ => return,
+        }
+
+        self.system.finish_instruction();
+    }
+
+    fn update(&mut self) {
+        if self.running {
+            self.system.run_frame();
         }
     }
 
     fn draw(&mut self, engine: &mut SDLEngine) -> Result<(), String> {
-        let disassembled_program = disassemble(&self.cpu, self.cpu.pc, self.cpu.pc + 20).join("\n");
+        let (width, height) = engine.output_size()?;
+        let cpu = &self.system.cpu;
+        let disassembled_program = disassemble(cpu, cpu.pc, cpu.pc.wrapping_add(20)).join("\n");
 
         let debug_text = format!(
             "
@@ -154,47 +460,125 @@ PC: ${:04X}
 A: ${:02X} [{}]
 X: ${:02X} [{}]
 Y: ${:02X} [{}]
-Stack Ptr: ${:04X}
-Status: N V - B D I Z C
-        0 0   0 0 0 0 0
+Stack Ptr: ${:02X}
+Status: ${:02X} [{}]
 Space: Step Instruction
 R: Reset
 I: IRQ
 N: NMI
+C: Cycle-accurate mode [{:?}]
+P: Run/Pause [{}]
+F1: Hide overlay
+F11: Fullscreen
         ",
-            self.cpu.pc,
-            self.cpu.a,
-            self.cpu.a,
-            self.cpu.x,
-            self.cpu.x,
-            self.cpu.y,
-            self.cpu.y,
-            self.cpu.stack_ptr,
+            cpu.pc,
+            cpu.a,
+            cpu.a,
+            cpu.x,
+            cpu.x,
+            cpu.y,
+            cpu.y,
+            cpu.stack_ptr,
+            cpu.status_flags.bits(),
+            cpu.status_flags,
+            cpu.execution_mode,
+            if self.running { "Running" } else { "Paused" },
         );
         engine.draw_text(debug_text.trim().into(), 0, 0)?;
 
+        if let Some(ppu) = &self.system.ppu {
+            let ppu = ppu.borrow();
+            let debug_text = format!(
+                "PPU: Scanline {} Dot {} Frame {} [{:?}]",
+                ppu.scanline(),
+                ppu.dot(),
+                ppu.frame(),
+                self.system.region()
+            );
+            engine.draw_text(debug_text, 0, height as isize - 30)?;
+        }
+
         let debug_text = format!("Program:\n-> {}", disassembled_program,);
-        engine.draw_text(debug_text.trim().into(), SCREEN_WIDTH as isize / 2, 0)?;
+        engine.draw_text(debug_text.trim().into(), width as isize / 2, 0)?;
 
-        let debug_text = self
-            .cpu
+        let debug_text = cpu
             .bus
             .borrow()
-            .read_bulk(0x0200, 100)
+            .peek_bulk(0x0200, 100)
             .iter()
             .map(|x| format!("{:02X}", x))
             .collect::<Vec<String>>()
             .join(" ");
         let debug_text = format!("Memory: (0x0200 -> 0x0300)\n{}", debug_text);
-        engine.draw_text(debug_text.trim().into(), 0, SCREEN_HEIGHT as isize / 2 + 30)?;
+        engine.draw_text(debug_text.trim().into(), 0, height as isize / 2 + 30)?;
 
         Ok(())
     }
 }
 
+// Command line: [options] [rom]
+struct Options {
+    rom_path: Option<String>,
+    region: Option<Region>,
+    video: VideoOptions,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            rom_path: None,
+            region: None,
+            video: VideoOptions::default(),
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--scale" => {
+                    options.video.scale = args
+                        .next()
+                        .and_then(|scale| scale.parse().ok())
+                        .filter(|&scale| scale > 0)
+                        .ok_or("--scale expects a whole number above 0")?
+                }
+                "--region" => {
+                    options.region = Some(
+                        args.next()
+                            .and_then(|region| Region::from_name(&region))
+                            .ok_or("--region expects ntsc, pal or dendy")?,
+                    )
+                }
+                "--no-aspect-correction" => options.video.aspect_correction = false,
+                "--crop-overscan" => options.video.crop_overscan = true,
+                "--fullscreen" => options.video.fullscreen = true,
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ => options.rom_path = Some(arg),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
 fn main() -> Result<(), String> {
-    let mut app = App::new();
-    let mut engine = SDLEngine::new()?;
+    let options = Options::parse(env::args().skip(1))?;
+
+    let cartridge = match options.rom_path {
+        Some(path) => {
+            let cartridge = Cartridge::load(&path).map_err(|e| format!("{}: {}", path, e))?;
+            println!("Loaded {}: {}", path, cartridge.header);
+            Some(cartridge)
+        }
+        None => None,
+    };
+
+    let input_bindings = InputBindings::load(CONFIG_PATH)?;
+
+    let mut app = App::new(cartridge, options.region);
+    let mut engine = SDLEngine::new(
+        input_bindings,
+        options.video,
+        app.system.region().frame_rate(),
+    )?;
     engine.draw(&mut app)?;
     Ok(())
 }
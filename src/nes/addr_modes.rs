@@ -1,4 +1,3 @@
-
 pub enum AddrMode {
     Implied,
     Immediate,
@@ -12,11 +11,15 @@ pub enum AddrMode {
     IndirectOffsetX,
     IndirectOffsetY,
     Relative,
+
+    // 65C02 only
+    ZeroPageIndirect,
+    AbsoluteIndirectOffsetX,
 }
 
 use AddrMode::*;
 
-use super::mos_6502::Mos6502;
+use super::mos_6502::{CpuVariant, Mos6502};
 
 impl Mos6502 {
     pub fn handle_addr_mode(&mut self, addr_mode: AddrMode) -> u8 {
@@ -26,35 +29,32 @@ impl Mos6502 {
             }
             Immediate => {
                 self.addr_abs = self.pc;
-                self.pc += 1;
+                self.pc = self.pc.wrapping_add(1);
             }
             ZeroPage => {
                 self.addr_abs = self.read_byte(self.pc) as u16;
-                self.pc += 1;
-                self.addr_abs &= 0x00FF;
+                self.pc = self.pc.wrapping_add(1);
             }
             ZeroPageOffsetX => {
-                self.addr_abs = self.read_byte(self.pc + self.x as u16) as u16;
-                self.pc += 1;
-                self.addr_abs &= 0x00FF;
+                // Indexed zero page addressing never leaves the zero page
+                self.addr_abs = self.read_byte(self.pc).wrapping_add(self.x) as u16;
+                self.pc = self.pc.wrapping_add(1);
             }
             ZeroPageOffsetY => {
-                self.addr_abs = self.read_byte(self.pc + self.y as u16) as u16;
-                self.pc += 1;
-                self.addr_abs &= 0x00FF;
+                self.addr_abs = self.read_byte(self.pc).wrapping_add(self.y) as u16;
+                self.pc = self.pc.wrapping_add(1);
             }
             Absolute => {
                 let addr = self.read_word(self.pc);
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
 
                 self.addr_abs = addr;
             }
             AbsoluteOffsetX => {
                 let (addr, hi, _) = self.read_word_and_bytes(self.pc);
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
 
-                self.addr_abs = addr;
-                self.addr_abs += self.x as u16;
+                self.addr_abs = addr.wrapping_add(self.x as u16);
 
                 if self.addr_abs & 0xFF00 != ((hi as u16) << 8) {
                     return 1;
@@ -62,10 +62,9 @@ impl Mos6502 {
             }
             AbsoluteOffsetY => {
                 let (addr, hi, _) = self.read_word_and_bytes(self.pc);
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
 
-                self.addr_abs = addr;
-                self.addr_abs += self.y as u16;
+                self.addr_abs = addr.wrapping_add(self.y as u16);
 
                 if self.addr_abs & 0xFF00 != ((hi as u16) << 8) {
                     return 1;
@@ -73,45 +72,63 @@ impl Mos6502 {
             }
             Indirect => {
                 let (memory_pointer, _, lo) = self.read_word_and_bytes(self.pc);
-                self.pc += 2;
+                self.pc = self.pc.wrapping_add(2);
 
-                let low_byte = if lo == 0x00FF {
-                    // Simulate page boundary hardware bug
+                let high_byte = if lo == 0xFF && self.variant != CpuVariant::Cmos65C02 {
+                    // Simulate page boundary hardware bug, fixed on the 65C02
                     self.read_byte(memory_pointer & 0xFF00) as u16
                 } else {
                     // Behave normally
-                    self.read_byte(memory_pointer + 1) as u16
+                    self.read_byte(memory_pointer.wrapping_add(1)) as u16
                 };
 
-                let high_byte = self.read_byte(memory_pointer) as u16;
-                self.addr_abs = (low_byte << 8) | high_byte;
+                let low_byte = self.read_byte(memory_pointer) as u16;
+                self.addr_abs = (high_byte << 8) | low_byte;
             }
             IndirectOffsetX => {
-                let supplied_address = self.read_byte(self.pc);
-                self.pc += 1;
+                let supplied_address = self.read_byte(self.pc).wrapping_add(self.x);
+                self.pc = self.pc.wrapping_add(1);
 
-                self.addr_abs = self.read_word(supplied_address as u16 + self.x as u16);
+                self.addr_abs = self.read_zero_page_word(supplied_address);
             }
             IndirectOffsetY => {
                 let supplied_address = self.read_byte(self.pc);
-                self.pc += 1;
+                self.pc = self.pc.wrapping_add(1);
 
-                let (addr, hi, _) = self.read_word_and_bytes(supplied_address as u16);
-                self.addr_abs = addr;
-                self.addr_abs += self.y as u16;
+                let addr = self.read_zero_page_word(supplied_address);
+                self.addr_abs = addr.wrapping_add(self.y as u16);
 
-                if self.addr_abs & 0xFF00 != ((hi as u16) << 8) {
+                if self.addr_abs & 0xFF00 != addr & 0xFF00 {
                     return 1;
                 }
             }
             Relative => {
                 self.addr_rel = self.read_byte(self.pc) as u16;
-                self.pc += 1;
+                self.pc = self.pc.wrapping_add(1);
                 if self.addr_rel & 0b10000000 != 0 {
                     self.addr_rel |= 0xFF00;
                 }
             }
+            ZeroPageIndirect => {
+                let supplied_address = self.read_byte(self.pc);
+                self.pc = self.pc.wrapping_add(1);
+
+                self.addr_abs = self.read_zero_page_word(supplied_address);
+            }
+            AbsoluteIndirectOffsetX => {
+                let memory_pointer = self.read_word(self.pc).wrapping_add(self.x as u16);
+                self.pc = self.pc.wrapping_add(2);
+
+                self.addr_abs = self.read_word(memory_pointer);
+            }
         }
-        return 0;
+        0
+    }
+
+    // Pointers stored in the zero page wrap around instead of crossing into page one
+    fn read_zero_page_word(&self, addr: u8) -> u16 {
+        let low_byte = self.read_byte(addr as u16) as u16;
+        let high_byte = self.read_byte(addr.wrapping_add(1) as u16) as u16;
+        (high_byte << 8) | low_byte
     }
 }
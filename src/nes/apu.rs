@@ -0,0 +1,681 @@
+use super::{device::Device, region::Region};
+
+// Anything beyond this is dropped until the frontend catches up
+const MAX_BUFFERED_SECONDS: f32 = 1.0;
+// The console's output stage removes DC with a high-pass filter around 90 Hz
+const HIGH_PASS_CUTOFF: f32 = 90.0;
+
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+    192, 24, 72, 26, 16, 28, 32, 30,
+];
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+const TRIANGLE_SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+    13, 14, 15,
+];
+#[derive(Default)]
+struct Envelope {
+    start: bool,
+    looping: bool,
+    constant: bool,
+    volume: u8,
+    divider: u8,
+    decay: u8,
+}
+
+impl Envelope {
+    fn write(&mut self, value: u8) {
+        self.looping = value & 0x20 != 0;
+        self.constant = value & 0x10 != 0;
+        self.volume = value & 0x0F;
+    }
+
+    fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.constant {
+            self.volume
+        } else {
+            self.decay
+        }
+    }
+}
+
+#[derive(Default)]
+struct LengthCounter {
+    enabled: bool,
+    halted: bool,
+    value: u8,
+}
+
+impl LengthCounter {
+    fn load(&mut self, index: u8) {
+        if self.enabled {
+            self.value = LENGTH_TABLE[(index >> 3) as usize];
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.value = 0;
+        }
+    }
+
+    fn clock(&mut self) {
+        if !self.halted && self.value > 0 {
+            self.value -= 1;
+        }
+    }
+
+    fn active(&self) -> bool {
+        self.value > 0
+    }
+}
+
+struct Pulse {
+    // Pulse 1 negates its sweep with ones' complement, pulse 2 with two's complement
+    ones_complement: bool,
+    duty: u8,
+    step: u8,
+    period: u16,
+    timer: u16,
+    envelope: Envelope,
+    length: LengthCounter,
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_divider: u8,
+    sweep_reload: bool,
+}
+
+impl Pulse {
+    fn new(ones_complement: bool) -> Self {
+        Self {
+            ones_complement,
+            duty: 0,
+            step: 0,
+            period: 0,
+            timer: 0,
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_divider: 0,
+            sweep_reload: false,
+        }
+    }
+
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.duty = value >> 6;
+                self.length.halted = value & 0x20 != 0;
+                self.envelope.write(value);
+            }
+            1 => {
+                self.sweep_enabled = value & 0x80 != 0;
+                self.sweep_period = (value >> 4) & 0x07;
+                self.sweep_negate = value & 0x08 != 0;
+                self.sweep_shift = value & 0x07;
+                self.sweep_reload = true;
+            }
+            2 => self.period = (self.period & 0x0700) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | ((value as u16 & 0x07) << 8);
+                self.length.load(value);
+                self.step = 0;
+                self.envelope.start = true;
+            }
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let change = self.period >> self.sweep_shift;
+        if !self.sweep_negate {
+            self.period + change
+        } else if self.ones_complement {
+            self.period.saturating_sub(change + 1)
+        } else {
+            self.period.saturating_sub(change)
+        }
+    }
+
+    // The sweep unit mutes the channel even while disabled
+    fn muted(&self) -> bool {
+        self.period < 8 || self.sweep_target() > 0x07FF
+    }
+
+    fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.muted() {
+            self.period = self.sweep_target();
+        }
+
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    // Clocked every other CPU cycle
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            self.step = (self.step + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.length.active()
+            || self.muted()
+            || DUTY_TABLE[self.duty as usize][self.step as usize] == 0
+        {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+#[derive(Default)]
+struct Triangle {
+    period: u16,
+    timer: u16,
+    step: u8,
+    length: LengthCounter,
+    // The control flag doubles as the length counter halt flag
+    control: bool,
+    linear_reload_value: u8,
+    linear_counter: u8,
+    linear_reload: bool,
+}
+
+impl Triangle {
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.control = value & 0x80 != 0;
+                self.length.halted = self.control;
+                self.linear_reload_value = value & 0x7F;
+            }
+            2 => self.period = (self.period & 0x0700) | value as u16,
+            3 => {
+                self.period = (self.period & 0x00FF) | ((value as u16 & 0x07) << 8);
+                self.length.load(value);
+                self.linear_reload = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn clock_linear_counter(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    // Clocked every CPU cycle
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            if self.length.active() && self.linear_counter > 0 {
+                self.step = (self.step + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        // Ultrasonic periods are silenced instead of popping at the mixer
+        if self.period < 2 {
+            return 7;
+        }
+        TRIANGLE_SEQUENCE[self.step as usize]
+    }
+}
+
+struct Noise {
+    // Timer periods in CPU cycles, which depend on the region
+    periods: &'static [u16; 16],
+    mode: bool,
+    period: u16,
+    timer: u16,
+    shift: u16,
+    envelope: Envelope,
+    length: LengthCounter,
+}
+
+impl Noise {
+    fn new(periods: &'static [u16; 16]) -> Self {
+        Self {
+            periods,
+            mode: false,
+            period: periods[0],
+            timer: 0,
+            shift: 1,
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+        }
+    }
+
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.length.halted = value & 0x20 != 0;
+                self.envelope.write(value);
+            }
+            2 => {
+                self.mode = value & 0x80 != 0;
+                self.period = self.periods[(value & 0x0F) as usize];
+            }
+            3 => {
+                self.length.load(value);
+                self.envelope.start = true;
+            }
+            _ => {}
+        }
+    }
+
+    // Clocked every CPU cycle
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period - 1;
+            let tap = if self.mode { 6 } else { 1 };
+            let feedback = (self.shift ^ (self.shift >> tap)) & 0x01;
+            self.shift = (self.shift >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if !self.length.active() || self.shift & 0x01 != 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+struct Dmc {
+    // Timer periods in CPU cycles, which depend on the region
+    rates: &'static [u16; 16],
+    irq_enabled: bool,
+    looping: bool,
+    period: u16,
+    timer: u16,
+    level: u8,
+    sample_addr: u16,
+    sample_length: u16,
+    current_addr: u16,
+    bytes_remaining: u16,
+    buffer: Option<u8>,
+    shift: u8,
+    bits_remaining: u8,
+    silence: bool,
+    irq: bool,
+}
+
+impl Dmc {
+    fn new(rates: &'static [u16; 16]) -> Self {
+        Self {
+            rates,
+            irq_enabled: false,
+            looping: false,
+            period: rates[0],
+            timer: 0,
+            level: 0,
+            sample_addr: 0xC000,
+            sample_length: 1,
+            current_addr: 0xC000,
+            bytes_remaining: 0,
+            buffer: None,
+            shift: 0,
+            bits_remaining: 8,
+            silence: true,
+            irq: false,
+        }
+    }
+
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.irq_enabled = value & 0x80 != 0;
+                self.looping = value & 0x40 != 0;
+                self.period = self.rates[(value & 0x0F) as usize];
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+            }
+            1 => self.level = value & 0x7F,
+            2 => self.sample_addr = 0xC000 | ((value as u16) << 6),
+            _ => self.sample_length = ((value as u16) << 4) | 1,
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.irq = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_addr = self.sample_addr;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    // Address of the next sample byte, when the buffer needs refilling
+    fn dma_request(&self) -> Option<u16> {
+        (self.buffer.is_none() && self.bytes_remaining > 0).then_some(self.current_addr)
+    }
+
+    fn dma_transfer(&mut self, value: u8) {
+        self.buffer = Some(value);
+        self.current_addr = self.current_addr.checked_add(1).unwrap_or(0x8000);
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    // Clocked every CPU cycle
+    fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.period - 1;
+
+        if !self.silence {
+            if self.shift & 0x01 != 0 {
+                if self.level <= 125 {
+                    self.level += 2;
+                }
+            } else if self.level >= 2 {
+                self.level -= 2;
+            }
+        }
+        self.shift >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.buffer.take() {
+                Some(sample) => {
+                    self.silence = false;
+                    self.shift = sample;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+}
+
+// Ricoh 2A03 sound generation, clocked once per CPU cycle. Samples are mixed down to the
+// host sample rate and buffered until the frontend takes them.
+pub struct Apu {
+    region: Region,
+    pulse_1: Pulse,
+    pulse_2: Pulse,
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+    five_step_mode: bool,
+    frame_irq_inhibit: bool,
+    frame_irq: bool,
+    frame_cycle: u32,
+    odd_cycle: bool,
+    sample_rate: f32,
+    sample_clock: f64,
+    sample_sum: f32,
+    sample_count: u32,
+    high_pass_previous_input: f32,
+    high_pass_previous_output: f32,
+    samples: Vec<f32>,
+}
+
+impl Apu {
+    pub fn new(region: Region, sample_rate: f32) -> Self {
+        Self {
+            region,
+            pulse_1: Pulse::new(true),
+            pulse_2: Pulse::new(false),
+            triangle: Triangle::default(),
+            noise: Noise::new(region.noise_periods()),
+            dmc: Dmc::new(region.dmc_rates()),
+            five_step_mode: false,
+            frame_irq_inhibit: false,
+            frame_irq: false,
+            frame_cycle: 0,
+            odd_cycle: false,
+            sample_rate,
+            sample_clock: 0.0,
+            sample_sum: 0.0,
+            sample_count: 0,
+            high_pass_previous_input: 0.0,
+            high_pass_previous_output: 0.0,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    // Mono samples in -1.0..1.0 produced since the last call
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    fn status(&self) -> u8 {
+        (self.pulse_1.length.active() as u8)
+            | (self.pulse_2.length.active() as u8) << 1
+            | (self.triangle.length.active() as u8) << 2
+            | (self.noise.length.active() as u8) << 3
+            | ((self.dmc.bytes_remaining > 0) as u8) << 4
+            | (self.frame_irq as u8) << 6
+            | (self.dmc.irq as u8) << 7
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulse_1.envelope.clock();
+        self.pulse_2.envelope.clock();
+        self.noise.envelope.clock();
+        self.triangle.clock_linear_counter();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulse_1.length.clock();
+        self.pulse_2.length.clock();
+        self.triangle.length.clock();
+        self.noise.length.clock();
+        self.pulse_1.clock_sweep();
+        self.pulse_2.clock_sweep();
+    }
+
+    fn clock_frame_counter(&mut self) {
+        self.frame_cycle += 1;
+        let (four_step, five_step) = self.region.frame_counter_sequences();
+        let sequence = if self.five_step_mode {
+            five_step
+        } else {
+            four_step
+        };
+
+        match sequence.iter().position(|&cycle| cycle == self.frame_cycle) {
+            Some(0 | 2) => self.clock_quarter_frame(),
+            Some(step @ (1 | 3)) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+                if step == 3 && !self.five_step_mode && !self.frame_irq_inhibit {
+                    self.frame_irq = true;
+                }
+            }
+            Some(_) => self.frame_cycle = 0,
+            None => {}
+        }
+    }
+
+    // Non-linear mixing of the DACs, as approximated on the NESdev wiki
+    fn mix(&self) -> f32 {
+        let pulse = (self.pulse_1.output() + self.pulse_2.output()) as f32;
+        let pulse_out = if pulse == 0.0 {
+            0.0
+        } else {
+            95.88 / (8128.0 / pulse + 100.0)
+        };
+
+        let tnd = self.triangle.output() as f32 / 8227.0
+            + self.noise.output() as f32 / 12241.0
+            + self.dmc.level as f32 / 22638.0;
+        let tnd_out = if tnd == 0.0 {
+            0.0
+        } else {
+            159.79 / (1.0 / tnd + 100.0)
+        };
+
+        pulse_out + tnd_out
+    }
+
+    fn output_sample(&mut self) {
+        self.sample_sum += self.mix();
+        self.sample_count += 1;
+
+        let cpu_clock_rate = self.region.cpu_clock_rate();
+        self.sample_clock += self.sample_rate as f64;
+        if self.sample_clock < cpu_clock_rate {
+            return;
+        }
+        self.sample_clock -= cpu_clock_rate;
+
+        // Averaging the CPU-rate output over the sample period doubles as a low-pass filter
+        let input = self.sample_sum / self.sample_count as f32;
+        self.sample_sum = 0.0;
+        self.sample_count = 0;
+
+        let rc = 1.0 / (2.0 * std::f32::consts::PI * HIGH_PASS_CUTOFF);
+        let alpha = rc / (rc + 1.0 / self.sample_rate);
+        let output =
+            alpha * (self.high_pass_previous_output + input - self.high_pass_previous_input);
+        self.high_pass_previous_input = input;
+        self.high_pass_previous_output = output;
+
+        if (self.samples.len() as f32) < self.sample_rate * MAX_BUFFERED_SECONDS {
+            self.samples.push(output);
+        }
+    }
+}
+
+// CPU side: $4000-$4013, $4015 and $4017
+impl Device for Apu {
+    fn read(&mut self, addr: u16) -> Option<u8> {
+        let value = self.peek(addr);
+        if addr == 0x4015 {
+            self.frame_irq = false;
+        }
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4000..=0x4003 => self.pulse_1.write(addr & 0x03, value),
+            0x4004..=0x4007 => self.pulse_2.write(addr & 0x03, value),
+            0x4008..=0x400B => self.triangle.write(addr & 0x03, value),
+            0x400C..=0x400F => self.noise.write(addr & 0x03, value),
+            0x4010..=0x4013 => self.dmc.write(addr & 0x03, value),
+            0x4015 => {
+                self.pulse_1.length.set_enabled(value & 0x01 != 0);
+                self.pulse_2.length.set_enabled(value & 0x02 != 0);
+                self.triangle.length.set_enabled(value & 0x04 != 0);
+                self.noise.length.set_enabled(value & 0x08 != 0);
+                self.dmc.set_enabled(value & 0x10 != 0);
+            }
+            0x4017 => {
+                self.five_step_mode = value & 0x80 != 0;
+                self.frame_irq_inhibit = value & 0x40 != 0;
+                if self.frame_irq_inhibit {
+                    self.frame_irq = false;
+                }
+                self.frame_cycle = 0;
+                if self.five_step_mode {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        // Every register but the status one is write-only
+        (addr == 0x4015).then(|| self.status())
+    }
+
+    fn tick(&mut self) {
+        self.clock_frame_counter();
+
+        self.odd_cycle = !self.odd_cycle;
+        if self.odd_cycle {
+            self.pulse_1.clock_timer();
+            self.pulse_2.clock_timer();
+        }
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+
+        self.output_sample();
+    }
+
+    fn irq(&self) -> bool {
+        self.frame_irq || self.dmc.irq
+    }
+
+    fn dma_request(&self) -> Option<u16> {
+        self.dmc.dma_request()
+    }
+
+    fn dma_transfer(&mut self, value: u8) {
+        self.dmc.dma_transfer(value);
+    }
+}
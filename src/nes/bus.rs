@@ -1,23 +1,190 @@
+use std::{cell::RefCell, ops::RangeInclusive, rc::Rc};
+
+use super::{
+    cartridge::Cartridge,
+    device::{Device, Ram},
+};
+
+struct Mapping {
+    ranges: Vec<RangeInclusive<u16>>,
+    device: Box<dyn Device>,
+}
+
+impl Mapping {
+    fn contains(&self, addr: u16) -> bool {
+        self.ranges.iter().any(|range| range.contains(&addr))
+    }
+}
+
 pub struct Bus {
-    memory: [u8; 64 * 1024],
+    devices: Vec<Mapping>,
+    // Last value driven on the data bus, returned by reads nothing answers
+    open_bus: u8,
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Bus {
+    // The NES CPU memory map. The PPU, the APU, the controllers and the cartridge are
+    // attached by their owner, which keeps a handle to them.
     pub fn new() -> Self {
+        let mut bus = Self::empty();
+        bus.attach(0x0000..=0x1FFF, Ram::new(2 * 1024));
+        bus
+    }
+
+    // Flat 64 KiB of RAM, for running the CPU on its own
+    pub fn bare() -> Self {
+        let mut bus = Self::empty();
+        bus.attach(0x0000..=0xFFFF, Ram::new(64 * 1024));
+        bus
+    }
+
+    pub fn empty() -> Self {
         Self {
-            memory: [0; 64 * 1024],
+            devices: Vec::new(),
+            open_bus: 0,
         }
     }
 
-    pub fn read(&self, addr: u16) -> u8 {
-        self.memory[addr as usize]
+    // Reads are served by the first device attached over the address that drives the
+    // data bus, writes go to every device attached over it
+    pub fn attach(&mut self, range: RangeInclusive<u16>, device: impl Device + 'static) {
+        self.attach_ranges([range], device);
     }
 
-    pub fn read_bulk(&self, addr: u16, size: u16) -> Vec<u8> {
-        self.memory[(addr as usize)..((addr + size) as usize)].to_vec()
+    // Attaches a device whose registers are scattered over several ranges. It is still
+    // ticked and polled for interrupts and DMA once per cycle.
+    pub fn attach_ranges(
+        &mut self,
+        ranges: impl IntoIterator<Item = RangeInclusive<u16>>,
+        device: impl Device + 'static,
+    ) {
+        self.devices.push(Mapping {
+            ranges: ranges.into_iter().collect(),
+            device: Box::new(device),
+        });
+    }
+
+    // The cartridge connector sees the whole CPU bus, so some mappers can snoop writes
+    // to the PPU registers. Everything below $4020 is still answered by the console.
+    pub fn insert_cartridge(&mut self, cartridge: Rc<RefCell<Cartridge>>) {
+        self.attach(0x0000..=0xFFFF, cartridge);
+    }
+
+    pub fn read(&mut self, addr: u16) -> u8 {
+        let value = self
+            .devices
+            .iter_mut()
+            .filter(|mapping| mapping.contains(addr))
+            .find_map(|mapping| mapping.device.read(addr));
+
+        self.open_bus = value.unwrap_or(self.open_bus);
+        self.open_bus
+    }
+
+    // Reads without side effects, for debugging and visualisation. Addresses nothing
+    // answers show the current open bus value.
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.devices
+            .iter()
+            .filter(|mapping| mapping.contains(addr))
+            .find_map(|mapping| mapping.device.peek(addr))
+            .unwrap_or(self.open_bus)
+    }
+
+    pub fn peek_bulk(&self, addr: u16, size: u16) -> Vec<u8> {
+        (0..size)
+            .map(|offset| self.peek(addr.wrapping_add(offset)))
+            .collect()
     }
 
     pub fn write(&mut self, addr: u16, value: u8) {
-        self.memory[addr as usize] = value;
+        self.open_bus = value;
+
+        for mapping in &mut self.devices {
+            if mapping.contains(addr) {
+                mapping.device.write(addr, value);
+            }
+        }
+    }
+
+    pub fn tick(&mut self) {
+        for mapping in &mut self.devices {
+            mapping.device.tick();
+        }
+    }
+
+    // The IRQ line is shared, any device can pull it low
+    pub fn irq(&self) -> bool {
+        self.devices.iter().any(|mapping| mapping.device.irq())
+    }
+
+    pub fn nmi(&self) -> bool {
+        self.devices.iter().any(|mapping| mapping.device.nmi())
+    }
+
+    // Performs the first pending DMA read, returning whether there was one
+    pub fn service_dma(&mut self) -> bool {
+        let Some((index, addr)) = self
+            .devices
+            .iter()
+            .enumerate()
+            .find_map(|(index, mapping)| Some((index, mapping.device.dma_request()?)))
+        else {
+            return false;
+        };
+
+        let value = self.read(addr);
+        self.devices[index].device.dma_transfer(value);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Counter {
+        ticks: u32,
+        writes: Vec<u16>,
+    }
+
+    impl Device for Counter {
+        fn read(&mut self, _addr: u16) -> Option<u8> {
+            None
+        }
+
+        fn write(&mut self, addr: u16, _value: u8) {
+            self.writes.push(addr);
+        }
+
+        fn peek(&self, _addr: u16) -> Option<u8> {
+            None
+        }
+
+        fn tick(&mut self) {
+            self.ticks += 1;
+        }
+    }
+
+    #[test]
+    fn a_device_over_several_ranges_is_ticked_once() {
+        let counter = Rc::new(RefCell::new(Counter::default()));
+        let mut bus = Bus::empty();
+        bus.attach_ranges([0x4000..=0x4001, 0x4003..=0x4003], Rc::clone(&counter));
+
+        bus.tick();
+        for addr in 0x4000..=0x4003 {
+            bus.write(addr, 0);
+        }
+
+        assert_eq!(counter.borrow().ticks, 1);
+        assert_eq!(counter.borrow().writes, [0x4000, 0x4001, 0x4003]);
     }
 }
@@ -0,0 +1,659 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use super::{
+    device::Device,
+    mappers::{self, Mapper, Memory},
+};
+
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+const PRG_ROM_BANK_SIZE: usize = 16 * 1024;
+const CHR_ROM_BANK_SIZE: usize = 8 * 1024;
+const PRG_RAM_BANK_SIZE: usize = 8 * 1024;
+// Anything bigger than this cannot be a real cartridge
+const MAX_ROM_SIZE: usize = 64 * 1024 * 1024;
+const MAGIC: [u8; 4] = *b"NES\x1A";
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    Io(io::Error),
+    InvalidMagic,
+    MissingPrgRom,
+    RomTooLarge,
+    Truncated { expected: usize, actual: usize },
+    UnsupportedMapper(u16),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::Io(e) => write!(f, "could not read ROM file: {}", e),
+            CartridgeError::InvalidMagic => {
+                write!(f, "not an iNES file: missing \"NES\\x1A\" magic")
+            }
+            CartridgeError::MissingPrgRom => write!(f, "iNES header declares no PRG ROM"),
+            CartridgeError::RomTooLarge => {
+                write!(f, "NES 2.0 header declares an impossible ROM size")
+            }
+            CartridgeError::Truncated { expected, actual } => write!(
+                f,
+                "ROM file is truncated: expected {} bytes, found {}",
+                expected, actual
+            ),
+            CartridgeError::UnsupportedMapper(mapper) => {
+                write!(f, "mapper {} is not supported", mapper)
+            }
+        }
+    }
+}
+
+impl Error for CartridgeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CartridgeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CartridgeError {
+    fn from(e: io::Error) -> Self {
+        CartridgeError::Io(e)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    FourScreen,
+    // Only selectable by mappers
+    SingleScreenLower,
+    SingleScreenUpper,
+}
+
+impl Mirroring {
+    // Offset of a nametable address into 4 KiB of nametable RAM, of which the console
+    // only has the first 2 KiB
+    pub fn nametable_offset(self, addr: u16) -> usize {
+        let table = (addr as usize >> 10) & 0x03;
+        let table = match self {
+            Mirroring::Horizontal => table >> 1,
+            Mirroring::Vertical => table & 0x01,
+            Mirroring::FourScreen => table,
+            Mirroring::SingleScreenLower => 0,
+            Mirroring::SingleScreenUpper => 1,
+        };
+        table * 0x400 + (addr as usize & 0x03FF)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeaderFormat {
+    INes,
+    Nes2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    MultiRegion,
+    Dendy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem { ppu_type: u8, hardware_type: u8 },
+    Playchoice10,
+    // NES 2.0 extended console type, e.g. Famiclones with decimal mode or VT0x
+    Extended(u8),
+}
+
+#[derive(Clone, Debug)]
+pub struct Header {
+    pub format: HeaderFormat,
+    pub prg_rom_size: usize,
+    // 0 means the board uses CHR RAM
+    pub chr_rom_size: usize,
+    pub mapper: u16,
+    pub submapper: u8,
+    pub mirroring: Mirroring,
+    pub has_battery: bool,
+    pub has_trainer: bool,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+    pub expansion_device: u8,
+}
+
+impl Header {
+    pub fn parse(bytes: &[u8; HEADER_SIZE]) -> Result<Self, CartridgeError> {
+        if bytes[0..4] != MAGIC {
+            return Err(CartridgeError::InvalidMagic);
+        }
+
+        let flags_6 = bytes[6];
+        let flags_7 = bytes[7];
+        let format = if flags_7 & 0b00001100 == 0b00001000 {
+            HeaderFormat::Nes2
+        } else {
+            HeaderFormat::INes
+        };
+
+        let mirroring = if flags_6 & 0b00001000 != 0 {
+            Mirroring::FourScreen
+        } else if flags_6 & 0b00000001 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        let has_battery = flags_6 & 0b00000010 != 0;
+
+        let header = match format {
+            HeaderFormat::Nes2 => Self::parse_nes2(bytes, mirroring, has_battery)?,
+            HeaderFormat::INes => Self::parse_ines(bytes, mirroring, has_battery),
+        };
+        if header.prg_rom_size == 0 {
+            return Err(CartridgeError::MissingPrgRom);
+        }
+        Ok(header)
+    }
+
+    fn parse_ines(bytes: &[u8; HEADER_SIZE], mirroring: Mirroring, has_battery: bool) -> Self {
+        let flags_6 = bytes[6];
+        let mut flags_7 = bytes[7];
+        let mut flags_9 = bytes[9];
+
+        // Old dumping tools wrote garbage such as "DiskDude!" over bytes 7-15,
+        // in which case only the low nibble of the mapper number can be trusted
+        if bytes[12..16].iter().any(|&byte| byte != 0) {
+            flags_7 = 0;
+            flags_9 = 0;
+        }
+
+        let chr_rom_size = bytes[5] as usize * CHR_ROM_BANK_SIZE;
+
+        // iNES has no RAM sizes, assume the usual 8 KiB of (battery-backed) PRG RAM
+        let prg_ram_size = if bytes[8] == 0 { 1 } else { bytes[8] as usize } * PRG_RAM_BANK_SIZE;
+        let (prg_ram_size, prg_nvram_size) = if has_battery {
+            (0, prg_ram_size)
+        } else {
+            (prg_ram_size, 0)
+        };
+
+        let console_type = if flags_7 & 0b00000001 != 0 {
+            ConsoleType::VsSystem {
+                ppu_type: 0,
+                hardware_type: 0,
+            }
+        } else if flags_7 & 0b00000010 != 0 {
+            ConsoleType::Playchoice10
+        } else {
+            ConsoleType::Nes
+        };
+
+        Self {
+            format: HeaderFormat::INes,
+            prg_rom_size: bytes[4] as usize * PRG_ROM_BANK_SIZE,
+            chr_rom_size,
+            mapper: ((flags_7 & 0xF0) | (flags_6 >> 4)) as u16,
+            submapper: 0,
+            mirroring,
+            has_battery,
+            has_trainer: flags_6 & 0b00000100 != 0,
+            prg_ram_size,
+            prg_nvram_size,
+            chr_ram_size: if chr_rom_size == 0 {
+                CHR_ROM_BANK_SIZE
+            } else {
+                0
+            },
+            chr_nvram_size: 0,
+            timing: if flags_9 & 0b00000001 != 0 {
+                Timing::Pal
+            } else {
+                Timing::Ntsc
+            },
+            console_type,
+            expansion_device: 0,
+        }
+    }
+
+    fn parse_nes2(
+        bytes: &[u8; HEADER_SIZE],
+        mirroring: Mirroring,
+        has_battery: bool,
+    ) -> Result<Self, CartridgeError> {
+        let flags_6 = bytes[6];
+        let flags_7 = bytes[7];
+
+        let mapper =
+            ((bytes[8] as u16 & 0x0F) << 8) | (flags_7 & 0xF0) as u16 | (flags_6 >> 4) as u16;
+
+        let console_type = match flags_7 & 0b00000011 {
+            0 => ConsoleType::Nes,
+            1 => ConsoleType::VsSystem {
+                ppu_type: bytes[13] & 0x0F,
+                hardware_type: bytes[13] >> 4,
+            },
+            2 => ConsoleType::Playchoice10,
+            _ => ConsoleType::Extended(bytes[13] & 0x0F),
+        };
+
+        let timing = match bytes[12] & 0b00000011 {
+            0 => Timing::Ntsc,
+            1 => Timing::Pal,
+            2 => Timing::MultiRegion,
+            _ => Timing::Dendy,
+        };
+
+        Ok(Self {
+            format: HeaderFormat::Nes2,
+            prg_rom_size: nes2_rom_size(bytes[4], bytes[9] & 0x0F, PRG_ROM_BANK_SIZE)?,
+            chr_rom_size: nes2_rom_size(bytes[5], bytes[9] >> 4, CHR_ROM_BANK_SIZE)?,
+            mapper,
+            submapper: bytes[8] >> 4,
+            mirroring,
+            has_battery,
+            has_trainer: flags_6 & 0b00000100 != 0,
+            prg_ram_size: nes2_ram_size(bytes[10] & 0x0F),
+            prg_nvram_size: nes2_ram_size(bytes[10] >> 4),
+            chr_ram_size: nes2_ram_size(bytes[11] & 0x0F),
+            chr_nvram_size: nes2_ram_size(bytes[11] >> 4),
+            timing,
+            console_type,
+            expansion_device: bytes[15] & 0b00111111,
+        })
+    }
+}
+
+// The ROM size MSB nibble extends the bank count, unless it is $F: then the LSB byte
+// holds an exponent and a multiplier, giving 2^E * (MM * 2 + 1) bytes
+fn nes2_rom_size(lsb: u8, msb: u8, bank_size: usize) -> Result<usize, CartridgeError> {
+    if msb != 0x0F {
+        return Ok((((msb as usize) << 8) | lsb as usize) * bank_size);
+    }
+
+    let exponent = (lsb >> 2) as u32;
+    let multiplier = (lsb & 0b00000011) as usize * 2 + 1;
+    1usize
+        .checked_shl(exponent)
+        .and_then(|size| size.checked_mul(multiplier))
+        .filter(|&size| size <= MAX_ROM_SIZE)
+        .ok_or(CartridgeError::RomTooLarge)
+}
+
+// RAM sizes are stored as a shift count: 64 << shift bytes, 0 meaning none
+fn nes2_ram_size(shift: u8) -> usize {
+    match shift {
+        0 => 0,
+        shift => 64 << shift,
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.format {
+            HeaderFormat::INes => write!(f, "iNES, mapper {}", self.mapper)?,
+            HeaderFormat::Nes2 => write!(f, "NES 2.0, mapper {}.{}", self.mapper, self.submapper)?,
+        }
+        write!(
+            f,
+            ", {} KiB PRG ROM, {} KiB CHR ROM, {:?} mirroring, {:?}",
+            self.prg_rom_size / 1024,
+            self.chr_rom_size / 1024,
+            self.mirroring,
+            self.timing
+        )?;
+
+        for (name, size) in [
+            ("PRG RAM", self.prg_ram_size),
+            ("PRG NVRAM", self.prg_nvram_size),
+            ("CHR RAM", self.chr_ram_size),
+            ("CHR NVRAM", self.chr_nvram_size),
+        ] {
+            if size != 0 {
+                write!(f, ", {} KiB {}", size as f32 / 1024.0, name)?;
+            }
+        }
+
+        if self.console_type != ConsoleType::Nes {
+            write!(f, ", {:?}", self.console_type)?;
+        }
+        if self.expansion_device != 0 {
+            write!(f, ", expansion device ${:02X}", self.expansion_device)?;
+        }
+        if self.has_battery {
+            write!(f, ", battery")?;
+        }
+        if self.has_trainer {
+            write!(f, ", trainer")?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Cartridge {
+    pub header: Header,
+    pub mapper: Box<dyn Mapper>,
+}
+
+impl Cartridge {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CartridgeError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CartridgeError> {
+        let header_bytes: &[u8; HEADER_SIZE] = bytes
+            .get(..HEADER_SIZE)
+            .and_then(|header| header.try_into().ok())
+            .ok_or(CartridgeError::Truncated {
+                expected: HEADER_SIZE,
+                actual: bytes.len(),
+            })?;
+        let header = Header::parse(header_bytes)?;
+
+        let trainer_size = if header.has_trainer { TRAINER_SIZE } else { 0 };
+        let prg_rom_start = HEADER_SIZE + trainer_size;
+        let chr_rom_start = prg_rom_start + header.prg_rom_size;
+        let expected = chr_rom_start + header.chr_rom_size;
+        if bytes.len() < expected {
+            return Err(CartridgeError::Truncated {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+
+        // The trainer is loaded into PRG RAM at $7000
+        let mut prg_ram = vec![0; header.prg_ram_size + header.prg_nvram_size];
+        if header.has_trainer && prg_ram.len() >= 0x1000 + TRAINER_SIZE {
+            prg_ram[0x1000..0x1000 + TRAINER_SIZE]
+                .copy_from_slice(&bytes[HEADER_SIZE..prg_rom_start]);
+        }
+
+        let memory = Memory::new(
+            &header,
+            bytes[prg_rom_start..chr_rom_start].to_vec(),
+            bytes[chr_rom_start..expected].to_vec(),
+            prg_ram,
+        );
+
+        Ok(Self {
+            mapper: mappers::create(&header, memory)?,
+            header,
+        })
+    }
+}
+
+impl Device for Cartridge {
+    fn read(&mut self, addr: u16) -> Option<u8> {
+        self.mapper.cpu_read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.mapper.cpu_write(addr, value);
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        self.mapper.cpu_peek(addr)
+    }
+
+    fn tick(&mut self) {
+        self.mapper.tick();
+    }
+
+    fn irq(&self) -> bool {
+        self.mapper.irq()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An iNES header with bytes 4 and up taken from `fields`
+    fn header(fields: &[u8]) -> [u8; HEADER_SIZE] {
+        let mut header = [0; HEADER_SIZE];
+        header[..4].copy_from_slice(&MAGIC);
+        header[4..4 + fields.len()].copy_from_slice(fields);
+        header
+    }
+
+    fn rom(header: [u8; HEADER_SIZE], contents: &[&[u8]]) -> Vec<u8> {
+        let mut rom = header.to_vec();
+        for part in contents {
+            rom.extend_from_slice(part);
+        }
+        rom
+    }
+
+    #[test]
+    fn rejects_a_missing_magic() {
+        let mut bytes = header(&[1, 1]);
+        bytes[3] = 0x1B;
+        assert!(matches!(
+            Header::parse(&bytes),
+            Err(CartridgeError::InvalidMagic)
+        ));
+    }
+
+    #[test]
+    fn rejects_a_header_without_prg_rom() {
+        assert!(matches!(
+            Header::parse(&header(&[0, 1])),
+            Err(CartridgeError::MissingPrgRom)
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let bytes = header(&[1, 1]);
+        assert!(matches!(
+            Cartridge::from_bytes(&bytes[..10]),
+            Err(CartridgeError::Truncated {
+                expected: 16,
+                actual: 10
+            })
+        ));
+
+        let prg_rom = [0; PRG_ROM_BANK_SIZE];
+        assert!(matches!(
+            Cartridge::from_bytes(&rom(bytes, &[&prg_rom[..100]])),
+            Err(CartridgeError::Truncated {
+                expected: 0x6010,
+                actual: 116
+            })
+        ));
+        assert!(matches!(
+            Cartridge::from_bytes(&rom(bytes, &[&prg_rom, &[0; 100]])),
+            Err(CartridgeError::Truncated {
+                expected: 0x6010,
+                actual: 0x4074
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_mappers() {
+        let bytes = header(&[1, 1, 0xF0, 0xF0]);
+        assert!(matches!(
+            Cartridge::from_bytes(&rom(bytes, &[&[0; 0x6000]])),
+            Err(CartridgeError::UnsupportedMapper(255))
+        ));
+    }
+
+    #[test]
+    fn parses_ines_headers() {
+        let header = Header::parse(&header(&[2, 1, 0x13, 0x40])).unwrap();
+        assert_eq!(header.format, HeaderFormat::INes);
+        assert_eq!(header.prg_rom_size, 32 * 1024);
+        assert_eq!(header.chr_rom_size, 8 * 1024);
+        assert_eq!(header.mapper, 0x41);
+        assert_eq!(header.mirroring, Mirroring::Vertical);
+        assert!(header.has_battery);
+        assert!(!header.has_trainer);
+        assert_eq!(header.prg_ram_size, 0);
+        assert_eq!(header.prg_nvram_size, 8 * 1024);
+        assert_eq!(header.chr_ram_size, 0);
+        assert_eq!(header.timing, Timing::Ntsc);
+        assert_eq!(header.console_type, ConsoleType::Nes);
+    }
+
+    #[test]
+    fn ines_flags_select_four_screen_pal_and_prg_ram_banks() {
+        let header = Header::parse(&header(&[1, 1, 0x09, 0x02, 4, 0x01])).unwrap();
+        assert_eq!(header.mirroring, Mirroring::FourScreen);
+        assert_eq!(header.console_type, ConsoleType::Playchoice10);
+        assert_eq!(header.prg_ram_size, 32 * 1024);
+        assert_eq!(header.timing, Timing::Pal);
+    }
+
+    #[test]
+    fn garbage_in_the_padding_keeps_only_the_low_mapper_nibble() {
+        let mut bytes = header(&[1, 1, 0x40]);
+        bytes[7..16].copy_from_slice(b"DiskDude!");
+
+        let header = Header::parse(&bytes).unwrap();
+        assert_eq!(header.format, HeaderFormat::INes);
+        assert_eq!(header.mapper, 4);
+        assert_eq!(header.console_type, ConsoleType::Nes);
+    }
+
+    #[test]
+    fn boards_without_chr_rom_get_8k_of_chr_ram() {
+        let bytes = header(&[1, 0]);
+        let mut cartridge = Cartridge::from_bytes(&rom(bytes, &[&[0; PRG_ROM_BANK_SIZE]])).unwrap();
+        assert_eq!(cartridge.header.chr_rom_size, 0);
+        assert_eq!(cartridge.header.chr_ram_size, CHR_ROM_BANK_SIZE);
+
+        let mut ciram = [0; 0x800];
+        cartridge.mapper.ppu_write(0x1FFF, 0x42, &mut ciram);
+        assert_eq!(cartridge.mapper.ppu_peek(0x1FFF, &ciram), 0x42);
+    }
+
+    #[test]
+    fn the_trainer_is_loaded_at_7000() {
+        let bytes = header(&[1, 1, 0x04]);
+        let contents: [&[u8]; 3] = [
+            &[0xAB; TRAINER_SIZE],
+            &[0x11; PRG_ROM_BANK_SIZE],
+            &[0; 0x2000],
+        ];
+        let cartridge = Cartridge::from_bytes(&rom(bytes, &contents)).unwrap();
+
+        assert!(cartridge.header.has_trainer);
+        assert_eq!(cartridge.peek(0x6FFF), Some(0));
+        assert_eq!(cartridge.peek(0x7000), Some(0xAB));
+        assert_eq!(cartridge.peek(0x71FF), Some(0xAB));
+        assert_eq!(cartridge.peek(0x7200), Some(0));
+        assert_eq!(cartridge.peek(0x8000), Some(0x11));
+    }
+
+    #[test]
+    fn nes2_rom_sizes_extend_the_bank_count() {
+        assert_eq!(
+            nes2_rom_size(0x02, 0x01, PRG_ROM_BANK_SIZE).unwrap(),
+            0x102 * 16 * 1024
+        );
+        assert_eq!(
+            nes2_rom_size(0x20, 0x00, CHR_ROM_BANK_SIZE).unwrap(),
+            256 * 1024
+        );
+    }
+
+    #[test]
+    fn nes2_rom_sizes_with_an_exponent_and_multiplier() {
+        // 2^E * (MM * 2 + 1)
+        assert_eq!(
+            nes2_rom_size(10 << 2 | 1, 0x0F, PRG_ROM_BANK_SIZE).unwrap(),
+            3 * 1024
+        );
+        assert_eq!(
+            nes2_rom_size(4 << 2 | 3, 0x0F, PRG_ROM_BANK_SIZE).unwrap(),
+            112
+        );
+        assert_eq!(
+            nes2_rom_size(26 << 2, 0x0F, PRG_ROM_BANK_SIZE).unwrap(),
+            MAX_ROM_SIZE
+        );
+        assert!(matches!(
+            nes2_rom_size(26 << 2 | 1, 0x0F, PRG_ROM_BANK_SIZE),
+            Err(CartridgeError::RomTooLarge)
+        ));
+        assert!(matches!(
+            nes2_rom_size(0xFF, 0x0F, CHR_ROM_BANK_SIZE),
+            Err(CartridgeError::RomTooLarge)
+        ));
+    }
+
+    #[test]
+    fn parses_nes2_headers() {
+        let header = Header::parse(&header(&[
+            0x02, 0x01, 0x42, 0x18, 0x35, 0x01, 0x77, 0x09, 0x01, 0x00, 0x00, 0x25,
+        ]))
+        .unwrap();
+        assert_eq!(header.format, HeaderFormat::Nes2);
+        assert_eq!(header.mapper, 0x514);
+        assert_eq!(header.submapper, 3);
+        assert_eq!(header.prg_rom_size, 0x102 * 16 * 1024);
+        assert_eq!(header.chr_rom_size, 8 * 1024);
+        assert_eq!(header.mirroring, Mirroring::Horizontal);
+        assert!(header.has_battery);
+        assert_eq!(header.prg_ram_size, 8 * 1024);
+        assert_eq!(header.prg_nvram_size, 8 * 1024);
+        assert_eq!(header.chr_ram_size, 32 * 1024);
+        assert_eq!(header.chr_nvram_size, 0);
+        assert_eq!(header.timing, Timing::Pal);
+        assert_eq!(header.console_type, ConsoleType::Nes);
+        assert_eq!(header.expansion_device, 0x25);
+    }
+
+    #[test]
+    fn nes2_timing() {
+        let timings = [
+            Timing::Ntsc,
+            Timing::Pal,
+            Timing::MultiRegion,
+            Timing::Dendy,
+        ];
+        for (value, timing) in timings.into_iter().enumerate() {
+            let mut bytes = header(&[1, 1, 0x00, 0x08]);
+            bytes[12] = value as u8;
+            assert_eq!(Header::parse(&bytes).unwrap().timing, timing);
+        }
+    }
+
+    #[test]
+    fn nes2_console_types() {
+        let console_types = [
+            ConsoleType::Nes,
+            ConsoleType::VsSystem {
+                ppu_type: 0x03,
+                hardware_type: 0x0A,
+            },
+            ConsoleType::Playchoice10,
+            ConsoleType::Extended(0x03),
+        ];
+        for (value, console_type) in console_types.into_iter().enumerate() {
+            let mut bytes = header(&[1, 1, 0x00, 0x08 | value as u8]);
+            bytes[13] = 0xA3;
+            assert_eq!(Header::parse(&bytes).unwrap().console_type, console_type);
+        }
+    }
+
+    #[test]
+    fn nes2_chr_ram_size_comes_from_its_shift_count() {
+        let bytes = header(&[1, 0, 0x00, 0x08, 0x00, 0x00, 0x00, 0x08]);
+        let mut cartridge = Cartridge::from_bytes(&rom(bytes, &[&[0; PRG_ROM_BANK_SIZE]])).unwrap();
+        assert_eq!(cartridge.header.chr_ram_size, 16 * 1024);
+
+        let mut ciram = [0; 0x800];
+        cartridge.mapper.ppu_write(0x1FFF, 0x42, &mut ciram);
+        assert_eq!(cartridge.mapper.ppu_peek(0x1FFF, &ciram), 0x42);
+    }
+}
@@ -0,0 +1,174 @@
+use super::device::Device;
+
+// Buttons in the order the standard controller shifts them out
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    A,
+    B,
+    Select,
+    Start,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Button {
+    pub const ALL: [Button; 8] = [
+        Button::A,
+        Button::B,
+        Button::Select,
+        Button::Start,
+        Button::Up,
+        Button::Down,
+        Button::Left,
+        Button::Right,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|button| format!("{:?}", button).eq_ignore_ascii_case(name))
+    }
+
+    const fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+// Standard controllers plugged into both ports. Writing bit 0 of $4016 high keeps
+// reloading the shift registers with the buttons held; once it goes low, each read of
+// $4016 (player 1) or $4017 (player 2) shifts out the next button.
+#[derive(Default)]
+pub struct Controllers {
+    strobe: bool,
+    buttons: [u8; 2],
+    shift: [u8; 2],
+}
+
+impl Controllers {
+    // Players are 0 and 1, buttons set for any other player are ignored
+    pub fn set_button(&mut self, player: usize, button: Button, pressed: bool) {
+        let Some(held) = self.buttons.get_mut(player) else {
+            return;
+        };
+        if pressed {
+            *held |= button.mask();
+        } else {
+            *held &= !button.mask();
+        }
+        self.reload_while_strobing();
+    }
+
+    // Replaces everything a player holds, releasing the buttons not listed
+    pub fn set_buttons(&mut self, player: usize, buttons: &[Button]) {
+        let Some(held) = self.buttons.get_mut(player) else {
+            return;
+        };
+        *held = buttons.iter().fold(0, |held, button| held | button.mask());
+        self.reload_while_strobing();
+    }
+
+    fn reload_while_strobing(&mut self) {
+        if self.strobe {
+            self.shift = self.buttons;
+        }
+    }
+}
+
+impl Device for Controllers {
+    fn read(&mut self, addr: u16) -> Option<u8> {
+        let value = self.peek(addr);
+        let port = (addr & 0x0001) as usize;
+        // Official controllers return 1 once all eight buttons are shifted out
+        if !self.strobe {
+            self.shift[port] = (self.shift[port] >> 1) | 0x80;
+        }
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if addr == 0x4016 {
+            self.strobe = value & 0x01 != 0;
+            if self.strobe {
+                self.shift = self.buttons;
+            }
+        }
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        // Controllers only drive the low bits, the upper ones usually keep the $40 left
+        // on the bus by the address high byte
+        Some(0x40 | (self.shift[(addr & 0x0001) as usize] & 0x01))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_bits(controllers: &mut Controllers, addr: u16, count: usize) -> Vec<u8> {
+        (0..count)
+            .map(|_| controllers.read(addr).unwrap() & 0x01)
+            .collect()
+    }
+
+    #[test]
+    fn buttons_shift_out_in_order_then_ones() {
+        let mut controllers = Controllers::default();
+        controllers.set_buttons(0, &[Button::A, Button::Select, Button::Down]);
+        controllers.set_buttons(1, &[Button::B, Button::Right]);
+
+        controllers.write(0x4016, 1);
+        controllers.write(0x4016, 0);
+        assert_eq!(
+            read_bits(&mut controllers, 0x4016, 10),
+            [1, 0, 1, 0, 0, 1, 0, 0, 1, 1]
+        );
+        assert_eq!(
+            read_bits(&mut controllers, 0x4017, 10),
+            [0, 1, 0, 0, 0, 0, 0, 1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn strobing_keeps_returning_the_first_button() {
+        let mut controllers = Controllers::default();
+        controllers.set_button(0, Button::A, true);
+        controllers.write(0x4016, 1);
+        assert_eq!(read_bits(&mut controllers, 0x4016, 3), [1, 1, 1]);
+
+        // The shift register follows the buttons while the strobe is high
+        controllers.set_button(0, Button::A, false);
+        assert_eq!(read_bits(&mut controllers, 0x4016, 1), [0]);
+    }
+
+    #[test]
+    fn buttons_are_latched_when_the_strobe_goes_low() {
+        let mut controllers = Controllers::default();
+        controllers.set_button(0, Button::Start, true);
+        controllers.write(0x4016, 1);
+        controllers.write(0x4016, 0);
+
+        controllers.set_button(0, Button::Start, false);
+        controllers.set_button(0, Button::B, true);
+        assert_eq!(read_bits(&mut controllers, 0x4016, 4), [0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn peeking_does_not_shift_and_the_upper_bits_are_open_bus() {
+        let mut controllers = Controllers::default();
+        controllers.set_button(1, Button::A, true);
+        controllers.write(0x4016, 1);
+        controllers.write(0x4016, 0);
+
+        assert_eq!(controllers.peek(0x4017), Some(0x41));
+        assert_eq!(controllers.peek(0x4017), Some(0x41));
+        assert_eq!(controllers.read(0x4017), Some(0x41));
+        assert_eq!(controllers.read(0x4017), Some(0x40));
+
+        // Writes to $4017 belong to the APU
+        controllers.write(0x4017, 1);
+        assert_eq!(controllers.read(0x4017), Some(0x40));
+    }
+}
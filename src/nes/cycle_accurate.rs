@@ -0,0 +1,492 @@
+use super::{
+    addr_modes::AddrMode::{self, *},
+    instruction_summary::InstructionSummary,
+    instructions::Instruction::{self, *},
+    interrupts::Interrupt,
+    mos_6502::{CpuVariant, Mos6502, STACK_PAGE},
+    status_flags::StatusFlags,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+    ReadModifyWrite,
+}
+
+fn access(instruction: &Instruction) -> Access {
+    match instruction {
+        STA_StoreAccInMemory
+        | STX_StoreXInMemory
+        | STY_StoreYInMemory
+        | STZ_StoreZeroInMemory
+        | SAX_StoreAccANDX
+        | SHA_StoreAccANDXANDHighByte
+        | SHX_StoreXANDHighByte
+        | SHY_StoreYANDHighByte
+        | TAS_TransferAccANDXToStackThenStore => Access::Write,
+        ASL_ShiftLeftOneBit
+        | LSR_ShiftOneBitRight
+        | ROL_RotateOneBitLeft
+        | ROR_RotateOneBitRight
+        | INC_IncrementMemoryByOne
+        | DEC_DecrementMemoryByOne
+        | SLO_ShiftLeftThenORWithAcc
+        | RLA_RotateLeftThenANDWithAcc
+        | SRE_ShiftRightThenEORWithAcc
+        | RRA_RotateRightThenAddWithCarry
+        | DCP_DecrementThenCompareWithAcc
+        | ISC_IncrementThenSubtractFromAcc
+        | TRB_TestAndResetMemoryBits
+        | TSB_TestAndSetMemoryBits => Access::ReadModifyWrite,
+        _ => Access::Read,
+    }
+}
+
+// Every call to clock_cycle_accurate performs exactly one bus access. `step` is the cycle
+// of the current instruction, starting at 1 for the opcode fetch. The instruction itself is
+// still executed by handle_instruction on its last cycle, once the operand has been read.
+impl Mos6502 {
+    pub(super) fn clock_cycle_accurate(&mut self) {
+        let done = if self.step == 0 {
+            self.start_instruction()
+        } else if self.extra_cycles > 0 {
+            self.read_byte(self.pc);
+            self.extra_cycles -= 1;
+            self.extra_cycles == 0
+        } else {
+            self.step += 1;
+            self.instruction_cycle()
+        };
+
+        if done && self.extra_cycles == 0 {
+            self.step = 0;
+        }
+        self.cycles = (self.step != 0 || self.pending_interrupt.is_some()) as u8;
+    }
+
+    fn start_instruction(&mut self) -> bool {
+        self.step = 1;
+
+        if self.pending_interrupt.is_none() {
+            self.poll_interrupt_lines();
+        }
+
+        if self.pending_interrupt.is_some() {
+            // Interrupts run the BRK sequence, with the opcode fetch turned into a dummy read
+            self.read_byte(self.pc);
+            self.opcode = 0x00;
+            return false;
+        }
+
+        self.opcode = self.read_byte(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+
+        // The 65C02 single byte NOPs are over after the opcode fetch
+        InstructionSummary::decode(self.opcode, self.variant).cycles == 1
+    }
+
+    fn instruction_cycle(&mut self) -> bool {
+        let summary = InstructionSummary::decode(self.opcode, self.variant);
+        match summary.instruction {
+            BRK_ForceBreak => self.break_cycle(),
+            JSR_JumpToSavingReturnAddr => self.jump_to_subroutine_cycle(),
+            RTS_ReturnFromSubroutine => self.return_from_subroutine_cycle(),
+            RTI_ReturnFromInterrupt => self.return_from_interrupt_cycle(),
+            JMP_JumpTo => self.jump_cycle(summary.addr_mode),
+            PHA_PushAccOnStack
+            | PHP_PushProcessorStatusOnStack
+            | PHX_PushXOnStack
+            | PHY_PushYOnStack => self.push_cycle(),
+            PLA_PullAccFromStack
+            | PLP_PullProcessorStatusFromStack
+            | PLX_PullXFromStack
+            | PLY_PullYFromStack => self.pull_cycle(),
+            instruction => self.addressed_cycle(summary.addr_mode, access(&instruction)),
+        }
+    }
+
+    fn execute(&mut self) {
+        let summary = InstructionSummary::decode(self.opcode, self.variant);
+
+        // Cycles added by the instruction (65C02 decimal mode) are spent as dummy reads,
+        // as is anything short of the base count (the 8 cycle 65C02 NOP at $5C)
+        self.cycles = 0;
+        self.handle_instruction(summary.instruction);
+        self.extra_cycles = self.cycles + summary.cycles.saturating_sub(self.step);
+    }
+
+    fn addressed_cycle(&mut self, addr_mode: AddrMode, access: Access) -> bool {
+        let step = self.step;
+        match addr_mode {
+            Implied => {
+                self.read_byte(self.pc);
+                self.fetched = self.a;
+                self.execute();
+                true
+            }
+            Immediate => {
+                self.fetched = self.read_byte(self.pc);
+                self.pc = self.pc.wrapping_add(1);
+                self.execute();
+                true
+            }
+            Relative => self.branch_cycle(),
+            ZeroPage => match step {
+                2 => self.fetch_operand_low(),
+                _ => self.operand_cycle(access, step - 3),
+            },
+            ZeroPageOffsetX | ZeroPageOffsetY => match step {
+                2 => self.fetch_operand_low(),
+                3 => {
+                    // Dummy read of the address before it is indexed
+                    self.read_byte(self.addr_abs);
+                    let index = self.index_register(addr_mode);
+                    self.addr_abs = (self.addr_abs as u8).wrapping_add(index) as u16;
+                    false
+                }
+                _ => self.operand_cycle(access, step - 4),
+            },
+            Absolute => match step {
+                2 => self.fetch_operand_low(),
+                3 => self.fetch_operand_high(),
+                _ => self.operand_cycle(access, step - 4),
+            },
+            AbsoluteOffsetX | AbsoluteOffsetY => {
+                let fix_up = self.needs_fix_up(access) as u8;
+                match step {
+                    2 => self.fetch_operand_low(),
+                    3 => {
+                        self.fetch_operand_high();
+                        self.pointer = self.addr_abs;
+                        let index = self.index_register(addr_mode);
+                        self.addr_abs = self.addr_abs.wrapping_add(index as u16);
+                        false
+                    }
+                    4 if fix_up == 1 => self.fix_up_cycle(),
+                    _ => self.operand_cycle(access, step - 4 - fix_up),
+                }
+            }
+            IndirectOffsetX => match step {
+                2 => self.fetch_pointer(),
+                3 => {
+                    self.read_byte(self.pointer);
+                    self.pointer = (self.pointer as u8).wrapping_add(self.x) as u16;
+                    false
+                }
+                4 => self.fetch_pointer_low(),
+                5 => self.fetch_pointer_high(),
+                _ => self.operand_cycle(access, step - 6),
+            },
+            IndirectOffsetY => {
+                let fix_up = self.needs_fix_up(access) as u8;
+                match step {
+                    2 => self.fetch_pointer(),
+                    3 => self.fetch_pointer_low(),
+                    4 => {
+                        self.fetch_pointer_high();
+                        self.pointer = self.addr_abs;
+                        self.addr_abs = self.addr_abs.wrapping_add(self.y as u16);
+                        false
+                    }
+                    5 if fix_up == 1 => self.fix_up_cycle(),
+                    _ => self.operand_cycle(access, step - 5 - fix_up),
+                }
+            }
+            ZeroPageIndirect => match step {
+                2 => self.fetch_pointer(),
+                3 => self.fetch_pointer_low(),
+                4 => self.fetch_pointer_high(),
+                _ => self.operand_cycle(access, step - 5),
+            },
+            Indirect | AbsoluteIndirectOffsetX => self.jump_cycle(addr_mode),
+        }
+    }
+
+    // Cycles left once the effective address is known
+    fn operand_cycle(&mut self, access: Access, cycle: u8) -> bool {
+        match (access, cycle) {
+            (Access::Read, _) => {
+                self.fetched = self.read_byte(self.addr_abs);
+                self.execute();
+                true
+            }
+            (Access::Write, _) => {
+                self.execute();
+                true
+            }
+            (Access::ReadModifyWrite, 0) => {
+                self.fetched = self.read_byte(self.addr_abs);
+                false
+            }
+            (Access::ReadModifyWrite, 1) => {
+                // NMOS writes the unmodified value back while the ALU works, the 65C02 reads it again
+                if self.variant == CpuVariant::Cmos65C02 {
+                    self.read_byte(self.addr_abs);
+                } else {
+                    self.write(self.addr_abs, self.fetched);
+                }
+                false
+            }
+            (Access::ReadModifyWrite, _) => {
+                self.execute();
+                true
+            }
+        }
+    }
+
+    fn branch_cycle(&mut self) -> bool {
+        match self.step {
+            2 => {
+                self.handle_addr_mode(Relative);
+                self.pointer = self.pc;
+                self.execute();
+
+                // The extra cycles of a taken branch have their own dummy reads below
+                let taken = self.extra_cycles > 0;
+                self.extra_cycles = 0;
+                !taken
+            }
+            3 => {
+                // Dummy read of the next opcode while the offset is added to PCL
+                self.read_byte(self.pointer);
+                self.pointer & 0xFF00 == self.pc & 0xFF00
+            }
+            _ => {
+                // Dummy read before PCH is fixed
+                self.read_byte((self.pointer & 0xFF00) | (self.pc & 0x00FF));
+                true
+            }
+        }
+    }
+
+    fn jump_cycle(&mut self, addr_mode: AddrMode) -> bool {
+        let fixed_page_bug = self.variant == CpuVariant::Cmos65C02;
+        match (&addr_mode, self.step) {
+            (_, 2) => self.fetch_operand_low(),
+            (Absolute, _) => {
+                self.fetch_operand_high();
+                self.execute();
+                true
+            }
+            (_, 3) => {
+                self.fetch_operand_high();
+                self.pointer = self.addr_abs;
+                false
+            }
+            (AbsoluteIndirectOffsetX, 4) => {
+                self.read_byte(self.pc.wrapping_sub(1));
+                self.pointer = self.pointer.wrapping_add(self.x as u16);
+                false
+            }
+            (Indirect, 4) | (AbsoluteIndirectOffsetX, 5) => {
+                self.addr_abs = self.read_byte(self.pointer) as u16;
+                false
+            }
+            (Indirect, 5) if fixed_page_bug => {
+                // The 65C02 spends a cycle fixing the page wrap bug
+                self.read_byte(self.pointer.wrapping_add(1));
+                false
+            }
+            _ => {
+                let high_addr = if matches!(addr_mode, Indirect) && !fixed_page_bug {
+                    (self.pointer & 0xFF00) | (self.pointer.wrapping_add(1) & 0x00FF)
+                } else {
+                    self.pointer.wrapping_add(1)
+                };
+                self.addr_abs |= (self.read_byte(high_addr) as u16) << 8;
+                self.execute();
+                true
+            }
+        }
+    }
+
+    fn jump_to_subroutine_cycle(&mut self) -> bool {
+        match self.step {
+            2 => self.fetch_operand_low(),
+            3 => self.read_stack(),
+            4 => {
+                self.push_byte((self.pc >> 8) as u8);
+                false
+            }
+            5 => {
+                self.push_byte(self.pc as u8);
+                false
+            }
+            _ => {
+                self.fetch_operand_high();
+                self.pc = self.addr_abs;
+                true
+            }
+        }
+    }
+
+    fn return_from_subroutine_cycle(&mut self) -> bool {
+        match self.step {
+            2 => self.read_pc(),
+            3 => self.read_stack(),
+            4 => {
+                self.addr_abs = self.pull_byte() as u16;
+                false
+            }
+            5 => {
+                self.addr_abs |= (self.pull_byte() as u16) << 8;
+                self.pc = self.addr_abs;
+                false
+            }
+            _ => {
+                self.read_byte(self.pc);
+                self.pc = self.pc.wrapping_add(1);
+                true
+            }
+        }
+    }
+
+    fn return_from_interrupt_cycle(&mut self) -> bool {
+        match self.step {
+            2 => self.read_pc(),
+            3 => self.read_stack(),
+            4 => {
+                self.pull_status();
+                false
+            }
+            5 => {
+                self.addr_abs = self.pull_byte() as u16;
+                false
+            }
+            _ => {
+                self.addr_abs |= (self.pull_byte() as u16) << 8;
+                self.pc = self.addr_abs;
+                true
+            }
+        }
+    }
+
+    fn push_cycle(&mut self) -> bool {
+        match self.step {
+            2 => self.read_pc(),
+            _ => {
+                self.execute();
+                true
+            }
+        }
+    }
+
+    fn pull_cycle(&mut self) -> bool {
+        match self.step {
+            2 => self.read_pc(),
+            3 => self.read_stack(),
+            _ => {
+                self.execute();
+                true
+            }
+        }
+    }
+
+    // BRK, and the IRQ, NMI and reset sequences that reuse it
+    fn break_cycle(&mut self) -> bool {
+        let interrupt = self.pending_interrupt;
+        // BRK shares the IRQ vector
+        let vector = interrupt.unwrap_or(Interrupt::Irq).vector();
+
+        match self.step {
+            2 => {
+                // BRK skips its padding byte, hardware interrupts leave PC alone
+                self.read_byte(self.pc);
+                if interrupt.is_none() {
+                    self.pc = self.pc.wrapping_add(1);
+                }
+                false
+            }
+            3 => self.push_unless_reset((self.pc >> 8) as u8),
+            4 => self.push_unless_reset(self.pc as u8),
+            5 => {
+                self.push_unless_reset(self.status_flags.to_stack_byte(interrupt.is_none()));
+
+                self.set_flag(StatusFlags::DISABLE_INTERRUPTS, true);
+                if self.variant == CpuVariant::Cmos65C02 {
+                    self.set_flag(StatusFlags::DECIMAL_MODE, false);
+                }
+                false
+            }
+            6 => {
+                self.addr_abs = self.read_byte(vector) as u16;
+                false
+            }
+            _ => {
+                self.addr_abs |= (self.read_byte(vector.wrapping_add(1)) as u16) << 8;
+                self.pc = self.addr_abs;
+                self.pending_interrupt = None;
+                true
+            }
+        }
+    }
+
+    // Reset runs the interrupt sequence with writes turned into reads
+    fn push_unless_reset(&mut self, value: u8) -> bool {
+        if self.pending_interrupt == Some(Interrupt::Reset) {
+            self.read_stack();
+            self.stack_ptr = self.stack_ptr.wrapping_sub(1);
+        } else {
+            self.push_byte(value);
+        }
+        false
+    }
+
+    fn read_pc(&mut self) -> bool {
+        self.read_byte(self.pc);
+        false
+    }
+
+    fn read_stack(&mut self) -> bool {
+        self.read_byte(STACK_PAGE | self.stack_ptr as u16);
+        false
+    }
+
+    fn fetch_operand_low(&mut self) -> bool {
+        self.addr_abs = self.read_byte(self.pc) as u16;
+        self.pc = self.pc.wrapping_add(1);
+        false
+    }
+
+    fn fetch_operand_high(&mut self) -> bool {
+        self.addr_abs |= (self.read_byte(self.pc) as u16) << 8;
+        self.pc = self.pc.wrapping_add(1);
+        false
+    }
+
+    fn fetch_pointer(&mut self) -> bool {
+        self.pointer = self.read_byte(self.pc) as u16;
+        self.pc = self.pc.wrapping_add(1);
+        false
+    }
+
+    fn fetch_pointer_low(&mut self) -> bool {
+        self.addr_abs = self.read_byte(self.pointer) as u16;
+        false
+    }
+
+    fn fetch_pointer_high(&mut self) -> bool {
+        let high_addr = (self.pointer as u8).wrapping_add(1) as u16;
+        self.addr_abs |= (self.read_byte(high_addr) as u16) << 8;
+        false
+    }
+
+    fn index_register(&self, addr_mode: AddrMode) -> u8 {
+        match addr_mode {
+            ZeroPageOffsetY | AbsoluteOffsetY | IndirectOffsetY => self.y,
+            _ => self.x,
+        }
+    }
+
+    // Indexed reads that stay in the same page skip the fix-up cycle, writes never do
+    fn needs_fix_up(&self, access: Access) -> bool {
+        access != Access::Read || self.pointer & 0xFF00 != self.addr_abs & 0xFF00
+    }
+
+    // Dummy read from the indexed address before its high byte is corrected
+    fn fix_up_cycle(&mut self) -> bool {
+        self.read_byte((self.pointer & 0xFF00) | (self.addr_abs & 0x00FF));
+        false
+    }
+}
@@ -0,0 +1,98 @@
+use std::{cell::RefCell, rc::Rc};
+
+// Anything that can be attached to an address range of the bus. Addresses are passed
+// as seen by the CPU, so devices handle their own mirroring.
+pub trait Device {
+    // None means the device leaves the data bus floating at this address
+    fn read(&mut self, addr: u16) -> Option<u8>;
+
+    fn write(&mut self, addr: u16, value: u8);
+
+    // Same as read, but must never change the device's state
+    fn peek(&self, addr: u16) -> Option<u8>;
+
+    // Called once per CPU cycle
+    fn tick(&mut self) {}
+
+    // Whether the device holds the CPU's IRQ line low
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // Whether the device holds the CPU's NMI line low
+    fn nmi(&self) -> bool {
+        false
+    }
+
+    // Address the device wants read through the 2A03's DMA unit, such as the DMC
+    // fetching its next sample byte
+    fn dma_request(&self) -> Option<u16> {
+        None
+    }
+
+    // Delivers the byte read for the pending DMA request
+    fn dma_transfer(&mut self, _value: u8) {}
+}
+
+// Lets the owner of a device keep a handle to it after attaching it to the bus
+impl<T: Device> Device for Rc<RefCell<T>> {
+    fn read(&mut self, addr: u16) -> Option<u8> {
+        self.borrow_mut().read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.borrow_mut().write(addr, value);
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        self.borrow().peek(addr)
+    }
+
+    fn tick(&mut self) {
+        self.borrow_mut().tick();
+    }
+
+    fn irq(&self) -> bool {
+        self.borrow().irq()
+    }
+
+    fn nmi(&self) -> bool {
+        self.borrow().nmi()
+    }
+
+    fn dma_request(&self) -> Option<u16> {
+        self.borrow().dma_request()
+    }
+
+    fn dma_transfer(&mut self, value: u8) {
+        self.borrow_mut().dma_transfer(value);
+    }
+}
+
+// Plain RAM, mirrored across whatever range it is attached to
+pub struct Ram {
+    data: Vec<u8>,
+}
+
+impl Ram {
+    pub fn new(size: usize) -> Self {
+        Self {
+            data: vec![0; size],
+        }
+    }
+}
+
+impl Device for Ram {
+    fn read(&mut self, addr: u16) -> Option<u8> {
+        self.peek(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        let len = self.data.len();
+        self.data[addr as usize % len] = value;
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        Some(self.data[addr as usize % self.data.len()])
+    }
+}
@@ -4,27 +4,38 @@ pub fn disassemble(cpu: &Mos6502, start: u16, end: u16) -> Vec<String> {
     let mut pc = start;
     let mut disassembled: Vec<String> = vec![];
     while pc <= end {
-        let sum = InstructionSummary::from(cpu.read_byte(pc));
-        pc += 1;
-        let (word, _, byte) = cpu.read_word_and_bytes(pc);
+        let sum = InstructionSummary::decode(cpu.peek_byte(pc), cpu.variant);
+        pc = pc.wrapping_add(1);
+        let (word, byte) = (cpu.peek_word(pc), cpu.peek_byte(pc));
 
         let (params, bytes_to_skip) = match sum.addr_mode {
-            Implied => ("".into(), 1),
+            Implied => ("".into(), 0),
             Immediate => (format!("#${:02X}", byte), 1),
             ZeroPage => (format!("${:02X}", byte), 1),
             ZeroPageOffsetX => (format!("${:02X}, X", byte), 1),
             ZeroPageOffsetY => (format!("${:02X}, Y", byte), 1),
             Absolute => (format!("${:04X}", word), 2),
-            AbsoluteOffsetX => (format!("${:04X}, X", word), 1),
-            AbsoluteOffsetY => (format!("${:04X}, Y", word), 1),
-            Indirect => (format!("(${:04X})", word), 1),
+            AbsoluteOffsetX => (format!("${:04X}, X", word), 2),
+            AbsoluteOffsetY => (format!("${:04X}, Y", word), 2),
+            Indirect => (format!("(${:04X})", word), 2),
             IndirectOffsetX => (format!("(${:02X}, X)", byte), 1),
             IndirectOffsetY => (format!("(${:02X}), Y", byte), 1),
-            Relative => (format!("${:02X} [${:04X}]", byte, word), 1),
+            ZeroPageIndirect => (format!("(${:02X})", byte), 1),
+            AbsoluteIndirectOffsetX => (format!("(${:04X}, X)", word), 2),
+            Relative => {
+                let target = pc.wrapping_add(1).wrapping_add(byte as i8 as u16);
+                (format!("${:02X} [${:04X}]", byte, target), 1)
+            }
         };
-        pc += bytes_to_skip;
+        pc = pc.wrapping_add(bytes_to_skip);
 
-        disassembled.push(format!("{} {}", sum.instruction.to_string(), params))
+        // Undocumented opcodes are marked with an asterisk, e.g. "*LAX $10"
+        let marker = if sum.undocumented { "*" } else { "" };
+        disassembled.push(format!("{}{} {}", marker, sum.instruction, params));
+
+        if pc < start {
+            break;
+        }
     }
     disassembled
 }
@@ -0,0 +1,26 @@
+use super::mos_6502::Mos6502;
+
+pub(super) const OAM_DMA: u16 = 0x4014;
+const OAM_DATA: u16 = 0x2004;
+
+impl Mos6502 {
+    // Copies a page of CPU memory to sprite memory. The CPU is halted for 513 cycles,
+    // plus one to line up with a read cycle when DMA starts on an odd cycle.
+    pub(super) fn oam_dma(&mut self, page: u8) {
+        let start = (page as u16) << 8;
+        for offset in 0..=0xFF {
+            let value = self.read_byte(start | offset);
+            self.bus.borrow_mut().write(OAM_DATA, value);
+        }
+
+        self.dma_stall += 513 + (self.total_cycles & 1) as u16;
+    }
+
+    // Serves DMA reads requested by devices, such as DMC sample fetches. Each byte
+    // halts the CPU for 4 cycles.
+    pub(super) fn device_dma(&mut self) {
+        if self.bus.borrow_mut().service_dma() {
+            self.dma_stall += 4;
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use super::{addr_modes::AddrMode, instructions::Instruction};
+use super::{addr_modes::AddrMode, instructions::Instruction, mos_6502::CpuVariant};
 use AddrMode::*;
 use Instruction::*;
 
@@ -6,6 +6,7 @@ pub struct InstructionSummary {
     pub addr_mode: AddrMode,
     pub instruction: Instruction,
     pub cycles: u8,
+    pub undocumented: bool,
 }
 
 impl InstructionSummary {
@@ -14,6 +15,76 @@ impl InstructionSummary {
             addr_mode,
             cycles,
             instruction,
+            undocumented: false,
+        }
+    }
+
+    pub fn undocumented(addr_mode: AddrMode, instruction: Instruction, cycles: u8) -> Self {
+        Self {
+            undocumented: true,
+            ..Self::new(addr_mode, instruction, cycles)
+        }
+    }
+
+    pub fn decode(opcode: u8, variant: CpuVariant) -> Self {
+        match variant {
+            CpuVariant::Cmos65C02 => Self::decode_65c02(opcode),
+            CpuVariant::Nmos6502 | CpuVariant::Ricoh2A03 => Self::from(opcode),
+        }
+    }
+
+    // The 65C02 replaces the undocumented NMOS opcodes, everything else decodes the same
+    fn decode_65c02(opcode: u8) -> Self {
+        match opcode {
+            0x04 => Self::new(ZeroPage, TSB_TestAndSetMemoryBits, 5),
+            0x0C => Self::new(Absolute, TSB_TestAndSetMemoryBits, 6),
+            0x12 => Self::new(ZeroPageIndirect, ORA_ORMemoryWithAcc, 5),
+            0x14 => Self::new(ZeroPage, TRB_TestAndResetMemoryBits, 5),
+            0x1A => Self::new(Implied, INC_IncrementMemoryByOne, 2),
+            0x1C => Self::new(Absolute, TRB_TestAndResetMemoryBits, 6),
+
+            0x32 => Self::new(ZeroPageIndirect, AND_AndBitwiseWithAcc, 5),
+            0x34 => Self::new(ZeroPageOffsetX, BIT_BitTestInMemoryWithAcc, 4),
+            0x3A => Self::new(Implied, DEC_DecrementMemoryByOne, 2),
+            0x3C => Self::new(AbsoluteOffsetX, BIT_BitTestInMemoryWithAcc, 4),
+
+            0x52 => Self::new(ZeroPageIndirect, EOR_ExclusiveORMemoryWithAcc, 5),
+            0x5A => Self::new(Implied, PHY_PushYOnStack, 3),
+
+            0x64 => Self::new(ZeroPage, STZ_StoreZeroInMemory, 3),
+            0x6C => Self::new(Indirect, JMP_JumpTo, 6),
+
+            0x72 => Self::new(ZeroPageIndirect, ADC_AddMemoryToAccWithCarry, 5),
+            0x74 => Self::new(ZeroPageOffsetX, STZ_StoreZeroInMemory, 4),
+            0x7A => Self::new(Implied, PLY_PullYFromStack, 4),
+            0x7C => Self::new(AbsoluteIndirectOffsetX, JMP_JumpTo, 6),
+
+            0x80 => Self::new(Relative, BRA_BranchAlways, 2),
+            0x89 => Self::new(Immediate, BIT_BitTestInMemoryWithAcc, 2),
+
+            0x92 => Self::new(ZeroPageIndirect, STA_StoreAccInMemory, 5),
+            0x9C => Self::new(Absolute, STZ_StoreZeroInMemory, 4),
+            0x9E => Self::new(AbsoluteOffsetX, STZ_StoreZeroInMemory, 5),
+
+            0xB2 => Self::new(ZeroPageIndirect, LDA_LoadAccWithMemory, 5),
+
+            0xD2 => Self::new(ZeroPageIndirect, CMP_CompareMemoryAndAcc, 5),
+            0xDA => Self::new(Implied, PHX_PushXOnStack, 3),
+
+            0xF2 => Self::new(ZeroPageIndirect, SBC_SubtractMemoryFromAccWithBorrow, 5),
+            0xFA => Self::new(Implied, PLX_PullXFromStack, 4),
+
+            // Unused opcodes are NOPs of different lengths and timings
+            0x02 | 0x22 | 0x42 | 0x62 | 0x82 | 0xC2 | 0xE2 => {
+                Self::undocumented(Immediate, NOP_NoOperation, 2)
+            }
+            0x44 => Self::undocumented(ZeroPage, NOP_NoOperation, 3),
+            0x54 | 0xD4 | 0xF4 => Self::undocumented(ZeroPageOffsetX, NOP_NoOperation, 4),
+            0x5C => Self::undocumented(Absolute, NOP_NoOperation, 8),
+            0xDC | 0xFC => Self::undocumented(Absolute, NOP_NoOperation, 4),
+            _ if opcode & 0x03 == 0x03 => Self::undocumented(Implied, NOP_NoOperation, 1),
+
+            _ => Self::from(opcode),
         }
     }
 }
@@ -23,172 +94,412 @@ impl From<u8> for InstructionSummary {
         match opcode {
             0x00 => Self::new(Implied, BRK_ForceBreak, 7),
             0x01 => Self::new(IndirectOffsetX, ORA_ORMemoryWithAcc, 6),
+            0x02 => Self::undocumented(Implied, JAM_HaltProcessor, 2),
+            0x03 => Self::undocumented(IndirectOffsetX, SLO_ShiftLeftThenORWithAcc, 8),
+            0x04 => Self::undocumented(ZeroPage, NOP_NoOperation, 3),
             0x05 => Self::new(ZeroPage, ORA_ORMemoryWithAcc, 3),
             0x06 => Self::new(ZeroPage, ASL_ShiftLeftOneBit, 5),
+            0x07 => Self::undocumented(ZeroPage, SLO_ShiftLeftThenORWithAcc, 5),
             0x08 => Self::new(Implied, PHP_PushProcessorStatusOnStack, 3),
             0x09 => Self::new(Immediate, ORA_ORMemoryWithAcc, 2),
             0x0A => Self::new(Implied, ASL_ShiftLeftOneBit, 2),
+            0x0B => Self::undocumented(Immediate, ANC_ANDThenCopyNegativeToCarry, 2),
+            0x0C => Self::undocumented(Absolute, NOP_NoOperation, 4),
             0x0D => Self::new(Absolute, ORA_ORMemoryWithAcc, 4),
             0x0E => Self::new(Absolute, ASL_ShiftLeftOneBit, 6),
+            0x0F => Self::undocumented(Absolute, SLO_ShiftLeftThenORWithAcc, 6),
 
             0x10 => Self::new(Relative, BPL_BranchOnResultPlus, 2),
             0x11 => Self::new(IndirectOffsetY, ORA_ORMemoryWithAcc, 5),
+            0x12 => Self::undocumented(Implied, JAM_HaltProcessor, 2),
+            0x13 => Self::undocumented(IndirectOffsetY, SLO_ShiftLeftThenORWithAcc, 8),
+            0x14 => Self::undocumented(ZeroPageOffsetX, NOP_NoOperation, 4),
             0x15 => Self::new(ZeroPageOffsetX, ORA_ORMemoryWithAcc, 4),
             0x16 => Self::new(ZeroPageOffsetX, ASL_ShiftLeftOneBit, 6),
+            0x17 => Self::undocumented(ZeroPageOffsetX, SLO_ShiftLeftThenORWithAcc, 6),
             0x18 => Self::new(Implied, CLC_ClearCarryFlag, 2),
             0x19 => Self::new(AbsoluteOffsetY, ORA_ORMemoryWithAcc, 4),
+            0x1A => Self::undocumented(Implied, NOP_NoOperation, 2),
+            0x1B => Self::undocumented(AbsoluteOffsetY, SLO_ShiftLeftThenORWithAcc, 7),
+            0x1C => Self::undocumented(AbsoluteOffsetX, NOP_NoOperation, 4),
             0x1D => Self::new(AbsoluteOffsetX, ORA_ORMemoryWithAcc, 4),
             0x1E => Self::new(AbsoluteOffsetX, ASL_ShiftLeftOneBit, 7),
+            0x1F => Self::undocumented(AbsoluteOffsetX, SLO_ShiftLeftThenORWithAcc, 7),
 
             0x20 => Self::new(Absolute, JSR_JumpToSavingReturnAddr, 6),
             0x21 => Self::new(IndirectOffsetX, AND_AndBitwiseWithAcc, 6),
+            0x22 => Self::undocumented(Implied, JAM_HaltProcessor, 2),
+            0x23 => Self::undocumented(IndirectOffsetX, RLA_RotateLeftThenANDWithAcc, 8),
             0x24 => Self::new(ZeroPage, BIT_BitTestInMemoryWithAcc, 3),
             0x25 => Self::new(ZeroPage, AND_AndBitwiseWithAcc, 3),
             0x26 => Self::new(ZeroPage, ROL_RotateOneBitLeft, 5),
+            0x27 => Self::undocumented(ZeroPage, RLA_RotateLeftThenANDWithAcc, 5),
             0x28 => Self::new(Implied, PLP_PullProcessorStatusFromStack, 4),
             0x29 => Self::new(Immediate, AND_AndBitwiseWithAcc, 2),
             0x2A => Self::new(Implied, ROL_RotateOneBitLeft, 2),
+            0x2B => Self::undocumented(Immediate, ANC_ANDThenCopyNegativeToCarry, 2),
             0x2C => Self::new(Absolute, BIT_BitTestInMemoryWithAcc, 4),
             0x2D => Self::new(Absolute, AND_AndBitwiseWithAcc, 4),
             0x2E => Self::new(Absolute, ROL_RotateOneBitLeft, 6),
+            0x2F => Self::undocumented(Absolute, RLA_RotateLeftThenANDWithAcc, 6),
 
             0x30 => Self::new(Relative, BMI_BranchOnResultMinus, 2),
             0x31 => Self::new(IndirectOffsetY, AND_AndBitwiseWithAcc, 5),
+            0x32 => Self::undocumented(Implied, JAM_HaltProcessor, 2),
+            0x33 => Self::undocumented(IndirectOffsetY, RLA_RotateLeftThenANDWithAcc, 8),
+            0x34 => Self::undocumented(ZeroPageOffsetX, NOP_NoOperation, 4),
             0x35 => Self::new(ZeroPageOffsetX, AND_AndBitwiseWithAcc, 4),
             0x36 => Self::new(ZeroPageOffsetX, ROL_RotateOneBitLeft, 6),
+            0x37 => Self::undocumented(ZeroPageOffsetX, RLA_RotateLeftThenANDWithAcc, 6),
             0x38 => Self::new(Implied, SEC_SetCarryFlag, 2),
             0x39 => Self::new(AbsoluteOffsetY, AND_AndBitwiseWithAcc, 4),
+            0x3A => Self::undocumented(Implied, NOP_NoOperation, 2),
+            0x3B => Self::undocumented(AbsoluteOffsetY, RLA_RotateLeftThenANDWithAcc, 7),
+            0x3C => Self::undocumented(AbsoluteOffsetX, NOP_NoOperation, 4),
             0x3D => Self::new(AbsoluteOffsetX, AND_AndBitwiseWithAcc, 4),
             0x3E => Self::new(AbsoluteOffsetX, ROL_RotateOneBitLeft, 7),
+            0x3F => Self::undocumented(AbsoluteOffsetX, RLA_RotateLeftThenANDWithAcc, 7),
 
             0x40 => Self::new(Implied, RTI_ReturnFromInterrupt, 6),
             0x41 => Self::new(IndirectOffsetX, EOR_ExclusiveORMemoryWithAcc, 6),
+            0x42 => Self::undocumented(Implied, JAM_HaltProcessor, 2),
+            0x43 => Self::undocumented(IndirectOffsetX, SRE_ShiftRightThenEORWithAcc, 8),
+            0x44 => Self::undocumented(ZeroPage, NOP_NoOperation, 3),
             0x45 => Self::new(ZeroPage, EOR_ExclusiveORMemoryWithAcc, 3),
             0x46 => Self::new(ZeroPage, LSR_ShiftOneBitRight, 5),
+            0x47 => Self::undocumented(ZeroPage, SRE_ShiftRightThenEORWithAcc, 5),
             0x48 => Self::new(Implied, PHA_PushAccOnStack, 3),
             0x49 => Self::new(Immediate, EOR_ExclusiveORMemoryWithAcc, 2),
             0x4A => Self::new(Implied, LSR_ShiftOneBitRight, 2),
+            0x4B => Self::undocumented(Immediate, ALR_ANDThenShiftRight, 2),
             0x4C => Self::new(Absolute, JMP_JumpTo, 3),
             0x4D => Self::new(Absolute, EOR_ExclusiveORMemoryWithAcc, 4),
             0x4E => Self::new(Absolute, LSR_ShiftOneBitRight, 6),
+            0x4F => Self::undocumented(Absolute, SRE_ShiftRightThenEORWithAcc, 6),
 
             0x50 => Self::new(Relative, BVC_BranchOnOverflowClear, 2),
-            0x51 => Self::new(IndirectOffsetX, EOR_ExclusiveORMemoryWithAcc, 5),
-            0x55 => Self::new(ZeroPage, EOR_ExclusiveORMemoryWithAcc, 4),
-            0x56 => Self::new(ZeroPage, LSR_ShiftOneBitRight, 6),
+            0x51 => Self::new(IndirectOffsetY, EOR_ExclusiveORMemoryWithAcc, 5),
+            0x52 => Self::undocumented(Implied, JAM_HaltProcessor, 2),
+            0x53 => Self::undocumented(IndirectOffsetY, SRE_ShiftRightThenEORWithAcc, 8),
+            0x54 => Self::undocumented(ZeroPageOffsetX, NOP_NoOperation, 4),
+            0x55 => Self::new(ZeroPageOffsetX, EOR_ExclusiveORMemoryWithAcc, 4),
+            0x56 => Self::new(ZeroPageOffsetX, LSR_ShiftOneBitRight, 6),
+            0x57 => Self::undocumented(ZeroPageOffsetX, SRE_ShiftRightThenEORWithAcc, 6),
             0x58 => Self::new(Implied, CLI_ClearInterruptDisableBit, 2),
             0x59 => Self::new(AbsoluteOffsetY, EOR_ExclusiveORMemoryWithAcc, 4),
+            0x5A => Self::undocumented(Implied, NOP_NoOperation, 2),
+            0x5B => Self::undocumented(AbsoluteOffsetY, SRE_ShiftRightThenEORWithAcc, 7),
+            0x5C => Self::undocumented(AbsoluteOffsetX, NOP_NoOperation, 4),
             0x5D => Self::new(AbsoluteOffsetX, EOR_ExclusiveORMemoryWithAcc, 4),
-            0x5E => Self::new(AbsoluteOffsetX, ASL_ShiftLeftOneBit, 7),
+            0x5E => Self::new(AbsoluteOffsetX, LSR_ShiftOneBitRight, 7),
+            0x5F => Self::undocumented(AbsoluteOffsetX, SRE_ShiftRightThenEORWithAcc, 7),
 
             0x60 => Self::new(Implied, RTS_ReturnFromSubroutine, 6),
             0x61 => Self::new(IndirectOffsetX, ADC_AddMemoryToAccWithCarry, 6),
+            0x62 => Self::undocumented(Implied, JAM_HaltProcessor, 2),
+            0x63 => Self::undocumented(IndirectOffsetX, RRA_RotateRightThenAddWithCarry, 8),
+            0x64 => Self::undocumented(ZeroPage, NOP_NoOperation, 3),
             0x65 => Self::new(ZeroPage, ADC_AddMemoryToAccWithCarry, 3),
             0x66 => Self::new(ZeroPage, ROR_RotateOneBitRight, 5),
+            0x67 => Self::undocumented(ZeroPage, RRA_RotateRightThenAddWithCarry, 5),
             0x68 => Self::new(Implied, PLA_PullAccFromStack, 4),
             0x69 => Self::new(Immediate, ADC_AddMemoryToAccWithCarry, 2),
             0x6A => Self::new(Implied, ROR_RotateOneBitRight, 2),
+            0x6B => Self::undocumented(Immediate, ARR_ANDThenRotateRight, 2),
             0x6C => Self::new(Indirect, JMP_JumpTo, 5),
             0x6D => Self::new(Absolute, ADC_AddMemoryToAccWithCarry, 4),
             0x6E => Self::new(Absolute, ROR_RotateOneBitRight, 6),
+            0x6F => Self::undocumented(Absolute, RRA_RotateRightThenAddWithCarry, 6),
 
             0x70 => Self::new(Relative, BVS_BranchOnOverflowSet, 2),
             0x71 => Self::new(IndirectOffsetY, ADC_AddMemoryToAccWithCarry, 5),
+            0x72 => Self::undocumented(Implied, JAM_HaltProcessor, 2),
+            0x73 => Self::undocumented(IndirectOffsetY, RRA_RotateRightThenAddWithCarry, 8),
+            0x74 => Self::undocumented(ZeroPageOffsetX, NOP_NoOperation, 4),
             0x75 => Self::new(ZeroPageOffsetX, ADC_AddMemoryToAccWithCarry, 4),
             0x76 => Self::new(ZeroPageOffsetX, ROR_RotateOneBitRight, 6),
+            0x77 => Self::undocumented(ZeroPageOffsetX, RRA_RotateRightThenAddWithCarry, 6),
             0x78 => Self::new(Implied, SEI_SetInterruptDisableStatus, 2),
             0x79 => Self::new(AbsoluteOffsetY, ADC_AddMemoryToAccWithCarry, 4),
+            0x7A => Self::undocumented(Implied, NOP_NoOperation, 2),
+            0x7B => Self::undocumented(AbsoluteOffsetY, RRA_RotateRightThenAddWithCarry, 7),
+            0x7C => Self::undocumented(AbsoluteOffsetX, NOP_NoOperation, 4),
             0x7D => Self::new(AbsoluteOffsetX, ADC_AddMemoryToAccWithCarry, 4),
             0x7E => Self::new(AbsoluteOffsetX, ROR_RotateOneBitRight, 7),
+            0x7F => Self::undocumented(AbsoluteOffsetX, RRA_RotateRightThenAddWithCarry, 7),
 
+            0x80 => Self::undocumented(Immediate, NOP_NoOperation, 2),
             0x81 => Self::new(IndirectOffsetX, STA_StoreAccInMemory, 6),
+            0x82 => Self::undocumented(Immediate, NOP_NoOperation, 2),
+            0x83 => Self::undocumented(IndirectOffsetX, SAX_StoreAccANDX, 6),
             0x84 => Self::new(ZeroPage, STY_StoreYInMemory, 3),
             0x85 => Self::new(ZeroPage, STA_StoreAccInMemory, 3),
             0x86 => Self::new(ZeroPage, STX_StoreXInMemory, 3),
+            0x87 => Self::undocumented(ZeroPage, SAX_StoreAccANDX, 3),
             0x88 => Self::new(Implied, DEY_DecrementYByOne, 2),
+            0x89 => Self::undocumented(Immediate, NOP_NoOperation, 2),
             0x8A => Self::new(Implied, TXA_TransferXToAcc, 2),
+            0x8B => Self::undocumented(Immediate, ANE_ANDXAndImmediateIntoAcc, 2),
             0x8C => Self::new(Absolute, STY_StoreYInMemory, 4),
             0x8D => Self::new(Absolute, STA_StoreAccInMemory, 4),
             0x8E => Self::new(Absolute, STX_StoreXInMemory, 4),
+            0x8F => Self::undocumented(Absolute, SAX_StoreAccANDX, 4),
 
             0x90 => Self::new(Relative, BCC_BranchOnCarryClear, 2),
             0x91 => Self::new(IndirectOffsetY, STA_StoreAccInMemory, 6),
+            0x92 => Self::undocumented(Implied, JAM_HaltProcessor, 2),
+            0x93 => Self::undocumented(IndirectOffsetY, SHA_StoreAccANDXANDHighByte, 6),
             0x94 => Self::new(ZeroPageOffsetX, STY_StoreYInMemory, 4),
             0x95 => Self::new(ZeroPageOffsetX, STA_StoreAccInMemory, 4),
             0x96 => Self::new(ZeroPageOffsetY, STX_StoreXInMemory, 4),
+            0x97 => Self::undocumented(ZeroPageOffsetY, SAX_StoreAccANDX, 4),
             0x98 => Self::new(Implied, TYA_TransferYToAcc, 2),
             0x99 => Self::new(AbsoluteOffsetY, STA_StoreAccInMemory, 5),
             0x9A => Self::new(Implied, TXS_TransferXToStackRegister, 2),
+            0x9B => Self::undocumented(AbsoluteOffsetY, TAS_TransferAccANDXToStackThenStore, 5),
+            0x9C => Self::undocumented(AbsoluteOffsetX, SHY_StoreYANDHighByte, 5),
             0x9D => Self::new(AbsoluteOffsetX, STA_StoreAccInMemory, 5),
+            0x9E => Self::undocumented(AbsoluteOffsetY, SHX_StoreXANDHighByte, 5),
+            0x9F => Self::undocumented(AbsoluteOffsetY, SHA_StoreAccANDXANDHighByte, 5),
 
             0xA0 => Self::new(Immediate, LDY_LoadYWithMemory, 2),
             0xA1 => Self::new(IndirectOffsetX, LDA_LoadAccWithMemory, 6),
             0xA2 => Self::new(Immediate, LDX_LoadXWithMemory, 2),
+            0xA3 => Self::undocumented(IndirectOffsetX, LAX_LoadAccAndXWithMemory, 6),
             0xA4 => Self::new(ZeroPage, LDY_LoadYWithMemory, 3),
             0xA5 => Self::new(ZeroPage, LDA_LoadAccWithMemory, 3),
             0xA6 => Self::new(ZeroPage, LDX_LoadXWithMemory, 3),
+            0xA7 => Self::undocumented(ZeroPage, LAX_LoadAccAndXWithMemory, 3),
             0xA8 => Self::new(Implied, TAY_TransferAccToY, 2),
             0xA9 => Self::new(Immediate, LDA_LoadAccWithMemory, 2),
             0xAA => Self::new(Implied, TAX_TransferAccToX, 2),
+            0xAB => Self::undocumented(Immediate, LXA_LoadAccAndXWithImmediate, 2),
             0xAC => Self::new(Absolute, LDY_LoadYWithMemory, 4),
             0xAD => Self::new(Absolute, LDA_LoadAccWithMemory, 4),
             0xAE => Self::new(Absolute, LDX_LoadXWithMemory, 4),
+            0xAF => Self::undocumented(Absolute, LAX_LoadAccAndXWithMemory, 4),
 
             0xB0 => Self::new(Relative, BCS_BranchOnCarrySet, 2),
             0xB1 => Self::new(IndirectOffsetY, LDA_LoadAccWithMemory, 5),
+            0xB2 => Self::undocumented(Implied, JAM_HaltProcessor, 2),
+            0xB3 => Self::undocumented(IndirectOffsetY, LAX_LoadAccAndXWithMemory, 5),
             0xB4 => Self::new(ZeroPageOffsetX, LDY_LoadYWithMemory, 4),
             0xB5 => Self::new(ZeroPageOffsetX, LDA_LoadAccWithMemory, 4),
             0xB6 => Self::new(ZeroPageOffsetY, LDX_LoadXWithMemory, 4),
+            0xB7 => Self::undocumented(ZeroPageOffsetY, LAX_LoadAccAndXWithMemory, 4),
             0xB8 => Self::new(Implied, CLV_ClearOverflowFlag, 2),
             0xB9 => Self::new(AbsoluteOffsetY, LDA_LoadAccWithMemory, 4),
             0xBA => Self::new(Implied, TSX_TransferStackPointerToX, 2),
+            0xBB => Self::undocumented(AbsoluteOffsetY, LAS_LoadAccXAndStackWithMemoryANDStack, 4),
             0xBC => Self::new(AbsoluteOffsetX, LDY_LoadYWithMemory, 4),
             0xBD => Self::new(AbsoluteOffsetX, LDA_LoadAccWithMemory, 4),
             0xBE => Self::new(AbsoluteOffsetY, LDX_LoadXWithMemory, 4),
+            0xBF => Self::undocumented(AbsoluteOffsetY, LAX_LoadAccAndXWithMemory, 4),
 
-            0xC0 => Self::new(Relative, CPY_CompareMemoryAndY, 2),
-            0xC1 => Self::new(IndirectOffsetY, CMP_CompareMemoryAndAcc, 6),
+            0xC0 => Self::new(Immediate, CPY_CompareMemoryAndY, 2),
+            0xC1 => Self::new(IndirectOffsetX, CMP_CompareMemoryAndAcc, 6),
+            0xC2 => Self::undocumented(Immediate, NOP_NoOperation, 2),
+            0xC3 => Self::undocumented(IndirectOffsetX, DCP_DecrementThenCompareWithAcc, 8),
             0xC4 => Self::new(ZeroPage, CPY_CompareMemoryAndY, 3),
             0xC5 => Self::new(ZeroPage, CMP_CompareMemoryAndAcc, 3),
             0xC6 => Self::new(ZeroPage, DEC_DecrementMemoryByOne, 5),
+            0xC7 => Self::undocumented(ZeroPage, DCP_DecrementThenCompareWithAcc, 5),
             0xC8 => Self::new(Implied, INY_IncrementYByOne, 2),
             0xC9 => Self::new(Immediate, CMP_CompareMemoryAndAcc, 2),
             0xCA => Self::new(Implied, DEX_DecrementXByOne, 2),
+            0xCB => Self::undocumented(Immediate, SBX_SubtractFromAccANDXIntoX, 2),
             0xCC => Self::new(Absolute, CPY_CompareMemoryAndY, 4),
             0xCD => Self::new(Absolute, CMP_CompareMemoryAndAcc, 4),
             0xCE => Self::new(Absolute, DEC_DecrementMemoryByOne, 6),
+            0xCF => Self::undocumented(Absolute, DCP_DecrementThenCompareWithAcc, 6),
 
             0xD0 => Self::new(Relative, BNE_BranchOnResultNotZero, 2),
             0xD1 => Self::new(IndirectOffsetY, CMP_CompareMemoryAndAcc, 5),
+            0xD2 => Self::undocumented(Implied, JAM_HaltProcessor, 2),
+            0xD3 => Self::undocumented(IndirectOffsetY, DCP_DecrementThenCompareWithAcc, 8),
+            0xD4 => Self::undocumented(ZeroPageOffsetX, NOP_NoOperation, 4),
             0xD5 => Self::new(ZeroPageOffsetX, CMP_CompareMemoryAndAcc, 4),
             0xD6 => Self::new(ZeroPageOffsetX, DEC_DecrementMemoryByOne, 6),
+            0xD7 => Self::undocumented(ZeroPageOffsetX, DCP_DecrementThenCompareWithAcc, 6),
             0xD8 => Self::new(Implied, CLD_ClearDecimalMode, 2),
             0xD9 => Self::new(AbsoluteOffsetY, CMP_CompareMemoryAndAcc, 4),
+            0xDA => Self::undocumented(Implied, NOP_NoOperation, 2),
+            0xDB => Self::undocumented(AbsoluteOffsetY, DCP_DecrementThenCompareWithAcc, 7),
+            0xDC => Self::undocumented(AbsoluteOffsetX, NOP_NoOperation, 4),
             0xDD => Self::new(AbsoluteOffsetX, CMP_CompareMemoryAndAcc, 4),
             0xDE => Self::new(AbsoluteOffsetX, DEC_DecrementMemoryByOne, 7),
+            0xDF => Self::undocumented(AbsoluteOffsetX, DCP_DecrementThenCompareWithAcc, 7),
 
             0xE0 => Self::new(Immediate, CPX_CompareMemoryAndX, 2),
             0xE1 => Self::new(IndirectOffsetX, SBC_SubtractMemoryFromAccWithBorrow, 6),
+            0xE2 => Self::undocumented(Immediate, NOP_NoOperation, 2),
+            0xE3 => Self::undocumented(IndirectOffsetX, ISC_IncrementThenSubtractFromAcc, 8),
             0xE4 => Self::new(ZeroPage, CPX_CompareMemoryAndX, 3),
             0xE5 => Self::new(ZeroPage, SBC_SubtractMemoryFromAccWithBorrow, 3),
             0xE6 => Self::new(ZeroPage, INC_IncrementMemoryByOne, 5),
+            0xE7 => Self::undocumented(ZeroPage, ISC_IncrementThenSubtractFromAcc, 5),
             0xE8 => Self::new(Implied, INX_IncrementXByOne, 2),
             0xE9 => Self::new(Immediate, SBC_SubtractMemoryFromAccWithBorrow, 2),
             0xEA => Self::new(Implied, NOP_NoOperation, 2),
+            0xEB => Self::undocumented(Immediate, SBC_SubtractMemoryFromAccWithBorrow, 2),
             0xEC => Self::new(Absolute, CPX_CompareMemoryAndX, 4),
             0xED => Self::new(Absolute, SBC_SubtractMemoryFromAccWithBorrow, 4),
             0xEE => Self::new(Absolute, INC_IncrementMemoryByOne, 6),
+            0xEF => Self::undocumented(Absolute, ISC_IncrementThenSubtractFromAcc, 6),
 
             0xF0 => Self::new(Relative, BEQ_BranchOnResultZero, 2),
             0xF1 => Self::new(IndirectOffsetY, SBC_SubtractMemoryFromAccWithBorrow, 5),
+            0xF2 => Self::undocumented(Implied, JAM_HaltProcessor, 2),
+            0xF3 => Self::undocumented(IndirectOffsetY, ISC_IncrementThenSubtractFromAcc, 8),
+            0xF4 => Self::undocumented(ZeroPageOffsetX, NOP_NoOperation, 4),
             0xF5 => Self::new(ZeroPageOffsetX, SBC_SubtractMemoryFromAccWithBorrow, 4),
             0xF6 => Self::new(ZeroPageOffsetX, INC_IncrementMemoryByOne, 6),
+            0xF7 => Self::undocumented(ZeroPageOffsetX, ISC_IncrementThenSubtractFromAcc, 6),
             0xF8 => Self::new(Implied, SED_SetDecimalMode, 2),
             0xF9 => Self::new(AbsoluteOffsetY, SBC_SubtractMemoryFromAccWithBorrow, 4),
+            0xFA => Self::undocumented(Implied, NOP_NoOperation, 2),
+            0xFB => Self::undocumented(AbsoluteOffsetY, ISC_IncrementThenSubtractFromAcc, 7),
+            0xFC => Self::undocumented(AbsoluteOffsetX, NOP_NoOperation, 4),
             0xFD => Self::new(AbsoluteOffsetX, SBC_SubtractMemoryFromAccWithBorrow, 4),
             0xFE => Self::new(AbsoluteOffsetX, INC_IncrementMemoryByOne, 7),
+            0xFF => Self::undocumented(AbsoluteOffsetX, ISC_IncrementThenSubtractFromAcc, 7),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The NMOS 6502 opcode matrix: mnemonic, addressing mode, length in bytes and base
+    // cycles, before page crossing and branch penalties. Undocumented opcodes are marked
+    // with a '*'. JAM halts the CPU, it is listed with the two cycles spent fetching it.
+    #[rustfmt::skip]
+    const REFERENCE: [(&str, &str, u16, u8); 256] = [
+        ("BRK", "imp", 1, 7), ("ORA", "izx", 2, 6), ("*JAM", "imp", 1, 2), ("*SLO", "izx", 2, 8),
+        ("*NOP", "zp", 2, 3), ("ORA", "zp", 2, 3), ("ASL", "zp", 2, 5), ("*SLO", "zp", 2, 5),
+        ("PHP", "imp", 1, 3), ("ORA", "imm", 2, 2), ("ASL", "imp", 1, 2), ("*ANC", "imm", 2, 2),
+        ("*NOP", "abs", 3, 4), ("ORA", "abs", 3, 4), ("ASL", "abs", 3, 6), ("*SLO", "abs", 3, 6),
+
+        ("BPL", "rel", 2, 2), ("ORA", "izy", 2, 5), ("*JAM", "imp", 1, 2), ("*SLO", "izy", 2, 8),
+        ("*NOP", "zpx", 2, 4), ("ORA", "zpx", 2, 4), ("ASL", "zpx", 2, 6), ("*SLO", "zpx", 2, 6),
+        ("CLC", "imp", 1, 2), ("ORA", "aby", 3, 4), ("*NOP", "imp", 1, 2), ("*SLO", "aby", 3, 7),
+        ("*NOP", "abx", 3, 4), ("ORA", "abx", 3, 4), ("ASL", "abx", 3, 7), ("*SLO", "abx", 3, 7),
+
+        ("JSR", "abs", 3, 6), ("AND", "izx", 2, 6), ("*JAM", "imp", 1, 2), ("*RLA", "izx", 2, 8),
+        ("BIT", "zp", 2, 3), ("AND", "zp", 2, 3), ("ROL", "zp", 2, 5), ("*RLA", "zp", 2, 5),
+        ("PLP", "imp", 1, 4), ("AND", "imm", 2, 2), ("ROL", "imp", 1, 2), ("*ANC", "imm", 2, 2),
+        ("BIT", "abs", 3, 4), ("AND", "abs", 3, 4), ("ROL", "abs", 3, 6), ("*RLA", "abs", 3, 6),
+
+        ("BMI", "rel", 2, 2), ("AND", "izy", 2, 5), ("*JAM", "imp", 1, 2), ("*RLA", "izy", 2, 8),
+        ("*NOP", "zpx", 2, 4), ("AND", "zpx", 2, 4), ("ROL", "zpx", 2, 6), ("*RLA", "zpx", 2, 6),
+        ("SEC", "imp", 1, 2), ("AND", "aby", 3, 4), ("*NOP", "imp", 1, 2), ("*RLA", "aby", 3, 7),
+        ("*NOP", "abx", 3, 4), ("AND", "abx", 3, 4), ("ROL", "abx", 3, 7), ("*RLA", "abx", 3, 7),
+
+        ("RTI", "imp", 1, 6), ("EOR", "izx", 2, 6), ("*JAM", "imp", 1, 2), ("*SRE", "izx", 2, 8),
+        ("*NOP", "zp", 2, 3), ("EOR", "zp", 2, 3), ("LSR", "zp", 2, 5), ("*SRE", "zp", 2, 5),
+        ("PHA", "imp", 1, 3), ("EOR", "imm", 2, 2), ("LSR", "imp", 1, 2), ("*ALR", "imm", 2, 2),
+        ("JMP", "abs", 3, 3), ("EOR", "abs", 3, 4), ("LSR", "abs", 3, 6), ("*SRE", "abs", 3, 6),
+
+        ("BVC", "rel", 2, 2), ("EOR", "izy", 2, 5), ("*JAM", "imp", 1, 2), ("*SRE", "izy", 2, 8),
+        ("*NOP", "zpx", 2, 4), ("EOR", "zpx", 2, 4), ("LSR", "zpx", 2, 6), ("*SRE", "zpx", 2, 6),
+        ("CLI", "imp", 1, 2), ("EOR", "aby", 3, 4), ("*NOP", "imp", 1, 2), ("*SRE", "aby", 3, 7),
+        ("*NOP", "abx", 3, 4), ("EOR", "abx", 3, 4), ("LSR", "abx", 3, 7), ("*SRE", "abx", 3, 7),
+
+        ("RTS", "imp", 1, 6), ("ADC", "izx", 2, 6), ("*JAM", "imp", 1, 2), ("*RRA", "izx", 2, 8),
+        ("*NOP", "zp", 2, 3), ("ADC", "zp", 2, 3), ("ROR", "zp", 2, 5), ("*RRA", "zp", 2, 5),
+        ("PLA", "imp", 1, 4), ("ADC", "imm", 2, 2), ("ROR", "imp", 1, 2), ("*ARR", "imm", 2, 2),
+        ("JMP", "ind", 3, 5), ("ADC", "abs", 3, 4), ("ROR", "abs", 3, 6), ("*RRA", "abs", 3, 6),
+
+        ("BVS", "rel", 2, 2), ("ADC", "izy", 2, 5), ("*JAM", "imp", 1, 2), ("*RRA", "izy", 2, 8),
+        ("*NOP", "zpx", 2, 4), ("ADC", "zpx", 2, 4), ("ROR", "zpx", 2, 6), ("*RRA", "zpx", 2, 6),
+        ("SEI", "imp", 1, 2), ("ADC", "aby", 3, 4), ("*NOP", "imp", 1, 2), ("*RRA", "aby", 3, 7),
+        ("*NOP", "abx", 3, 4), ("ADC", "abx", 3, 4), ("ROR", "abx", 3, 7), ("*RRA", "abx", 3, 7),
+
+        ("*NOP", "imm", 2, 2), ("STA", "izx", 2, 6), ("*NOP", "imm", 2, 2), ("*SAX", "izx", 2, 6),
+        ("STY", "zp", 2, 3), ("STA", "zp", 2, 3), ("STX", "zp", 2, 3), ("*SAX", "zp", 2, 3),
+        ("DEY", "imp", 1, 2), ("*NOP", "imm", 2, 2), ("TXA", "imp", 1, 2), ("*ANE", "imm", 2, 2),
+        ("STY", "abs", 3, 4), ("STA", "abs", 3, 4), ("STX", "abs", 3, 4), ("*SAX", "abs", 3, 4),
+
+        ("BCC", "rel", 2, 2), ("STA", "izy", 2, 6), ("*JAM", "imp", 1, 2), ("*SHA", "izy", 2, 6),
+        ("STY", "zpx", 2, 4), ("STA", "zpx", 2, 4), ("STX", "zpy", 2, 4), ("*SAX", "zpy", 2, 4),
+        ("TYA", "imp", 1, 2), ("STA", "aby", 3, 5), ("TXS", "imp", 1, 2), ("*TAS", "aby", 3, 5),
+        ("*SHY", "abx", 3, 5), ("STA", "abx", 3, 5), ("*SHX", "aby", 3, 5), ("*SHA", "aby", 3, 5),
+
+        ("LDY", "imm", 2, 2), ("LDA", "izx", 2, 6), ("LDX", "imm", 2, 2), ("*LAX", "izx", 2, 6),
+        ("LDY", "zp", 2, 3), ("LDA", "zp", 2, 3), ("LDX", "zp", 2, 3), ("*LAX", "zp", 2, 3),
+        ("TAY", "imp", 1, 2), ("LDA", "imm", 2, 2), ("TAX", "imp", 1, 2), ("*LXA", "imm", 2, 2),
+        ("LDY", "abs", 3, 4), ("LDA", "abs", 3, 4), ("LDX", "abs", 3, 4), ("*LAX", "abs", 3, 4),
+
+        ("BCS", "rel", 2, 2), ("LDA", "izy", 2, 5), ("*JAM", "imp", 1, 2), ("*LAX", "izy", 2, 5),
+        ("LDY", "zpx", 2, 4), ("LDA", "zpx", 2, 4), ("LDX", "zpy", 2, 4), ("*LAX", "zpy", 2, 4),
+        ("CLV", "imp", 1, 2), ("LDA", "aby", 3, 4), ("TSX", "imp", 1, 2), ("*LAS", "aby", 3, 4),
+        ("LDY", "abx", 3, 4), ("LDA", "abx", 3, 4), ("LDX", "aby", 3, 4), ("*LAX", "aby", 3, 4),
+
+        ("CPY", "imm", 2, 2), ("CMP", "izx", 2, 6), ("*NOP", "imm", 2, 2), ("*DCP", "izx", 2, 8),
+        ("CPY", "zp", 2, 3), ("CMP", "zp", 2, 3), ("DEC", "zp", 2, 5), ("*DCP", "zp", 2, 5),
+        ("INY", "imp", 1, 2), ("CMP", "imm", 2, 2), ("DEX", "imp", 1, 2), ("*SBX", "imm", 2, 2),
+        ("CPY", "abs", 3, 4), ("CMP", "abs", 3, 4), ("DEC", "abs", 3, 6), ("*DCP", "abs", 3, 6),
+
+        ("BNE", "rel", 2, 2), ("CMP", "izy", 2, 5), ("*JAM", "imp", 1, 2), ("*DCP", "izy", 2, 8),
+        ("*NOP", "zpx", 2, 4), ("CMP", "zpx", 2, 4), ("DEC", "zpx", 2, 6), ("*DCP", "zpx", 2, 6),
+        ("CLD", "imp", 1, 2), ("CMP", "aby", 3, 4), ("*NOP", "imp", 1, 2), ("*DCP", "aby", 3, 7),
+        ("*NOP", "abx", 3, 4), ("CMP", "abx", 3, 4), ("DEC", "abx", 3, 7), ("*DCP", "abx", 3, 7),
+
+        ("CPX", "imm", 2, 2), ("SBC", "izx", 2, 6), ("*NOP", "imm", 2, 2), ("*ISC", "izx", 2, 8),
+        ("CPX", "zp", 2, 3), ("SBC", "zp", 2, 3), ("INC", "zp", 2, 5), ("*ISC", "zp", 2, 5),
+        ("INX", "imp", 1, 2), ("SBC", "imm", 2, 2), ("NOP", "imp", 1, 2), ("*SBC", "imm", 2, 2),
+        ("CPX", "abs", 3, 4), ("SBC", "abs", 3, 4), ("INC", "abs", 3, 6), ("*ISC", "abs", 3, 6),
+
+        ("BEQ", "rel", 2, 2), ("SBC", "izy", 2, 5), ("*JAM", "imp", 1, 2), ("*ISC", "izy", 2, 8),
+        ("*NOP", "zpx", 2, 4), ("SBC", "zpx", 2, 4), ("INC", "zpx", 2, 6), ("*ISC", "zpx", 2, 6),
+        ("SED", "imp", 1, 2), ("SBC", "aby", 3, 4), ("*NOP", "imp", 1, 2), ("*ISC", "aby", 3, 7),
+        ("*NOP", "abx", 3, 4), ("SBC", "abx", 3, 4), ("INC", "abx", 3, 7), ("*ISC", "abx", 3, 7),
+    ];
+
+    fn mode_and_length(addr_mode: &AddrMode) -> (&'static str, u16) {
+        match addr_mode {
+            Implied => ("imp", 1),
+            Immediate => ("imm", 2),
+            ZeroPage => ("zp", 2),
+            ZeroPageOffsetX => ("zpx", 2),
+            ZeroPageOffsetY => ("zpy", 2),
+            Absolute => ("abs", 3),
+            AbsoluteOffsetX => ("abx", 3),
+            AbsoluteOffsetY => ("aby", 3),
+            Indirect => ("ind", 3),
+            IndirectOffsetX => ("izx", 2),
+            IndirectOffsetY => ("izy", 2),
+            Relative => ("rel", 2),
+            ZeroPageIndirect => ("izp", 2),
+            AbsoluteIndirectOffsetX => ("iax", 3),
+        }
+    }
+
+    #[test]
+    fn every_opcode_matches_the_reference_table() {
+        for (opcode, &(mnemonic, mode, length, cycles)) in REFERENCE.iter().enumerate() {
+            let summary = InstructionSummary::from(opcode as u8);
+            let (decoded_mode, decoded_length) = mode_and_length(&summary.addr_mode);
 
-            _ => Self::new(Implied, InvalidInstruction, 2),
+            assert_eq!(
+                (
+                    summary.instruction.to_string().as_str(),
+                    decoded_mode,
+                    decoded_length,
+                    summary.cycles,
+                    summary.undocumented
+                ),
+                (
+                    mnemonic.trim_start_matches('*'),
+                    mode,
+                    length,
+                    cycles,
+                    mnemonic.starts_with('*')
+                ),
+                "opcode ${:02X}",
+                opcode
+            );
         }
     }
 }
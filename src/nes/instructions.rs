@@ -1,4 +1,3 @@
-
 #[allow(non_camel_case_types)]
 pub enum Instruction {
     ADC_AddMemoryToAccWithCarry,
@@ -57,15 +56,52 @@ pub enum Instruction {
     TXA_TransferXToAcc,
     TXS_TransferXToStackRegister,
     TYA_TransferYToAcc,
-    InvalidInstruction,
+
+    // Undocumented
+    ALR_ANDThenShiftRight,
+    ANC_ANDThenCopyNegativeToCarry,
+    ANE_ANDXAndImmediateIntoAcc,
+    ARR_ANDThenRotateRight,
+    DCP_DecrementThenCompareWithAcc,
+    ISC_IncrementThenSubtractFromAcc,
+    JAM_HaltProcessor,
+    LAS_LoadAccXAndStackWithMemoryANDStack,
+    LAX_LoadAccAndXWithMemory,
+    LXA_LoadAccAndXWithImmediate,
+    RLA_RotateLeftThenANDWithAcc,
+    RRA_RotateRightThenAddWithCarry,
+    SAX_StoreAccANDX,
+    SBX_SubtractFromAccANDXIntoX,
+    SHA_StoreAccANDXANDHighByte,
+    SHX_StoreXANDHighByte,
+    SHY_StoreYANDHighByte,
+    SLO_ShiftLeftThenORWithAcc,
+    SRE_ShiftRightThenEORWithAcc,
+    TAS_TransferAccANDXToStackThenStore,
+
+    // 65C02
+    BRA_BranchAlways,
+    PHX_PushXOnStack,
+    PHY_PushYOnStack,
+    PLX_PullXFromStack,
+    PLY_PullYFromStack,
+    STZ_StoreZeroInMemory,
+    TRB_TestAndResetMemoryBits,
+    TSB_TestAndSetMemoryBits,
 }
 
+use std::fmt;
+
 use Instruction::*;
 
-use super::mos_6502::{Mos6502, Flag};
+use super::{
+    addr_modes::AddrMode,
+    mos_6502::{CpuVariant, Mos6502},
+    status_flags::StatusFlags,
+};
 
-impl ToString for Instruction {
-    fn to_string(&self) -> String {
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let str = match self {
             ADC_AddMemoryToAccWithCarry => "ADC",
             AND_AndBitwiseWithAcc => "AND",
@@ -123,95 +159,537 @@ impl ToString for Instruction {
             TXA_TransferXToAcc => "TXA",
             TXS_TransferXToStackRegister => "TXS",
             TYA_TransferYToAcc => "TYA",
-            InvalidInstruction => "INVALID INSTRUCTION!",
+            ALR_ANDThenShiftRight => "ALR",
+            ANC_ANDThenCopyNegativeToCarry => "ANC",
+            ANE_ANDXAndImmediateIntoAcc => "ANE",
+            ARR_ANDThenRotateRight => "ARR",
+            DCP_DecrementThenCompareWithAcc => "DCP",
+            ISC_IncrementThenSubtractFromAcc => "ISC",
+            JAM_HaltProcessor => "JAM",
+            LAS_LoadAccXAndStackWithMemoryANDStack => "LAS",
+            LAX_LoadAccAndXWithMemory => "LAX",
+            LXA_LoadAccAndXWithImmediate => "LXA",
+            RLA_RotateLeftThenANDWithAcc => "RLA",
+            RRA_RotateRightThenAddWithCarry => "RRA",
+            SAX_StoreAccANDX => "SAX",
+            SBX_SubtractFromAccANDXIntoX => "SBX",
+            SHA_StoreAccANDXANDHighByte => "SHA",
+            SHX_StoreXANDHighByte => "SHX",
+            SHY_StoreYANDHighByte => "SHY",
+            SLO_ShiftLeftThenORWithAcc => "SLO",
+            SRE_ShiftRightThenEORWithAcc => "SRE",
+            TAS_TransferAccANDXToStackThenStore => "TAS",
+            BRA_BranchAlways => "BRA",
+            PHX_PushXOnStack => "PHX",
+            PHY_PushYOnStack => "PHY",
+            PLX_PullXFromStack => "PLX",
+            PLY_PullYFromStack => "PLY",
+            STZ_StoreZeroInMemory => "STZ",
+            TRB_TestAndResetMemoryBits => "TRB",
+            TSB_TestAndSetMemoryBits => "TSB",
         };
-        str.into()
+        f.write_str(str)
     }
 }
 
 impl Mos6502 {
     pub fn handle_instruction(&mut self, instruction: Instruction) -> u8 {
         match instruction {
-            ADC_AddMemoryToAccWithCarry => {}
+            ADC_AddMemoryToAccWithCarry => {
+                self.fetch();
+                self.add_with_carry(self.fetched);
+                return 1;
+            }
             AND_AndBitwiseWithAcc => {
                 self.fetch();
                 self.a &= self.fetched;
-                self.set_flag(Flag::Zero, self.a == 0);
-                self.set_flag(Flag::Negative, self.a & 0b10000000 == 1);
+                self.set_zero_and_negative(self.a);
+                return 1;
+            }
+            ASL_ShiftLeftOneBit => {
+                self.fetch();
+                let result = self.shift_left(self.fetched);
+                self.write_result(result);
+            }
+            BCC_BranchOnCarryClear => self.branch(!self.get_flag(StatusFlags::CARRY)),
+            BCS_BranchOnCarrySet => self.branch(self.get_flag(StatusFlags::CARRY)),
+            BEQ_BranchOnResultZero => self.branch(self.get_flag(StatusFlags::ZERO)),
+            BIT_BitTestInMemoryWithAcc => {
+                self.fetch();
+                self.set_flag(StatusFlags::ZERO, self.a & self.fetched == 0);
+
+                // The 65C02 immediate form only affects the Zero flag
+                if matches!(self.addr_mode(), AddrMode::Immediate) {
+                    return 0;
+                }
+                self.set_flag(StatusFlags::OVERFLOW, self.fetched & 0b01000000 != 0);
+                self.set_flag(StatusFlags::NEGATIVE, self.fetched & 0b10000000 != 0);
                 return 1;
             }
-            ASL_ShiftLeftOneBit => {}
-            BCC_BranchOnCarryClear => {}
-            BCS_BranchOnCarrySet => {
-                if self.get_flag(Flag::Carry) {
-                    self.cycles += 1;
-                    self.addr_abs = self.pc + self.addr_rel;
-
-                    if self.addr_abs & 0xFF00 != self.pc & 0xFF00 {
-                        self.cycles += 1;
-                    }
-                    self.pc = self.addr_abs
+            BMI_BranchOnResultMinus => self.branch(self.get_flag(StatusFlags::NEGATIVE)),
+            BNE_BranchOnResultNotZero => self.branch(!self.get_flag(StatusFlags::ZERO)),
+            BPL_BranchOnResultPlus => self.branch(!self.get_flag(StatusFlags::NEGATIVE)),
+            BRK_ForceBreak => {
+                // BRK skips a padding byte, so the return address is PC + 2
+                self.pc = self.pc.wrapping_add(1);
+                self.push_word(self.pc);
+                self.push_status(true);
+
+                self.set_flag(StatusFlags::DISABLE_INTERRUPTS, true);
+                if self.variant == CpuVariant::Cmos65C02 {
+                    self.set_flag(StatusFlags::DECIMAL_MODE, false);
                 }
+                self.pc = self.read_word(0xFFFE);
+            }
+            BVC_BranchOnOverflowClear => self.branch(!self.get_flag(StatusFlags::OVERFLOW)),
+            BVS_BranchOnOverflowSet => self.branch(self.get_flag(StatusFlags::OVERFLOW)),
+            CLC_ClearCarryFlag => self.set_flag(StatusFlags::CARRY, false),
+            CLD_ClearDecimalMode => self.set_flag(StatusFlags::DECIMAL_MODE, false),
+            CLI_ClearInterruptDisableBit => self.set_flag(StatusFlags::DISABLE_INTERRUPTS, false),
+            CLV_ClearOverflowFlag => self.set_flag(StatusFlags::OVERFLOW, false),
+            CMP_CompareMemoryAndAcc => {
+                self.fetch();
+                self.compare(self.a, self.fetched);
+                return 1;
+            }
+            CPX_CompareMemoryAndX => {
+                self.fetch();
+                self.compare(self.x, self.fetched);
+            }
+            CPY_CompareMemoryAndY => {
+                self.fetch();
+                self.compare(self.y, self.fetched);
+            }
+            DEC_DecrementMemoryByOne => {
+                self.fetch();
+                let result = self.fetched.wrapping_sub(1);
+                self.set_zero_and_negative(result);
+                self.write_result(result);
+            }
+            DEX_DecrementXByOne => {
+                self.x = self.x.wrapping_sub(1);
+                self.set_zero_and_negative(self.x);
+            }
+            DEY_DecrementYByOne => {
+                self.y = self.y.wrapping_sub(1);
+                self.set_zero_and_negative(self.y);
+            }
+            EOR_ExclusiveORMemoryWithAcc => {
+                self.fetch();
+                self.a ^= self.fetched;
+                self.set_zero_and_negative(self.a);
+                return 1;
+            }
+            INC_IncrementMemoryByOne => {
+                self.fetch();
+                let result = self.fetched.wrapping_add(1);
+                self.set_zero_and_negative(result);
+                self.write_result(result);
+            }
+            INX_IncrementXByOne => {
+                self.x = self.x.wrapping_add(1);
+                self.set_zero_and_negative(self.x);
+            }
+            INY_IncrementYByOne => {
+                self.y = self.y.wrapping_add(1);
+                self.set_zero_and_negative(self.y);
+            }
+            JMP_JumpTo => self.pc = self.addr_abs,
+            JSR_JumpToSavingReturnAddr => {
+                // The pushed address points to the last byte of the JSR instruction
+                self.push_word(self.pc.wrapping_sub(1));
+                self.pc = self.addr_abs;
             }
-            BEQ_BranchOnResultZero => {}
-            BIT_BitTestInMemoryWithAcc => {}
-            BMI_BranchOnResultMinus => {}
-            BNE_BranchOnResultNotZero => {}
-            BPL_BranchOnResultPlus => {}
-            BRK_ForceBreak => {}
-            BVC_BranchOnOverflowClear => {}
-            BVS_BranchOnOverflowSet => {}
-            CLC_ClearCarryFlag => self.set_flag(Flag::Carry, false),
-            CLD_ClearDecimalMode => self.set_flag(Flag::DecimalMode, false),
-            CLI_ClearInterruptDisableBit => self.set_flag(Flag::DisableInterrupts, false),
-            CLV_ClearOverflowFlag => {}
-            CMP_CompareMemoryAndAcc => {}
-            CPX_CompareMemoryAndX => {}
-            CPY_CompareMemoryAndY => {}
-            DEC_DecrementMemoryByOne => {}
-            DEX_DecrementXByOne => {}
-            DEY_DecrementYByOne => {}
-            EOR_ExclusiveORMemoryWithAcc => {}
-            INC_IncrementMemoryByOne => {}
-            INX_IncrementXByOne => {}
-            INY_IncrementYByOne => {}
-            JMP_JumpTo => {}
-            JSR_JumpToSavingReturnAddr => {}
             LDA_LoadAccWithMemory => {
                 self.fetch();
                 self.a = self.fetched;
+                self.set_zero_and_negative(self.a);
+                return 1;
+            }
+            LDX_LoadXWithMemory => {
+                self.fetch();
+                self.x = self.fetched;
+                self.set_zero_and_negative(self.x);
+                return 1;
+            }
+            LDY_LoadYWithMemory => {
+                self.fetch();
+                self.y = self.fetched;
+                self.set_zero_and_negative(self.y);
+                return 1;
+            }
+            LSR_ShiftOneBitRight => {
+                self.fetch();
+                let result = self.shift_right(self.fetched);
+                self.write_result(result);
+            }
+            NOP_NoOperation => {
+                // Undocumented NOPs with an operand still read it
+                self.fetch();
+                return 1;
+            }
+            ORA_ORMemoryWithAcc => {
+                self.fetch();
+                self.a |= self.fetched;
+                self.set_zero_and_negative(self.a);
+                return 1;
+            }
+            PHA_PushAccOnStack => self.push_byte(self.a),
+            PHP_PushProcessorStatusOnStack => self.push_status(true),
+            PLA_PullAccFromStack => {
+                self.a = self.pull_byte();
+                self.set_zero_and_negative(self.a);
+            }
+            PLP_PullProcessorStatusFromStack => self.pull_status(),
+            ROL_RotateOneBitLeft => {
+                self.fetch();
+                let result = self.rotate_left(self.fetched);
+                self.write_result(result);
+            }
+            ROR_RotateOneBitRight => {
+                self.fetch();
+                let result = self.rotate_right(self.fetched);
+                self.write_result(result);
+            }
+            RTI_ReturnFromInterrupt => {
+                self.pull_status();
+                self.pc = self.pull_word();
+            }
+            RTS_ReturnFromSubroutine => {
+                self.pc = self.pull_word().wrapping_add(1);
+            }
+            SBC_SubtractMemoryFromAccWithBorrow => {
+                self.fetch();
+                self.subtract_with_borrow(self.fetched);
+                return 1;
+            }
+            SEC_SetCarryFlag => self.set_flag(StatusFlags::CARRY, true),
+            SED_SetDecimalMode => self.set_flag(StatusFlags::DECIMAL_MODE, true),
+            SEI_SetInterruptDisableStatus => self.set_flag(StatusFlags::DISABLE_INTERRUPTS, true),
+            STA_StoreAccInMemory => self.write(self.addr_abs, self.a),
+            STX_StoreXInMemory => self.write(self.addr_abs, self.x),
+            STY_StoreYInMemory => self.write(self.addr_abs, self.y),
+            TAX_TransferAccToX => {
+                self.x = self.a;
+                self.set_zero_and_negative(self.x);
+            }
+            TAY_TransferAccToY => {
+                self.y = self.a;
+                self.set_zero_and_negative(self.y);
+            }
+            TSX_TransferStackPointerToX => {
+                self.x = self.stack_ptr;
+                self.set_zero_and_negative(self.x);
+            }
+            TXA_TransferXToAcc => {
+                self.a = self.x;
+                self.set_zero_and_negative(self.a);
+            }
+            TXS_TransferXToStackRegister => self.stack_ptr = self.x,
+            TYA_TransferYToAcc => {
+                self.a = self.y;
+                self.set_zero_and_negative(self.a);
+            }
+
+            ALR_ANDThenShiftRight => {
+                self.fetch();
+                self.a = self.shift_right(self.a & self.fetched);
+            }
+            ANC_ANDThenCopyNegativeToCarry => {
+                self.fetch();
+                self.a &= self.fetched;
+                self.set_zero_and_negative(self.a);
+                self.set_flag(StatusFlags::CARRY, self.a & 0b10000000 != 0);
+            }
+            ANE_ANDXAndImmediateIntoAcc => {
+                // Unstable: the constant depends on the chip, $EE is the most common value
+                self.fetch();
+                self.a = (self.a | 0xEE) & self.x & self.fetched;
+                self.set_zero_and_negative(self.a);
+            }
+            ARR_ANDThenRotateRight => {
+                self.fetch();
+                self.a = self.rotate_right(self.a & self.fetched);
+                self.set_flag(StatusFlags::CARRY, self.a & 0b01000000 != 0);
+                self.set_flag(
+                    StatusFlags::OVERFLOW,
+                    ((self.a >> 6) ^ (self.a >> 5)) & 0b00000001 != 0,
+                );
+            }
+            DCP_DecrementThenCompareWithAcc => {
+                self.fetch();
+                let result = self.fetched.wrapping_sub(1);
+                self.write(self.addr_abs, result);
+                self.compare(self.a, result);
+            }
+            ISC_IncrementThenSubtractFromAcc => {
+                self.fetch();
+                let result = self.fetched.wrapping_add(1);
+                self.write(self.addr_abs, result);
+                self.subtract_with_borrow(result);
+            }
+            JAM_HaltProcessor => {
+                // The CPU locks up until it is reset, so keep fetching the same opcode
+                self.pc = self.pc.wrapping_sub(1);
+            }
+            LAS_LoadAccXAndStackWithMemoryANDStack => {
+                self.fetch();
+                self.stack_ptr &= self.fetched;
+                self.a = self.stack_ptr;
+                self.x = self.stack_ptr;
+                self.set_zero_and_negative(self.a);
+                return 1;
+            }
+            LAX_LoadAccAndXWithMemory => {
+                self.fetch();
+                self.a = self.fetched;
+                self.x = self.fetched;
+                self.set_zero_and_negative(self.a);
+                return 1;
+            }
+            LXA_LoadAccAndXWithImmediate => {
+                // Unstable in the same way as ANE
+                self.fetch();
+                self.a = (self.a | 0xEE) & self.fetched;
+                self.x = self.a;
+                self.set_zero_and_negative(self.a);
+            }
+            RLA_RotateLeftThenANDWithAcc => {
+                self.fetch();
+                let result = self.rotate_left(self.fetched);
+                self.write(self.addr_abs, result);
+                self.a &= result;
+                self.set_zero_and_negative(self.a);
+            }
+            RRA_RotateRightThenAddWithCarry => {
+                self.fetch();
+                let result = self.rotate_right(self.fetched);
+                self.write(self.addr_abs, result);
+                self.add_with_carry(result);
+            }
+            SAX_StoreAccANDX => self.write(self.addr_abs, self.a & self.x),
+            SBX_SubtractFromAccANDXIntoX => {
+                self.fetch();
+                let value = self.a & self.x;
+                self.x = value.wrapping_sub(self.fetched);
+                self.set_flag(StatusFlags::CARRY, value >= self.fetched);
+                self.set_zero_and_negative(self.x);
+            }
+            SHA_StoreAccANDXANDHighByte => self.store_and_high_byte(self.a & self.x, self.y),
+            SHX_StoreXANDHighByte => self.store_and_high_byte(self.x, self.y),
+            SHY_StoreYANDHighByte => self.store_and_high_byte(self.y, self.x),
+            SLO_ShiftLeftThenORWithAcc => {
+                self.fetch();
+                let result = self.shift_left(self.fetched);
+                self.write(self.addr_abs, result);
+                self.a |= result;
+                self.set_zero_and_negative(self.a);
+            }
+            SRE_ShiftRightThenEORWithAcc => {
+                self.fetch();
+                let result = self.shift_right(self.fetched);
+                self.write(self.addr_abs, result);
+                self.a ^= result;
+                self.set_zero_and_negative(self.a);
+            }
+            TAS_TransferAccANDXToStackThenStore => {
+                self.stack_ptr = self.a & self.x;
+                self.store_and_high_byte(self.stack_ptr, self.y);
             }
-            LDX_LoadXWithMemory => {}
-            LDY_LoadYWithMemory => {}
-            LSR_ShiftOneBitRight => {}
-            NOP_NoOperation => {}
-            ORA_ORMemoryWithAcc => {}
-            PHA_PushAccOnStack => {}
-            PHP_PushProcessorStatusOnStack => {}
-            PLA_PullAccFromStack => {}
-            PLP_PullProcessorStatusFromStack => {}
-            ROL_RotateOneBitLeft => {}
-            ROR_RotateOneBitRight => {}
-            RTI_ReturnFromInterrupt => {}
-            RTS_ReturnFromSubroutine => {}
-            SBC_SubtractMemoryFromAccWithBorrow => {}
-            SEC_SetCarryFlag => self.set_flag(Flag::Carry, true),
-            SED_SetDecimalMode => self.set_flag(Flag::DecimalMode, true),
-            SEI_SetInterruptDisableStatus => self.set_flag(Flag::DisableInterrupts, true),
-            STA_StoreAccInMemory => {
-                self.fetch();
-                self.write(self.addr_abs, self.a);
-            }
-            STX_StoreXInMemory => {}
-            STY_StoreYInMemory => {}
-            TAX_TransferAccToX => {}
-            TAY_TransferAccToY => {}
-            TSX_TransferStackPointerToX => {}
-            TXA_TransferXToAcc => {}
-            TXS_TransferXToStackRegister => {}
-            TYA_TransferYToAcc => {}
-            InvalidInstruction => println!("Invalid instruction!"),
+
+            BRA_BranchAlways => self.branch(true),
+            PHX_PushXOnStack => self.push_byte(self.x),
+            PHY_PushYOnStack => self.push_byte(self.y),
+            PLX_PullXFromStack => {
+                self.x = self.pull_byte();
+                self.set_zero_and_negative(self.x);
+            }
+            PLY_PullYFromStack => {
+                self.y = self.pull_byte();
+                self.set_zero_and_negative(self.y);
+            }
+            STZ_StoreZeroInMemory => self.write(self.addr_abs, 0),
+            TRB_TestAndResetMemoryBits => {
+                self.fetch();
+                self.set_flag(StatusFlags::ZERO, self.a & self.fetched == 0);
+                self.write(self.addr_abs, self.fetched & !self.a);
+            }
+            TSB_TestAndSetMemoryBits => {
+                self.fetch();
+                self.set_flag(StatusFlags::ZERO, self.a & self.fetched == 0);
+                self.write(self.addr_abs, self.fetched | self.a);
+            }
+        }
+        0
+    }
+
+    fn set_zero_and_negative(&mut self, value: u8) {
+        self.set_flag(StatusFlags::ZERO, value == 0);
+        self.set_flag(StatusFlags::NEGATIVE, value & 0b10000000 != 0);
+    }
+
+    fn decimal_mode(&self) -> bool {
+        self.variant.has_decimal_mode() && self.get_flag(StatusFlags::DECIMAL_MODE)
+    }
+
+    fn add_with_carry(&mut self, value: u8) {
+        let carry = self.get_flag(StatusFlags::CARRY) as u16;
+        let result = self.a as u16 + value as u16 + carry;
+
+        if !self.decimal_mode() {
+            self.set_flag(StatusFlags::CARRY, result > 0xFF);
+            // Overflow happens when both operands share a sign that differs from the result's
+            self.set_flag(
+                StatusFlags::OVERFLOW,
+                (!(self.a ^ value) & (self.a ^ result as u8)) & 0b10000000 != 0,
+            );
+
+            self.a = result as u8;
+            self.set_zero_and_negative(self.a);
+            return;
+        }
+
+        // Add one BCD digit at a time, adding 6 to skip the invalid values $A-$F
+        let mut low_digit = (self.a & 0x0F) as u16 + (value & 0x0F) as u16 + carry;
+        if low_digit >= 0x0A {
+            low_digit = ((low_digit + 0x06) & 0x0F) + 0x10;
+        }
+        let mut sum = (self.a & 0xF0) as u16 + (value & 0xF0) as u16 + low_digit;
+
+        // Negative and Overflow come from the sum before the high digit is adjusted
+        self.set_flag(
+            StatusFlags::OVERFLOW,
+            (!(self.a ^ value) & (self.a ^ sum as u8)) & 0b10000000 != 0,
+        );
+        self.set_flag(StatusFlags::NEGATIVE, sum & 0b10000000 != 0);
+
+        if sum >= 0xA0 {
+            sum += 0x60;
+        }
+        self.set_flag(StatusFlags::CARRY, sum > 0xFF);
+        self.a = sum as u8;
+
+        if self.variant == CpuVariant::Cmos65C02 {
+            // The 65C02 spends an extra cycle to produce valid N and Z flags
+            self.set_zero_and_negative(self.a);
+            self.cycles += 1;
+        } else {
+            // NMOS sets Zero from the binary result
+            self.set_flag(StatusFlags::ZERO, result & 0xFF == 0);
+        }
+    }
+
+    fn subtract_with_borrow(&mut self, value: u8) {
+        if !self.decimal_mode() {
+            // A - M - (1 - C) is the same as A + !M + C
+            self.add_with_carry(!value);
+            return;
+        }
+
+        let borrow = 1 - self.get_flag(StatusFlags::CARRY) as i16;
+        let result = self.a as i16 - value as i16 - borrow;
+        let low_digit = (self.a & 0x0F) as i16 - (value & 0x0F) as i16 - borrow;
+
+        // Carry and Overflow always follow the binary subtraction
+        self.set_flag(StatusFlags::CARRY, result >= 0);
+        self.set_flag(
+            StatusFlags::OVERFLOW,
+            ((self.a ^ value) & (self.a ^ result as u8)) & 0b10000000 != 0,
+        );
+
+        if self.variant == CpuVariant::Cmos65C02 {
+            let mut difference = result;
+            if difference < 0 {
+                difference -= 0x60;
+            }
+            if low_digit < 0 {
+                difference -= 0x06;
+            }
+
+            self.a = difference as u8;
+            self.set_zero_and_negative(self.a);
+            self.cycles += 1;
+        } else {
+            let mut low_digit = low_digit;
+            if low_digit < 0 {
+                low_digit = ((low_digit - 0x06) & 0x0F) - 0x10;
+            }
+            let mut difference = (self.a & 0xF0) as i16 - (value & 0xF0) as i16 + low_digit;
+            if difference < 0 {
+                difference -= 0x60;
+            }
+
+            // NMOS sets Negative and Zero from the binary result
+            self.set_zero_and_negative(result as u8);
+            self.a = difference as u8;
+        }
+    }
+
+    fn compare(&mut self, register: u8, value: u8) {
+        self.set_flag(StatusFlags::CARRY, register >= value);
+        self.set_zero_and_negative(register.wrapping_sub(value));
+    }
+
+    fn shift_left(&mut self, value: u8) -> u8 {
+        let result = value << 1;
+        self.set_flag(StatusFlags::CARRY, value & 0b10000000 != 0);
+        self.set_zero_and_negative(result);
+        result
+    }
+
+    fn shift_right(&mut self, value: u8) -> u8 {
+        let result = value >> 1;
+        self.set_flag(StatusFlags::CARRY, value & 0b00000001 != 0);
+        self.set_zero_and_negative(result);
+        result
+    }
+
+    fn rotate_left(&mut self, value: u8) -> u8 {
+        let result = (value << 1) | self.get_flag(StatusFlags::CARRY) as u8;
+        self.set_flag(StatusFlags::CARRY, value & 0b10000000 != 0);
+        self.set_zero_and_negative(result);
+        result
+    }
+
+    fn rotate_right(&mut self, value: u8) -> u8 {
+        let result = (value >> 1) | ((self.get_flag(StatusFlags::CARRY) as u8) << 7);
+        self.set_flag(StatusFlags::CARRY, value & 0b00000001 != 0);
+        self.set_zero_and_negative(result);
+        result
+    }
+
+    // SHA, SHX, SHY and TAS AND the value with the high byte of the base address plus one.
+    // When indexing crosses a page, that value also replaces the high byte of the target.
+    fn store_and_high_byte(&mut self, value: u8, index: u8) {
+        let base = self.addr_abs.wrapping_sub(index as u16);
+        let result = value & ((base >> 8) as u8).wrapping_add(1);
+
+        let mut addr = self.addr_abs;
+        if base & 0xFF00 != addr & 0xFF00 {
+            addr = ((result as u16) << 8) | (addr & 0x00FF);
+        }
+        self.write(addr, result);
+    }
+
+    fn branch(&mut self, condition: bool) {
+        if !condition {
+            return;
+        }
+
+        self.cycles += 1;
+        self.addr_abs = self.pc.wrapping_add(self.addr_rel);
+
+        if self.addr_abs & 0xFF00 != self.pc & 0xFF00 {
+            self.cycles += 1;
+        }
+        self.pc = self.addr_abs
+    }
+
+    // Shifts, rotates and the 65C02 INC/DEC operate either on the accumulator or on memory
+    fn write_result(&mut self, value: u8) {
+        match self.addr_mode() {
+            AddrMode::Implied => self.a = value,
+            _ => self.write(self.addr_abs, value),
         }
-        return 0
     }
 }
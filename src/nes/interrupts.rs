@@ -0,0 +1,108 @@
+use super::{
+    mos_6502::{CpuVariant, ExecutionMode, Mos6502},
+    status_flags::StatusFlags,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interrupt {
+    Reset,
+    Irq,
+    Nmi,
+}
+
+impl Interrupt {
+    pub fn vector(self) -> u16 {
+        match self {
+            Interrupt::Nmi => 0xFFFA,
+            Interrupt::Reset => 0xFFFC,
+            Interrupt::Irq => 0xFFFE,
+        }
+    }
+}
+
+impl Mos6502 {
+    pub fn reset(&mut self) {
+        self.fetched = 0;
+        self.addr_abs = 0;
+        self.addr_rel = 0;
+
+        if self.execution_mode == ExecutionMode::CycleAccurate {
+            // Reset aborts whatever instruction is running
+            self.step = 0;
+            self.request_interrupt(Interrupt::Reset);
+            return;
+        }
+
+        self.pc = self.read_word(Interrupt::Reset.vector());
+
+        // Reset goes through the interrupt sequence with writes suppressed,
+        // so the stack pointer still moves down by three: $00 at power-on becomes $FD
+        self.stack_ptr = self.stack_ptr.wrapping_sub(3);
+        self.set_flag(StatusFlags::DISABLE_INTERRUPTS, true);
+
+        self.cycles = 7;
+    }
+
+    pub fn irq(&mut self) {
+        if self.get_flag(StatusFlags::DISABLE_INTERRUPTS) {
+            return;
+        }
+
+        if self.execution_mode == ExecutionMode::CycleAccurate {
+            self.request_interrupt(Interrupt::Irq);
+            return;
+        }
+
+        self.interrupt(Interrupt::Irq.vector());
+        self.cycles = 7;
+    }
+
+    pub fn nmi(&mut self) {
+        if self.execution_mode == ExecutionMode::CycleAccurate {
+            self.request_interrupt(Interrupt::Nmi);
+            return;
+        }
+
+        self.interrupt(Interrupt::Nmi.vector());
+        self.cycles = 7;
+    }
+
+    fn interrupt(&mut self, vector: u16) {
+        self.push_word(self.pc);
+        self.push_status(false);
+
+        self.set_flag(StatusFlags::DISABLE_INTERRUPTS, true);
+        if self.variant == CpuVariant::Cmos65C02 {
+            self.set_flag(StatusFlags::DECIMAL_MODE, false);
+        }
+        self.pc = self.read_word(vector);
+    }
+
+    // NMI is edge-triggered, so the line is sampled every cycle and a rising edge is
+    // remembered until the next instruction boundary
+    pub(super) fn sample_nmi_line(&mut self) {
+        let nmi_line = self.bus.borrow().nmi();
+        self.nmi_edge |= nmi_line && !self.nmi_line;
+        self.nmi_line = nmi_line;
+    }
+
+    // Polled before each instruction. The IRQ line is level-triggered: it keeps
+    // interrupting for as long as a device holds it and interrupts are enabled.
+    pub(super) fn poll_interrupt_lines(&mut self) {
+        if self.nmi_edge {
+            self.nmi_edge = false;
+            self.nmi();
+        } else if self.bus.borrow().irq() {
+            self.irq();
+        }
+    }
+
+    // In cycle-accurate mode the interrupt sequence is run by the clock, one bus access
+    // per cycle, as soon as the current instruction finishes
+    fn request_interrupt(&mut self, interrupt: Interrupt) {
+        if self.pending_interrupt != Some(Interrupt::Reset) {
+            self.pending_interrupt = Some(interrupt);
+        }
+        self.cycles = 1;
+    }
+}
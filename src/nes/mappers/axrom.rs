@@ -0,0 +1,80 @@
+use super::{bank_offset, Mapper, Memory};
+use crate::nes::cartridge::Mirroring;
+
+const PRG_BANK_SIZE: usize = 32 * 1024;
+
+// Mapper 7: switchable 32 KiB PRG bank and single-screen mirroring
+pub struct Axrom {
+    memory: Memory,
+    prg_bank: usize,
+    mirroring: Mirroring,
+}
+
+impl Axrom {
+    pub fn new(memory: Memory) -> Self {
+        Self {
+            memory,
+            prg_bank: 0,
+            mirroring: Mirroring::SingleScreenLower,
+        }
+    }
+}
+
+impl Mapper for Axrom {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        bank_offset(self.prg_bank, PRG_BANK_SIZE, addr)
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn write_register(&mut self, _addr: u16, value: u8) {
+        self.prg_bank = (value & 0x07) as usize;
+        self.mirroring = if value & 0x10 != 0 {
+            Mirroring::SingleScreenUpper
+        } else {
+            Mirroring::SingleScreenLower
+        };
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::mappers::{numbered_board_with, CIRAM};
+
+    #[test]
+    fn switches_32k_and_picks_the_single_screen() {
+        let mut axrom = numbered_board_with(7, 32, 0);
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+
+        axrom.write_register(0x8000, 0x13);
+        assert_eq!(axrom.cpu_peek(0x8000), Some(12));
+        assert_eq!(axrom.cpu_peek(0xE000), Some(15));
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenUpper);
+
+        axrom.write_register(0x8000, 0x02);
+        assert_eq!(axrom.cpu_peek(0x8000), Some(8));
+        assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+    }
+
+    #[test]
+    fn chr_ram_is_writable() {
+        let mut axrom = numbered_board_with(7, 32, 0);
+        axrom.ppu_write(0x1234, 0x42, &mut [0; 0x800]);
+        assert_eq!(axrom.ppu_peek(0x1234, &CIRAM), 0x42);
+    }
+}
@@ -0,0 +1,55 @@
+use super::{bank_offset, Mapper, Memory};
+
+const CHR_BANK_SIZE: usize = 8 * 1024;
+
+// Mapper 3: fixed PRG ROM, switchable 8 KiB CHR bank
+pub struct Cnrom {
+    memory: Memory,
+    chr_bank: usize,
+}
+
+impl Cnrom {
+    pub fn new(memory: Memory) -> Self {
+        Self {
+            memory,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Cnrom {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        addr as usize - 0x8000
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        bank_offset(self.chr_bank, CHR_BANK_SIZE, addr)
+    }
+
+    fn write_register(&mut self, _addr: u16, value: u8) {
+        self.chr_bank = value as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nes::mappers::{numbered_board_with, CIRAM};
+
+    #[test]
+    fn switches_8k_of_chr_and_keeps_prg_fixed() {
+        let mut cnrom = numbered_board_with(3, 4, 32);
+        cnrom.write_register(0x8000, 3);
+        assert_eq!(cnrom.ppu_peek(0x0000, &CIRAM), 24);
+        assert_eq!(cnrom.ppu_peek(0x1C00, &CIRAM), 31);
+        assert_eq!(cnrom.cpu_peek(0x8000), Some(0));
+        assert_eq!(cnrom.cpu_peek(0xE000), Some(3));
+    }
+}
@@ -0,0 +1,234 @@
+use super::{bank_offset, Mapper, Memory};
+use crate::nes::cartridge::Mirroring;
+
+const PRG_BANK_SIZE: usize = 8 * 1024;
+const CHR_BANK_SIZE: usize = 1024;
+
+// Mapper 69: Sunsoft FME-7. Registers are written through a command port at $8000 and a
+// parameter port at $A000. The 5B sound variant's audio is not emulated.
+pub struct Fme7 {
+    memory: Memory,
+    command: u8,
+    chr_banks: [u8; 8],
+    // $6000, $8000, $A000 and $C000
+    prg_banks: [u8; 4],
+    mirroring: Mirroring,
+    irq_enabled: bool,
+    irq_counter_enabled: bool,
+    irq_counter: u16,
+    irq_pending: bool,
+}
+
+impl Fme7 {
+    pub fn new(memory: Memory) -> Self {
+        Self {
+            mirroring: memory.mirroring,
+            memory,
+            command: 0,
+            chr_banks: [0; 8],
+            prg_banks: [0; 4],
+            irq_enabled: false,
+            irq_counter_enabled: false,
+            irq_counter: 0,
+            irq_pending: false,
+        }
+    }
+
+    // The $6000 window shows either PRG RAM or a PRG ROM bank
+    fn ram_selected(&self) -> bool {
+        self.prg_banks[0] & 0x40 != 0
+    }
+}
+
+impl Mapper for Fme7 {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x6000..=0xDFFF => {
+                (self.prg_banks[(addr as usize - 0x6000) / PRG_BANK_SIZE] & 0x3F) as usize
+            }
+            _ => (self.memory.prg_rom.len() / PRG_BANK_SIZE).saturating_sub(1),
+        };
+        bank_offset(bank, PRG_BANK_SIZE, addr)
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[(addr as usize >> 10) & 0x07];
+        bank_offset(bank as usize, CHR_BANK_SIZE, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        match addr & 0xE000 {
+            0x8000 => self.command = value & 0x0F,
+            0xA000 => match self.command {
+                0x0..=0x7 => self.chr_banks[self.command as usize] = value,
+                0x8..=0xB => self.prg_banks[self.command as usize - 0x8] = value,
+                0xC => {
+                    self.mirroring = match value & 0x03 {
+                        0 => Mirroring::Vertical,
+                        1 => Mirroring::Horizontal,
+                        2 => Mirroring::SingleScreenLower,
+                        _ => Mirroring::SingleScreenUpper,
+                    }
+                }
+                0xD => {
+                    self.irq_enabled = value & 0x01 != 0;
+                    self.irq_counter_enabled = value & 0x80 != 0;
+                    self.irq_pending = false;
+                }
+                0xE => self.irq_counter = (self.irq_counter & 0xFF00) | value as u16,
+                _ => self.irq_counter = (self.irq_counter & 0x00FF) | ((value as u16) << 8),
+            },
+            _ => {}
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.ram_selected() && self.prg_banks[0] & 0x80 != 0 && !self.memory.prg_ram.is_empty()
+    }
+
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        let memory = &self.memory;
+        match addr {
+            0x6000..=0x7FFF if self.ram_selected() => self
+                .prg_ram_enabled()
+                .then(|| memory.prg_ram[(addr as usize - 0x6000) % memory.prg_ram.len()]),
+            0x6000..=0xFFFF => {
+                Some(memory.prg_rom[self.prg_rom_offset(addr) % memory.prg_rom.len()])
+            }
+            _ => None,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn tick(&mut self) {
+        if !self.irq_counter_enabled {
+            return;
+        }
+
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+        if self.irq_counter == 0xFFFF && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::mappers::{numbered_board, CIRAM};
+
+    fn command(fme7: &mut dyn Mapper, command: u8, value: u8) {
+        fme7.write_register(0x8000, command);
+        fme7.write_register(0xA000, value);
+    }
+
+    #[test]
+    fn prg_has_four_8k_windows_and_a_fixed_last_bank() {
+        let mut fme7 = numbered_board(69);
+        for (register, bank) in [(0x8, 3), (0x9, 5), (0xA, 6), (0xB, 7)] {
+            command(fme7.as_mut(), register, bank);
+        }
+        assert_eq!(fme7.cpu_peek(0x6000), Some(3));
+        assert_eq!(fme7.cpu_peek(0x8000), Some(5));
+        assert_eq!(fme7.cpu_peek(0xA000), Some(6));
+        assert_eq!(fme7.cpu_peek(0xC000), Some(7));
+        assert_eq!(fme7.cpu_peek(0xE000), Some(31));
+    }
+
+    #[test]
+    fn prg_ram_replaces_the_6000_bank() {
+        let mut fme7 = numbered_board(69);
+        command(fme7.as_mut(), 0x8, 0xC0);
+        fme7.cpu_write(0x6000, 0x42);
+        assert_eq!(fme7.cpu_peek(0x6000), Some(0x42));
+
+        // Selected but disabled RAM leaves the bus open
+        command(fme7.as_mut(), 0x8, 0x40);
+        assert_eq!(fme7.cpu_peek(0x6000), None);
+    }
+
+    #[test]
+    fn chr_has_eight_1k_windows() {
+        let mut fme7 = numbered_board(69);
+        for register in 0..8 {
+            command(fme7.as_mut(), register, 0x10 + register);
+        }
+        for window in 0..8 {
+            assert_eq!(fme7.ppu_peek(window * 0x400, &CIRAM), 0x10 + window as u8);
+        }
+    }
+
+    #[test]
+    fn mirroring_is_selected_by_command_c() {
+        let mut fme7 = numbered_board(69);
+        let modes = [
+            Mirroring::Vertical,
+            Mirroring::Horizontal,
+            Mirroring::SingleScreenLower,
+            Mirroring::SingleScreenUpper,
+        ];
+        for (value, mirroring) in modes.into_iter().enumerate() {
+            command(fme7.as_mut(), 0xC, value as u8);
+            assert_eq!(fme7.mirroring(), mirroring);
+        }
+    }
+
+    #[test]
+    fn irq_fires_when_the_counter_underflows() {
+        let mut fme7 = numbered_board(69);
+        command(fme7.as_mut(), 0xE, 0x02);
+        command(fme7.as_mut(), 0xF, 0x00);
+        command(fme7.as_mut(), 0xD, 0x81);
+
+        // 2, 1, 0, then $FFFF
+        for _ in 0..2 {
+            fme7.tick();
+        }
+        assert!(!fme7.irq());
+        fme7.tick();
+        assert!(fme7.irq());
+
+        // Writing the control register acknowledges
+        command(fme7.as_mut(), 0xD, 0x81);
+        assert!(!fme7.irq());
+    }
+
+    #[test]
+    fn irq_and_counter_are_enabled_separately() {
+        let mut fme7 = numbered_board(69);
+
+        // A stopped counter stays at 0, so enabling it underflows on the first tick
+        command(fme7.as_mut(), 0xD, 0x01);
+        for _ in 0..4 {
+            fme7.tick();
+        }
+        assert!(!fme7.irq());
+        command(fme7.as_mut(), 0xD, 0x81);
+        fme7.tick();
+        assert!(fme7.irq());
+
+        // The counter still runs and wraps with the IRQ disabled
+        command(fme7.as_mut(), 0xE, 0x01);
+        command(fme7.as_mut(), 0xF, 0x00);
+        command(fme7.as_mut(), 0xD, 0x80);
+        for _ in 0..4 {
+            fme7.tick();
+        }
+        assert!(!fme7.irq());
+    }
+}
@@ -0,0 +1,279 @@
+use super::{bank_offset, Mapper, Memory};
+use crate::nes::cartridge::Mirroring;
+
+const PRG_BANK_SIZE: usize = 16 * 1024;
+const CHR_BANK_SIZE: usize = 4 * 1024;
+// Boards with 512 KiB of PRG ROM (SUROM) use a CHR register bit to pick the 256 KiB half
+const PRG_OUTER_BANK_SIZE: usize = 256 * 1024;
+
+// Mapper 1: registers are loaded one bit at a time through a 5-bit shift register
+pub struct Mmc1 {
+    memory: Memory,
+    shift: u8,
+    shift_count: u8,
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+    // The serial port ignores writes on consecutive cycles, e.g. the double write of
+    // read-modify-write instructions
+    cycle: u64,
+    last_write_cycle: u64,
+}
+
+impl Mmc1 {
+    pub fn new(memory: Memory) -> Self {
+        Self {
+            memory,
+            shift: 0,
+            shift_count: 0,
+            // Powers up with the last PRG bank fixed at $C000
+            control: 0x0C,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+            cycle: 0,
+            last_write_cycle: u64::MAX,
+        }
+    }
+
+    fn load_register(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.control = value,
+            0xA000..=0xBFFF => self.chr_bank_0 = value,
+            0xC000..=0xDFFF => self.chr_bank_1 = value,
+            _ => self.prg_bank = value,
+        }
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        let outer = if self.memory.prg_rom.len() > PRG_OUTER_BANK_SIZE {
+            ((self.chr_bank_0 & 0x10) >> 4) as usize * (PRG_OUTER_BANK_SIZE / PRG_BANK_SIZE)
+        } else {
+            0
+        };
+        let last =
+            (self.memory.prg_rom.len().min(PRG_OUTER_BANK_SIZE) / PRG_BANK_SIZE).saturating_sub(1);
+        let bank = (self.prg_bank & 0x0F) as usize;
+
+        let bank = match ((self.control >> 2) & 0x03, addr) {
+            // 32 KiB mode ignores the low bit of the bank number
+            (0 | 1, 0x8000..=0xBFFF) => bank & !1,
+            (0 | 1, _) => bank | 1,
+            (2, 0x8000..=0xBFFF) => 0,
+            (2, _) => bank,
+            (_, 0x8000..=0xBFFF) => bank,
+            (_, _) => last,
+        };
+        bank_offset(outer + bank, PRG_BANK_SIZE, addr)
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank = match (self.control & 0x10 != 0, addr) {
+            // 8 KiB mode ignores the low bit of the bank number
+            (false, 0x0000..=0x0FFF) => self.chr_bank_0 & !1,
+            (false, _) => self.chr_bank_0 | 1,
+            (true, 0x0000..=0x0FFF) => self.chr_bank_0,
+            (true, _) => self.chr_bank_1,
+        };
+        bank_offset(bank as usize, CHR_BANK_SIZE, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        let consecutive = self.cycle == self.last_write_cycle.wrapping_add(1);
+        self.last_write_cycle = self.cycle;
+        if consecutive {
+            return;
+        }
+
+        if value & 0x80 != 0 {
+            self.shift = 0;
+            self.shift_count = 0;
+            self.control |= 0x0C;
+            return;
+        }
+
+        self.shift |= (value & 0x01) << self.shift_count;
+        self.shift_count += 1;
+        if self.shift_count == 5 {
+            self.load_register(addr, self.shift);
+            self.shift = 0;
+            self.shift_count = 0;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0x10 == 0 && !self.memory.prg_ram.is_empty()
+    }
+
+    fn tick(&mut self) {
+        self.cycle += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::mappers::{numbered_board, numbered_board_with, CIRAM};
+
+    // Writes the five bits of `value` through the serial port, a few cycles apart
+    fn write_serial(mmc1: &mut dyn Mapper, addr: u16, value: u8) {
+        for bit in 0..5 {
+            mmc1.tick();
+            mmc1.tick();
+            mmc1.write_register(addr, (value >> bit) & 0x01);
+        }
+    }
+
+    fn prg_banks(mmc1: &dyn Mapper) -> [Option<u8>; 2] {
+        [0x8000, 0xC000].map(|addr| mmc1.cpu_peek(addr))
+    }
+
+    #[test]
+    fn shift_register_loads_on_the_fifth_write() {
+        let mut mmc1 = numbered_board(1);
+        for bit in 0..4 {
+            mmc1.tick();
+            mmc1.tick();
+            mmc1.write_register(0xE000, (0x05 >> bit) & 0x01);
+            assert_eq!(mmc1.cpu_peek(0x8000), Some(0));
+        }
+        mmc1.tick();
+        mmc1.tick();
+        mmc1.write_register(0xE000, 0x00);
+
+        // 16 KiB bank 5 is 8 KiB bank 10
+        assert_eq!(prg_banks(mmc1.as_ref()), [Some(10), Some(30)]);
+    }
+
+    #[test]
+    fn bit_7_resets_the_shift_register_and_fixes_the_last_bank() {
+        let mut mmc1 = numbered_board(1);
+        write_serial(mmc1.as_mut(), 0x8000, 0x08);
+        write_serial(mmc1.as_mut(), 0xE000, 0x05);
+        assert_eq!(prg_banks(mmc1.as_ref()), [Some(0), Some(10)]);
+
+        // Two bits in, then a reset: the next five bits load a whole new value
+        mmc1.tick();
+        mmc1.tick();
+        mmc1.write_register(0xE000, 0x01);
+        mmc1.tick();
+        mmc1.tick();
+        mmc1.write_register(0xE000, 0x01);
+        mmc1.tick();
+        mmc1.tick();
+        mmc1.write_register(0x8000, 0x80);
+        assert_eq!(prg_banks(mmc1.as_ref()), [Some(10), Some(30)]);
+
+        write_serial(mmc1.as_mut(), 0xE000, 0x02);
+        assert_eq!(prg_banks(mmc1.as_ref()), [Some(4), Some(30)]);
+    }
+
+    #[test]
+    fn writes_on_consecutive_cycles_are_ignored() {
+        let mut mmc1 = numbered_board(1);
+        mmc1.tick();
+        mmc1.tick();
+
+        // Read-modify-write instructions write twice on back-to-back cycles, only the
+        // first write reaches the shift register
+        for _ in 0..5 {
+            mmc1.write_register(0xE000, 0x01);
+            mmc1.tick();
+            mmc1.write_register(0xE000, 0x00);
+            mmc1.tick();
+            mmc1.tick();
+        }
+        assert_eq!(prg_banks(mmc1.as_ref()), [Some(30), Some(30)]);
+    }
+
+    #[test]
+    fn prg_modes() {
+        let mut mmc1 = numbered_board(1);
+        write_serial(mmc1.as_mut(), 0xE000, 0x05);
+
+        // 32 KiB mode ignores the low bit of the bank number
+        write_serial(mmc1.as_mut(), 0x8000, 0x00);
+        assert_eq!(prg_banks(mmc1.as_ref()), [Some(8), Some(10)]);
+        write_serial(mmc1.as_mut(), 0x8000, 0x04);
+        assert_eq!(prg_banks(mmc1.as_ref()), [Some(8), Some(10)]);
+
+        // First bank fixed at $8000
+        write_serial(mmc1.as_mut(), 0x8000, 0x08);
+        assert_eq!(prg_banks(mmc1.as_ref()), [Some(0), Some(10)]);
+
+        // Last bank fixed at $C000
+        write_serial(mmc1.as_mut(), 0x8000, 0x0C);
+        assert_eq!(prg_banks(mmc1.as_ref()), [Some(10), Some(30)]);
+    }
+
+    #[test]
+    fn chr_modes() {
+        let mut mmc1 = numbered_board(1);
+        write_serial(mmc1.as_mut(), 0xA000, 0x03);
+        write_serial(mmc1.as_mut(), 0xC000, 0x07);
+
+        // 8 KiB mode ignores the low bit and the second register
+        write_serial(mmc1.as_mut(), 0x8000, 0x0C);
+        assert_eq!(mmc1.ppu_peek(0x0000, &CIRAM), 8);
+        assert_eq!(mmc1.ppu_peek(0x1000, &CIRAM), 12);
+
+        write_serial(mmc1.as_mut(), 0x8000, 0x1C);
+        assert_eq!(mmc1.ppu_peek(0x0000, &CIRAM), 12);
+        assert_eq!(mmc1.ppu_peek(0x1000, &CIRAM), 28);
+    }
+
+    #[test]
+    fn mirroring_is_selected_by_the_control_register() {
+        let mut mmc1 = numbered_board(1);
+        let modes = [
+            Mirroring::SingleScreenLower,
+            Mirroring::SingleScreenUpper,
+            Mirroring::Vertical,
+            Mirroring::Horizontal,
+        ];
+        for (value, mirroring) in modes.into_iter().enumerate() {
+            write_serial(mmc1.as_mut(), 0x8000, 0x0C | value as u8);
+            assert_eq!(mmc1.mirroring(), mirroring);
+        }
+    }
+
+    #[test]
+    fn surom_selects_the_256k_half_with_a_chr_register_bit() {
+        let mut mmc1 = numbered_board_with(1, 64, 0);
+        write_serial(mmc1.as_mut(), 0xE000, 0x02);
+        assert_eq!(prg_banks(mmc1.as_ref()), [Some(4), Some(30)]);
+
+        write_serial(mmc1.as_mut(), 0xA000, 0x10);
+        assert_eq!(prg_banks(mmc1.as_ref()), [Some(36), Some(62)]);
+    }
+
+    #[test]
+    fn prg_bank_bit_4_disables_prg_ram() {
+        let mut mmc1 = numbered_board(1);
+        mmc1.cpu_write(0x6000, 0x42);
+        assert_eq!(mmc1.cpu_peek(0x6000), Some(0x42));
+
+        write_serial(mmc1.as_mut(), 0xE000, 0x10);
+        assert_eq!(mmc1.cpu_peek(0x6000), None);
+    }
+}
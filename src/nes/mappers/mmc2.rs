@@ -0,0 +1,182 @@
+use super::{bank_offset, Mapper, Memory};
+use crate::nes::cartridge::Mirroring;
+
+const CHR_BANK_SIZE: usize = 4 * 1024;
+
+// Mappers 9 (MMC2) and 10 (MMC4): each 4 KiB CHR window has two banks, picked by a latch
+// that flips when the PPU fetches tile $FD or $FE from it
+pub struct Mmc2 {
+    memory: Memory,
+    // MMC4 has a 16 KiB PRG window instead of an 8 KiB one, and wider latch triggers
+    mmc4: bool,
+    prg_bank: usize,
+    // [window][latch], latch 0 is $FD and 1 is $FE
+    chr_banks: [[u8; 2]; 2],
+    latches: [usize; 2],
+    mirroring: Mirroring,
+}
+
+impl Mmc2 {
+    pub fn new(memory: Memory, mmc4: bool) -> Self {
+        Self {
+            mirroring: memory.mirroring,
+            memory,
+            mmc4,
+            prg_bank: 0,
+            chr_banks: [[0; 2]; 2],
+            latches: [0; 2],
+        }
+    }
+
+    fn prg_bank_size(&self) -> usize {
+        if self.mmc4 {
+            16 * 1024
+        } else {
+            8 * 1024
+        }
+    }
+}
+
+impl Mapper for Mmc2 {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        // Everything after the switchable window is fixed to the last banks
+        let size = self.prg_bank_size();
+        let window = (addr as usize - 0x8000) / size;
+        let bank = match window {
+            0 => self.prg_bank,
+            _ => (self.memory.prg_rom.len() / size).saturating_sub(0x8000 / size - window),
+        };
+        bank_offset(bank, size, addr)
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let window = (addr as usize >> 12) & 0x01;
+        let bank = self.chr_banks[window][self.latches[window]];
+        bank_offset(bank as usize, CHR_BANK_SIZE, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        match addr & 0xF000 {
+            0xA000 => self.prg_bank = (value & 0x0F) as usize,
+            0xB000 => self.chr_banks[0][0] = value & 0x1F,
+            0xC000 => self.chr_banks[0][1] = value & 0x1F,
+            0xD000 => self.chr_banks[1][0] = value & 0x1F,
+            0xE000 => self.chr_banks[1][1] = value & 0x1F,
+            0xF000 => {
+                self.mirroring = if value & 0x01 != 0 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn ppu_read(&mut self, addr: u16, ciram: &[u8]) -> u8 {
+        // The latch flips after the fetch, so the trigger tile itself comes from the old bank
+        let value = self.ppu_peek(addr, ciram);
+        let window = (addr as usize >> 12) & 0x01;
+        // MMC2 only triggers on the exact address in the first window
+        let whole_row = window == 1 || self.mmc4;
+        match addr & 0x3FF8 {
+            0x0FD8 | 0x1FD8 if whole_row || addr == 0x0FD8 => self.latches[window] = 0,
+            0x0FE8 | 0x1FE8 if whole_row || addr == 0x0FE8 => self.latches[window] = 1,
+            _ => {}
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::mappers::{numbered_board, CIRAM};
+
+    // Mapper 9 is MMC2 and mapper 10 MMC4
+    fn board(mapper: u16) -> Box<dyn Mapper> {
+        let mut board = numbered_board(mapper);
+        board.write_register(0xB000, 1);
+        board.write_register(0xC000, 2);
+        board.write_register(0xD000, 3);
+        board.write_register(0xE000, 4);
+        board
+    }
+
+    // Each 4 KiB CHR bank starts with its first 1 KiB bank number
+    fn chr_bank(board: &dyn Mapper, addr: u16) -> u8 {
+        board.ppu_peek(addr, &CIRAM) / 4
+    }
+
+    #[test]
+    fn mmc2_switches_8k_and_fixes_the_last_three_banks() {
+        let mut mmc2 = board(9);
+        mmc2.write_register(0xA000, 3);
+        assert_eq!(mmc2.cpu_peek(0x8000), Some(3));
+        assert_eq!(mmc2.cpu_peek(0xA000), Some(29));
+        assert_eq!(mmc2.cpu_peek(0xC000), Some(30));
+        assert_eq!(mmc2.cpu_peek(0xE000), Some(31));
+    }
+
+    #[test]
+    fn mmc4_switches_16k_and_fixes_the_last_bank() {
+        let mut mmc4 = board(10);
+        mmc4.write_register(0xA000, 2);
+        assert_eq!(mmc4.cpu_peek(0x8000), Some(4));
+        assert_eq!(mmc4.cpu_peek(0xA000), Some(5));
+        assert_eq!(mmc4.cpu_peek(0xC000), Some(30));
+        assert_eq!(mmc4.cpu_peek(0xE000), Some(31));
+    }
+
+    #[test]
+    fn mirroring_is_selected_by_f000() {
+        let mut mmc2 = board(9);
+        mmc2.write_register(0xF000, 0);
+        assert_eq!(mmc2.mirroring(), Mirroring::Vertical);
+        mmc2.write_register(0xF000, 1);
+        assert_eq!(mmc2.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn fd_and_fe_tiles_flip_the_latches() {
+        let mut mmc2 = board(9);
+        assert_eq!(chr_bank(mmc2.as_ref(), 0x0000), 1);
+        assert_eq!(chr_bank(mmc2.as_ref(), 0x1000), 3);
+
+        // The trigger fetch still comes from the old bank
+        assert_eq!(mmc2.ppu_read(0x0FE8, &CIRAM) / 4, 1);
+        assert_eq!(chr_bank(mmc2.as_ref(), 0x0000), 2);
+        assert_eq!(chr_bank(mmc2.as_ref(), 0x1000), 3);
+
+        mmc2.ppu_read(0x1FEF, &CIRAM);
+        assert_eq!(chr_bank(mmc2.as_ref(), 0x1000), 4);
+
+        mmc2.ppu_read(0x0FD8, &CIRAM);
+        mmc2.ppu_read(0x1FDA, &CIRAM);
+        assert_eq!(chr_bank(mmc2.as_ref(), 0x0000), 1);
+        assert_eq!(chr_bank(mmc2.as_ref(), 0x1000), 3);
+    }
+
+    #[test]
+    fn mmc2_only_triggers_on_the_exact_address_in_the_first_window() {
+        let mut mmc2 = board(9);
+        mmc2.ppu_read(0x0FE9, &CIRAM);
+        assert_eq!(chr_bank(mmc2.as_ref(), 0x0000), 1);
+
+        let mut mmc4 = board(10);
+        mmc4.ppu_read(0x0FE9, &CIRAM);
+        assert_eq!(chr_bank(mmc4.as_ref(), 0x0000), 2);
+    }
+}
@@ -0,0 +1,358 @@
+use super::{bank_offset, Mapper, Memory};
+use crate::nes::cartridge::Mirroring;
+
+const PRG_BANK_SIZE: usize = 8 * 1024;
+const CHR_BANK_SIZE: usize = 1024;
+// A12 has to stay low for a few CPU cycles before a rise clocks the IRQ counter, which
+// filters out the short dips between sprite pattern fetches
+const A12_FILTER_CYCLES: u8 = 3;
+
+// Mapper 4: 8 KiB PRG banks, 1/2 KiB CHR banks and a scanline counter clocked by PPU A12
+pub struct Mmc3 {
+    memory: Memory,
+    bank_select: u8,
+    banks: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram_protect: u8,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    a12_high: bool,
+    a12_low_cycles: u8,
+}
+
+impl Mmc3 {
+    pub fn new(memory: Memory) -> Self {
+        Self {
+            mirroring: memory.mirroring,
+            memory,
+            bank_select: 0,
+            banks: [0, 2, 4, 5, 6, 7, 0, 1],
+            prg_ram_protect: 0x80,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            a12_high: false,
+            a12_low_cycles: 0,
+        }
+    }
+
+    fn watch_a12(&mut self, addr: u16) {
+        if addr & 0x1000 == 0 {
+            self.a12_high = false;
+            return;
+        }
+
+        if !self.a12_high && self.a12_low_cycles >= A12_FILTER_CYCLES {
+            self.clock_irq_counter();
+        }
+        self.a12_high = true;
+        self.a12_low_cycles = 0;
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        let second_last = (self.memory.prg_rom.len() / PRG_BANK_SIZE).saturating_sub(2);
+        let swap = self.bank_select & 0x40 != 0;
+        let bank = match (addr, swap) {
+            (0x8000..=0x9FFF, false) | (0xC000..=0xDFFF, true) => self.banks[6] as usize,
+            (0x8000..=0x9FFF, true) | (0xC000..=0xDFFF, false) => second_last,
+            (0xA000..=0xBFFF, _) => self.banks[7] as usize,
+            _ => second_last + 1,
+        };
+        bank_offset(bank, PRG_BANK_SIZE, addr)
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        // Bit 7 of the bank select swaps the 2 KiB and the 1 KiB halves
+        let addr = if self.bank_select & 0x80 != 0 {
+            addr ^ 0x1000
+        } else {
+            addr
+        };
+        let bank = match addr & 0x1FFF {
+            0x0000..=0x03FF => self.banks[0] & !1,
+            0x0400..=0x07FF => self.banks[0] | 1,
+            0x0800..=0x0BFF => self.banks[1] & !1,
+            0x0C00..=0x0FFF => self.banks[1] | 1,
+            0x1000..=0x13FF => self.banks[2],
+            0x1400..=0x17FF => self.banks[3],
+            0x1800..=0x1BFF => self.banks[4],
+            _ => self.banks[5],
+        };
+        bank_offset(bank as usize, CHR_BANK_SIZE, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        match (addr & 0xE000, addr & 0x0001) {
+            (0x8000, 0) => self.bank_select = value,
+            (0x8000, _) => {
+                let register = (self.bank_select & 0x07) as usize;
+                // The PRG registers only have 6 bits
+                self.banks[register] = if register >= 6 { value & 0x3F } else { value };
+            }
+            (0xA000, 0) => {
+                if self.memory.mirroring != Mirroring::FourScreen {
+                    self.mirroring = if value & 0x01 != 0 {
+                        Mirroring::Horizontal
+                    } else {
+                        Mirroring::Vertical
+                    };
+                }
+            }
+            (0xA000, _) => self.prg_ram_protect = value,
+            (0xC000, 0) => self.irq_latch = value,
+            (0xC000, _) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (0xE000, 0) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            _ => self.irq_enabled = true,
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_ram_protect & 0x80 != 0 && !self.memory.prg_ram.is_empty()
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        self.prg_ram_enabled() && self.prg_ram_protect & 0x40 == 0
+    }
+
+    fn ppu_read(&mut self, addr: u16, ciram: &[u8]) -> u8 {
+        self.watch_a12(addr);
+        self.ppu_peek(addr, ciram)
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, ciram: &mut [u8]) {
+        self.watch_a12(addr);
+        let offset = self.chr_offset(addr);
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF if self.memory.chr_is_ram => {
+                let len = self.memory.chr.len();
+                self.memory.chr[offset % len] = value;
+            }
+            0x0000..=0x1FFF => {}
+            _ => self
+                .memory
+                .write_nametable(self.mirroring, addr, value, ciram),
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn tick(&mut self) {
+        if !self.a12_high {
+            self.a12_low_cycles = self.a12_low_cycles.saturating_add(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::mappers::{numbered_board, numbered_board_with, CIRAM};
+
+    fn set_bank(mmc3: &mut dyn Mapper, mode: u8, register: u8, bank: u8) {
+        mmc3.write_register(0x8000, mode | register);
+        mmc3.write_register(0x8001, bank);
+    }
+
+    // A12 stays low through the background fetches, then rises for the sprite fetches
+    fn scanline(mmc3: &mut dyn Mapper) {
+        mmc3.ppu_read(0x0000, &CIRAM);
+        for _ in 0..A12_FILTER_CYCLES {
+            mmc3.tick();
+        }
+        mmc3.ppu_read(0x1000, &CIRAM);
+    }
+
+    fn enable_irq(mmc3: &mut dyn Mapper, latch: u8) {
+        mmc3.write_register(0xC000, latch);
+        mmc3.write_register(0xC001, 0);
+        mmc3.write_register(0xE001, 0);
+    }
+
+    // Clocks scanlines until the IRQ fires, then acknowledges it
+    fn scanlines_until_irq(mmc3: &mut dyn Mapper) -> usize {
+        let scanlines = (1..=256)
+            .find(|_| {
+                scanline(mmc3);
+                mmc3.irq()
+            })
+            .expect("no IRQ within 256 scanlines");
+        mmc3.write_register(0xE000, 0);
+        mmc3.write_register(0xE001, 0);
+        scanlines
+    }
+
+    #[test]
+    fn prg_mode_swaps_the_fixed_and_switchable_banks() {
+        let mut mmc3 = numbered_board(4);
+        set_bank(mmc3.as_mut(), 0x00, 6, 5);
+        set_bank(mmc3.as_mut(), 0x00, 7, 9);
+
+        assert_eq!(mmc3.cpu_peek(0x8000), Some(5));
+        assert_eq!(mmc3.cpu_peek(0xA000), Some(9));
+        assert_eq!(mmc3.cpu_peek(0xC000), Some(30));
+        assert_eq!(mmc3.cpu_peek(0xE000), Some(31));
+
+        mmc3.write_register(0x8000, 0x40);
+        assert_eq!(mmc3.cpu_peek(0x8000), Some(30));
+        assert_eq!(mmc3.cpu_peek(0xA000), Some(9));
+        assert_eq!(mmc3.cpu_peek(0xC000), Some(5));
+        assert_eq!(mmc3.cpu_peek(0xE000), Some(31));
+    }
+
+    #[test]
+    fn single_bank_prg_rom_fills_every_window() {
+        let mmc3 = numbered_board_with(4, 1, 8);
+        for addr in [0x8000, 0xA000, 0xC000, 0xE000] {
+            assert_eq!(mmc3.cpu_peek(addr), Some(0));
+        }
+    }
+
+    #[test]
+    fn a001_enables_and_write_protects_prg_ram() {
+        let mut mmc3 = numbered_board(4);
+        mmc3.cpu_write(0x6000, 0x42);
+        assert_eq!(mmc3.cpu_peek(0x6000), Some(0x42));
+
+        mmc3.write_register(0xA001, 0xC0);
+        mmc3.cpu_write(0x6000, 0x24);
+        assert_eq!(mmc3.cpu_peek(0x6000), Some(0x42));
+
+        mmc3.write_register(0xA001, 0x00);
+        assert_eq!(mmc3.cpu_peek(0x6000), None);
+    }
+
+    #[test]
+    fn chr_mode_swaps_the_2k_and_1k_halves() {
+        let mut mmc3 = numbered_board(4);
+        set_bank(mmc3.as_mut(), 0x00, 0, 0x11);
+        set_bank(mmc3.as_mut(), 0x00, 2, 0x22);
+
+        // 2 KiB banks ignore the low bit
+        assert_eq!(mmc3.ppu_peek(0x0000, &CIRAM), 0x10);
+        assert_eq!(mmc3.ppu_peek(0x0400, &CIRAM), 0x11);
+        assert_eq!(mmc3.ppu_peek(0x1000, &CIRAM), 0x22);
+
+        mmc3.write_register(0x8000, 0x80);
+        assert_eq!(mmc3.ppu_peek(0x0000, &CIRAM), 0x22);
+        assert_eq!(mmc3.ppu_peek(0x1000, &CIRAM), 0x10);
+        assert_eq!(mmc3.ppu_peek(0x1400, &CIRAM), 0x11);
+    }
+
+    #[test]
+    fn mirroring_is_selected_by_a000() {
+        let mut mmc3 = numbered_board(4);
+        mmc3.write_register(0xA000, 0);
+        assert_eq!(mmc3.mirroring(), Mirroring::Vertical);
+        mmc3.write_register(0xA000, 1);
+        assert_eq!(mmc3.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn irq_fires_when_the_counter_reaches_zero() {
+        let mut mmc3 = numbered_board(4);
+        enable_irq(mmc3.as_mut(), 2);
+
+        // The first clock loads the latch, the next two count it down
+        scanline(mmc3.as_mut());
+        scanline(mmc3.as_mut());
+        assert!(!mmc3.irq());
+        scanline(mmc3.as_mut());
+        assert!(mmc3.irq());
+
+        // Writing $E000 acknowledges and disables
+        mmc3.write_register(0xE000, 0);
+        assert!(!mmc3.irq());
+        for _ in 0..3 {
+            scanline(mmc3.as_mut());
+        }
+        assert!(!mmc3.irq());
+    }
+
+    #[test]
+    fn counter_reloads_on_zero_or_after_c001() {
+        let mut mmc3 = numbered_board(4);
+        enable_irq(mmc3.as_mut(), 2);
+        assert_eq!(scanlines_until_irq(mmc3.as_mut()), 3);
+
+        // A zero counter reloads from the latch instead of wrapping
+        assert_eq!(scanlines_until_irq(mmc3.as_mut()), 3);
+
+        // A new latch is only picked up by the next reload
+        mmc3.write_register(0xC000, 5);
+        assert_eq!(scanlines_until_irq(mmc3.as_mut()), 6);
+
+        // $C001 reloads on the next clock, even though the counter is not zero yet
+        scanline(mmc3.as_mut());
+        scanline(mmc3.as_mut());
+        mmc3.write_register(0xC000, 2);
+        mmc3.write_register(0xC001, 0);
+        assert_eq!(scanlines_until_irq(mmc3.as_mut()), 3);
+    }
+
+    #[test]
+    fn zero_latch_fires_on_every_clock() {
+        let mut mmc3 = numbered_board(4);
+        enable_irq(mmc3.as_mut(), 0);
+        for _ in 0..3 {
+            assert_eq!(scanlines_until_irq(mmc3.as_mut()), 1);
+        }
+    }
+
+    #[test]
+    fn short_a12_dips_do_not_clock_the_counter() {
+        let mut mmc3 = numbered_board(4);
+        enable_irq(mmc3.as_mut(), 1);
+        scanline(mmc3.as_mut());
+
+        // Sprite pattern fetches toggle A12 every few PPU dots
+        for _ in 0..8 {
+            mmc3.ppu_read(0x0000, &CIRAM);
+            mmc3.tick();
+            mmc3.ppu_read(0x1000, &CIRAM);
+        }
+        assert!(!mmc3.irq());
+
+        scanline(mmc3.as_mut());
+        assert!(mmc3.irq());
+    }
+}
@@ -0,0 +1,514 @@
+use super::{Mapper, Memory};
+
+const EXRAM_SIZE: usize = 1024;
+// The PPU fetches 32 background tiles (4 reads each) before the sprite patterns
+const SPRITE_FETCHES: std::ops::Range<u16> = 128..160;
+
+// Mapper 5: Nintendo MMC5. It detects scanlines by watching the PPU fetch the same
+// nametable byte three times in a row, and snoops PPUCTRL/PPUMASK to know when 8x16
+// sprites need their own CHR banks. Expansion audio, the vertical split and extended
+// attribute mode are not emulated.
+pub struct Mmc5 {
+    memory: Memory,
+    prg_mode: u8,
+    chr_mode: u8,
+    prg_ram_protect: [u8; 2],
+    exram_mode: u8,
+    nametable_mapping: u8,
+    fill_tile: u8,
+    fill_attribute: u8,
+    // $5113-$5117: PRG RAM at $6000 then the four $8000-$FFFF windows
+    prg_banks: [u8; 5],
+    // $5120-$5127 are used for sprites, $5128-$512B for the background
+    chr_banks: [u16; 12],
+    chr_upper_bits: u16,
+    last_chr_set_b: bool,
+    exram: [u8; EXRAM_SIZE],
+    multiplicand: u8,
+    multiplier: u8,
+    tall_sprites: bool,
+    rendering: bool,
+    irq_scanline: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+    in_frame: bool,
+    scanline: u8,
+    last_nametable_addr: u16,
+    nametable_matches: u8,
+    fetches: u16,
+    idle_cycles: u8,
+}
+
+enum PrgTarget {
+    Rom(usize),
+    Ram(usize),
+}
+
+impl Mmc5 {
+    pub fn new(memory: Memory) -> Self {
+        Self {
+            memory,
+            prg_mode: 3,
+            chr_mode: 0,
+            prg_ram_protect: [0; 2],
+            exram_mode: 0,
+            nametable_mapping: 0,
+            fill_tile: 0,
+            fill_attribute: 0,
+            prg_banks: [0, 0xFF, 0xFF, 0xFF, 0xFF],
+            chr_banks: [0; 12],
+            chr_upper_bits: 0,
+            last_chr_set_b: false,
+            exram: [0; EXRAM_SIZE],
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+            tall_sprites: false,
+            rendering: false,
+            irq_scanline: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            in_frame: false,
+            scanline: 0,
+            last_nametable_addr: 0,
+            nametable_matches: 0,
+            fetches: 0,
+            idle_cycles: 0,
+        }
+    }
+
+    fn prg_target(&self, addr: u16) -> PrgTarget {
+        // Which register drives the window, and how big the window is
+        let (register, size) = match (self.prg_mode, addr) {
+            (_, 0x6000..=0x7FFF) => (0, 8),
+            (0, _) => (4, 32),
+            (1, 0x8000..=0xBFFF) => (2, 16),
+            (1, _) => (4, 16),
+            (2, 0x8000..=0xBFFF) => (2, 16),
+            (2, 0xC000..=0xDFFF) => (3, 8),
+            (2, _) => (4, 8),
+            (_, _) => (1 + ((addr as usize - 0x8000) >> 13), 8),
+        };
+
+        let value = self.prg_banks[register];
+        // Bank numbers are always in 8 KiB units, bigger windows ignore the low bits
+        let bank = (value & 0x7F) as usize & !(size / 8 - 1);
+        let offset = bank * 0x2000 + (addr as usize & (size * 1024 - 1));
+        // The last window is always ROM, the $6000 one always RAM
+        if register == 4 || (register != 0 && value & 0x80 != 0) {
+            PrgTarget::Rom(offset)
+        } else {
+            PrgTarget::Ram(offset)
+        }
+    }
+
+    // Sprites use the first set of CHR banks in 8x16 mode, otherwise whichever set was
+    // written last is used for everything
+    fn chr_set_b(&self) -> bool {
+        if self.tall_sprites && self.rendering && self.in_frame {
+            !SPRITE_FETCHES.contains(&self.fetches)
+        } else {
+            self.last_chr_set_b
+        }
+    }
+
+    fn nametable_peek(&self, addr: u16, ciram: &[u8]) -> u8 {
+        let table = (addr >> 10) & 0x03;
+        let offset = addr as usize & 0x03FF;
+        match (self.nametable_mapping >> (table * 2)) & 0x03 {
+            source @ (0 | 1) => ciram[source as usize * 0x400 + offset],
+            2 if self.exram_mode < 2 => self.exram[offset],
+            2 => 0,
+            // Fill mode: the same tile and palette everywhere
+            _ if offset >= 0x3C0 => (self.fill_attribute & 0x03) * 0x55,
+            _ => self.fill_tile,
+        }
+    }
+
+    fn watch_scanline(&mut self, addr: u16) {
+        self.idle_cycles = 0;
+        self.fetches = self.fetches.wrapping_add(1);
+
+        if !(0x2000..=0x2FFF).contains(&addr) {
+            self.nametable_matches = 0;
+            return;
+        }
+        if addr != self.last_nametable_addr {
+            self.last_nametable_addr = addr;
+            self.nametable_matches = 0;
+            return;
+        }
+
+        self.nametable_matches += 1;
+        if self.nametable_matches == 2 {
+            self.fetches = 0;
+            if self.in_frame {
+                self.scanline = self.scanline.wrapping_add(1);
+                if self.scanline == self.irq_scanline {
+                    self.irq_pending = true;
+                }
+            } else {
+                self.in_frame = true;
+                self.scanline = 0;
+            }
+        }
+    }
+}
+
+impl Mapper for Mmc5 {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        match self.prg_target(addr) {
+            PrgTarget::Rom(offset) | PrgTarget::Ram(offset) => offset,
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let slot = (addr as usize >> 10) & 0x07;
+        // Banks are counted in units of the current bank size
+        let (size, register) = match self.chr_mode {
+            0 => (8, 7),
+            1 => (4, slot | 3),
+            2 => (2, slot | 1),
+            _ => (1, slot),
+        };
+        let register = if self.chr_set_b() {
+            8 + (register & 0x03)
+        } else {
+            register
+        };
+
+        let bank = self.chr_banks[register] as usize;
+        bank * size * 1024 + (addr as usize & (size * 1024 - 1))
+    }
+
+    // Registers live in $5000-$5FFF, see cpu_write
+    fn write_register(&mut self, _addr: u16, _value: u8) {}
+
+    // Both protect registers need their magic values before PRG RAM can be written
+    fn prg_ram_writable(&self) -> bool {
+        self.prg_ram_protect == [0x02, 0x01] && !self.memory.prg_ram.is_empty()
+    }
+
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        let value = self.cpu_peek(addr);
+        if addr == 0x5204 {
+            self.irq_pending = false;
+        }
+        value
+    }
+
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x5204 => Some(((self.irq_pending as u8) << 7) | ((self.in_frame as u8) << 6)),
+            0x5205 => Some((self.multiplicand as u16 * self.multiplier as u16) as u8),
+            0x5206 => Some(((self.multiplicand as u16 * self.multiplier as u16) >> 8) as u8),
+            0x5C00..=0x5FFF if self.exram_mode >= 2 => Some(self.exram[addr as usize - 0x5C00]),
+            0x6000..=0xFFFF => match self.prg_target(addr) {
+                PrgTarget::Rom(offset) => {
+                    Some(self.memory.prg_rom[offset % self.memory.prg_rom.len()])
+                }
+                PrgTarget::Ram(_) if self.memory.prg_ram.is_empty() => None,
+                PrgTarget::Ram(offset) => {
+                    Some(self.memory.prg_ram[offset % self.memory.prg_ram.len()])
+                }
+            },
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            // PPU registers, as seen from the cartridge connector
+            0x2000..=0x3FFF => match addr & 0x0007 {
+                0x0000 => self.tall_sprites = value & 0x20 != 0,
+                0x0001 => {
+                    self.rendering = value & 0x18 != 0;
+                    if !self.rendering {
+                        self.in_frame = false;
+                    }
+                }
+                _ => {}
+            },
+            0x5100 => self.prg_mode = value & 0x03,
+            0x5101 => self.chr_mode = value & 0x03,
+            0x5102 => self.prg_ram_protect[0] = value & 0x03,
+            0x5103 => self.prg_ram_protect[1] = value & 0x03,
+            0x5104 => self.exram_mode = value & 0x03,
+            0x5105 => self.nametable_mapping = value,
+            0x5106 => self.fill_tile = value,
+            0x5107 => self.fill_attribute = value & 0x03,
+            0x5113..=0x5117 => self.prg_banks[addr as usize - 0x5113] = value,
+            0x5120..=0x512B => {
+                let register = addr as usize - 0x5120;
+                self.chr_banks[register] = value as u16 | (self.chr_upper_bits << 8);
+                self.last_chr_set_b = register >= 8;
+            }
+            0x5130 => self.chr_upper_bits = (value & 0x03) as u16,
+            0x5203 => self.irq_scanline = value,
+            0x5204 => self.irq_enabled = value & 0x80 != 0,
+            0x5205 => self.multiplicand = value,
+            0x5206 => self.multiplier = value,
+            0x5C00..=0x5FFF if self.exram_mode != 3 => self.exram[addr as usize - 0x5C00] = value,
+            0x6000..=0xFFFF => {
+                if let PrgTarget::Ram(offset) = self.prg_target(addr) {
+                    if self.prg_ram_writable() {
+                        let len = self.memory.prg_ram.len();
+                        self.memory.prg_ram[offset % len] = value;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, ciram: &[u8]) -> u8 {
+        self.watch_scanline(addr);
+        self.ppu_peek(addr, ciram)
+    }
+
+    fn ppu_peek(&self, addr: u16, ciram: &[u8]) -> u8 {
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF => self.memory.chr[self.chr_offset(addr) % self.memory.chr.len()],
+            _ => self.nametable_peek(addr, ciram),
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, ciram: &mut [u8]) {
+        let offset = addr as usize & 0x03FF;
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF if self.memory.chr_is_ram => {
+                let chr_offset = self.chr_offset(addr) % self.memory.chr.len();
+                self.memory.chr[chr_offset] = value;
+            }
+            0x0000..=0x1FFF => {}
+            _ => match (self.nametable_mapping >> (((addr >> 10) & 0x03) * 2)) & 0x03 {
+                source @ (0 | 1) => ciram[source as usize * 0x400 + offset] = value,
+                2 if self.exram_mode < 2 => self.exram[offset] = value,
+                _ => {}
+            },
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending && self.irq_enabled
+    }
+
+    fn tick(&mut self) {
+        // The PPU stops fetching between frames, which ends the frame for the MMC5
+        if self.idle_cycles < 3 {
+            self.idle_cycles += 1;
+            if self.idle_cycles == 3 {
+                self.in_frame = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::mappers::{numbered_board, CIRAM};
+
+    fn prg_banks(mmc5: &dyn Mapper) -> [Option<u8>; 4] {
+        [0x8000, 0xA000, 0xC000, 0xE000].map(|addr| mmc5.cpu_peek(addr))
+    }
+
+    // The PPU reads the same nametable byte several times at the end of each scanline
+    fn scanline(mmc5: &mut dyn Mapper) {
+        mmc5.ppu_read(0x0000, &CIRAM);
+        for _ in 0..3 {
+            mmc5.ppu_read(0x2000, &CIRAM);
+        }
+    }
+
+    #[test]
+    fn prg_modes_split_the_rom_into_bigger_windows() {
+        let mut mmc5 = numbered_board(5);
+        for (register, bank) in [
+            (0x5114, 0x85),
+            (0x5115, 0x86),
+            (0x5116, 0x87),
+            (0x5117, 0x1F),
+        ] {
+            mmc5.cpu_write(register, bank);
+        }
+        assert_eq!(
+            prg_banks(mmc5.as_ref()),
+            [Some(5), Some(6), Some(7), Some(31)]
+        );
+
+        // 16 KiB windows at $8000 and $C000
+        mmc5.cpu_write(0x5100, 1);
+        mmc5.cpu_write(0x5115, 0x85);
+        mmc5.cpu_write(0x5117, 0x0B);
+        assert_eq!(
+            prg_banks(mmc5.as_ref()),
+            [Some(4), Some(5), Some(10), Some(11)]
+        );
+
+        // 16 KiB at $8000, then two 8 KiB windows
+        mmc5.cpu_write(0x5100, 2);
+        mmc5.cpu_write(0x5116, 0x89);
+        assert_eq!(
+            prg_banks(mmc5.as_ref()),
+            [Some(4), Some(5), Some(9), Some(11)]
+        );
+
+        // One 32 KiB window
+        mmc5.cpu_write(0x5100, 0);
+        mmc5.cpu_write(0x5117, 0x1F);
+        assert_eq!(
+            prg_banks(mmc5.as_ref()),
+            [Some(28), Some(29), Some(30), Some(31)]
+        );
+    }
+
+    #[test]
+    fn prg_ram_is_write_protected_until_both_magic_values_are_set() {
+        let mut mmc5 = numbered_board(5);
+        // Bit 7 clear maps RAM into $8000-$9FFF
+        mmc5.cpu_write(0x5114, 0x00);
+        mmc5.cpu_write(0x8000, 0x42);
+        assert_eq!(mmc5.cpu_peek(0x8000), Some(0));
+
+        mmc5.cpu_write(0x5102, 0x02);
+        mmc5.cpu_write(0x5103, 0x01);
+        mmc5.cpu_write(0x8000, 0x42);
+        assert_eq!(mmc5.cpu_peek(0x8000), Some(0x42));
+        assert_eq!(mmc5.cpu_peek(0x6000), Some(0x42));
+    }
+
+    #[test]
+    fn chr_modes_split_the_pattern_tables_into_bigger_windows() {
+        let mut mmc5 = numbered_board(5);
+        mmc5.cpu_write(0x5101, 3);
+        mmc5.cpu_write(0x5120, 0x10);
+        mmc5.cpu_write(0x5127, 0x17);
+        assert_eq!(mmc5.ppu_peek(0x0000, &CIRAM), 0x10);
+        assert_eq!(mmc5.ppu_peek(0x1C00, &CIRAM), 0x17);
+
+        // Two 4 KiB windows driven by $5123 and $5127
+        mmc5.cpu_write(0x5101, 1);
+        mmc5.cpu_write(0x5123, 2);
+        mmc5.cpu_write(0x5127, 3);
+        assert_eq!(mmc5.ppu_peek(0x0000, &CIRAM), 8);
+        assert_eq!(mmc5.ppu_peek(0x1C00, &CIRAM), 15);
+
+        // One 8 KiB window driven by $5127
+        mmc5.cpu_write(0x5101, 0);
+        mmc5.cpu_write(0x5127, 1);
+        assert_eq!(mmc5.ppu_peek(0x0000, &CIRAM), 8);
+        assert_eq!(mmc5.ppu_peek(0x1C00, &CIRAM), 15);
+    }
+
+    #[test]
+    fn the_last_written_chr_set_is_used_outside_8x16_sprites() {
+        let mut mmc5 = numbered_board(5);
+        mmc5.cpu_write(0x5101, 3);
+        mmc5.cpu_write(0x5120, 0x10);
+        mmc5.cpu_write(0x5128, 0x30);
+        assert_eq!(mmc5.ppu_peek(0x0000, &CIRAM), 0x30);
+        assert_eq!(mmc5.ppu_peek(0x1000, &CIRAM), 0x30);
+
+        mmc5.cpu_write(0x5120, 0x10);
+        assert_eq!(mmc5.ppu_peek(0x0000, &CIRAM), 0x10);
+    }
+
+    #[test]
+    fn nametables_are_mapped_by_5105() {
+        let mut mmc5 = numbered_board(5);
+        let mut ciram = [0; 0x800];
+
+        // Vertical mirroring
+        mmc5.cpu_write(0x5105, 0x44);
+        mmc5.ppu_write(0x2400, 0x42, &mut ciram);
+        assert_eq!(ciram[0x400], 0x42);
+        assert_eq!(mmc5.ppu_peek(0x2C00, &ciram), 0x42);
+        assert_eq!(mmc5.ppu_peek(0x2800, &ciram), 0);
+
+        // ExRAM as a nametable
+        mmc5.cpu_write(0x5105, 0x02);
+        mmc5.ppu_write(0x2010, 0x24, &mut ciram);
+        assert_eq!(mmc5.ppu_peek(0x2010, &ciram), 0x24);
+        assert_eq!(ciram[0x10], 0);
+
+        // Fill mode
+        mmc5.cpu_write(0x5105, 0xFF);
+        mmc5.cpu_write(0x5106, 0x42);
+        mmc5.cpu_write(0x5107, 0x02);
+        assert_eq!(mmc5.ppu_peek(0x2000, &ciram), 0x42);
+        assert_eq!(mmc5.ppu_peek(0x2FC0, &ciram), 0xAA);
+    }
+
+    #[test]
+    fn irq_fires_on_the_5203_scanline() {
+        let mut mmc5 = numbered_board(5);
+        mmc5.cpu_write(0x5203, 3);
+        mmc5.cpu_write(0x5204, 0x80);
+
+        // The first detected scanline starts the frame
+        scanline(mmc5.as_mut());
+        assert_eq!(mmc5.cpu_peek(0x5204), Some(0x40));
+        for _ in 0..2 {
+            scanline(mmc5.as_mut());
+            assert!(!mmc5.irq());
+        }
+        scanline(mmc5.as_mut());
+        assert!(mmc5.irq());
+        assert_eq!(mmc5.cpu_peek(0x5204), Some(0xC0));
+    }
+
+    #[test]
+    fn reading_5204_acknowledges() {
+        let mut mmc5 = numbered_board(5);
+        mmc5.cpu_write(0x5203, 1);
+        mmc5.cpu_write(0x5204, 0x80);
+        scanline(mmc5.as_mut());
+        scanline(mmc5.as_mut());
+        assert!(mmc5.irq());
+
+        assert_eq!(mmc5.cpu_read(0x5204), Some(0xC0));
+        assert!(!mmc5.irq());
+        assert_eq!(mmc5.cpu_peek(0x5204), Some(0x40));
+    }
+
+    #[test]
+    fn pending_irq_is_visible_while_disabled() {
+        let mut mmc5 = numbered_board(5);
+        mmc5.cpu_write(0x5203, 1);
+        scanline(mmc5.as_mut());
+        scanline(mmc5.as_mut());
+        assert!(!mmc5.irq());
+        assert_eq!(mmc5.cpu_peek(0x5204), Some(0xC0));
+
+        mmc5.cpu_write(0x5204, 0x80);
+        assert!(mmc5.irq());
+    }
+
+    #[test]
+    fn frame_ends_when_the_ppu_stops_fetching() {
+        let mut mmc5 = numbered_board(5);
+        scanline(mmc5.as_mut());
+        assert_eq!(mmc5.cpu_peek(0x5204), Some(0x40));
+        for _ in 0..3 {
+            mmc5.tick();
+        }
+        assert_eq!(mmc5.cpu_peek(0x5204), Some(0x00));
+    }
+
+    #[test]
+    fn multiplier_returns_the_16_bit_product() {
+        let mut mmc5 = numbered_board(5);
+        mmc5.cpu_write(0x5205, 0xC8);
+        mmc5.cpu_write(0x5206, 0x0F);
+        assert_eq!(mmc5.cpu_peek(0x5205), Some(0xB8));
+        assert_eq!(mmc5.cpu_peek(0x5206), Some(0x0B));
+    }
+}
@@ -0,0 +1,220 @@
+mod axrom;
+mod cnrom;
+mod fme7;
+mod mmc1;
+mod mmc2;
+mod mmc3;
+mod mmc5;
+mod namco163;
+mod nrom;
+mod uxrom;
+mod vrc;
+mod vrc6;
+mod vrc7;
+
+use super::cartridge::{CartridgeError, Header, Mirroring};
+
+// Everything on the cartridge board that the mapper switches banks of
+pub struct Memory {
+    pub prg_rom: Vec<u8>,
+    pub prg_ram: Vec<u8>,
+    // Either CHR ROM or, when the header declares none, CHR RAM
+    pub chr: Vec<u8>,
+    pub chr_is_ram: bool,
+    // Extra nametable RAM on four-screen boards, the console only has 2 KiB
+    pub vram: Vec<u8>,
+    pub mirroring: Mirroring,
+}
+
+impl Memory {
+    pub fn new(header: &Header, prg_rom: Vec<u8>, chr_rom: Vec<u8>, prg_ram: Vec<u8>) -> Self {
+        let chr_is_ram = chr_rom.is_empty();
+        let chr = if chr_is_ram {
+            vec![0; (header.chr_ram_size + header.chr_nvram_size).max(8 * 1024)]
+        } else {
+            chr_rom
+        };
+        let vram = match header.mirroring {
+            Mirroring::FourScreen => vec![0; 2 * 1024],
+            _ => Vec::new(),
+        };
+
+        Self {
+            prg_rom,
+            prg_ram,
+            chr,
+            chr_is_ram,
+            vram,
+            mirroring: header.mirroring,
+        }
+    }
+
+    // Maps a nametable address ($2000-$3EFF) to CIRAM or the board's own VRAM
+    pub fn nametable(&self, mirroring: Mirroring, addr: u16, ciram: &[u8]) -> u8 {
+        match mirroring.nametable_offset(addr) {
+            offset if offset < 0x800 => ciram[offset],
+            offset => self.vram[offset - 0x800],
+        }
+    }
+
+    pub fn write_nametable(
+        &mut self,
+        mirroring: Mirroring,
+        addr: u16,
+        value: u8,
+        ciram: &mut [u8],
+    ) {
+        match mirroring.nametable_offset(addr) {
+            offset if offset < 0x800 => ciram[offset] = value,
+            offset => self.vram[offset - 0x800] = value,
+        }
+    }
+}
+
+// Offset of `addr` into a switchable window of `size` bytes showing `bank`
+pub fn bank_offset(bank: usize, size: usize, addr: u16) -> usize {
+    bank * size + (addr as usize & (size - 1))
+}
+
+// Nametable RAM for tests that only look at pattern tables
+#[cfg(test)]
+pub const CIRAM: [u8; 0x800] = [0; 0x800];
+
+// A `mapper` board whose PRG ROM bytes hold their 8 KiB bank number and whose CHR ROM
+// bytes hold their 1 KiB bank number, so reads show which banks are switched in. Without
+// CHR banks the board has CHR RAM.
+#[cfg(test)]
+pub fn numbered_board_with(mapper: u16, prg_banks: usize, chr_banks: usize) -> Box<dyn Mapper> {
+    let mut header = [0; 16];
+    header[..5].copy_from_slice(b"NES\x1A\x01");
+    header[6] = (mapper as u8 & 0x0F) << 4;
+    header[7] = mapper as u8 & 0xF0;
+    let header = Header::parse(&header).unwrap();
+
+    let prg_rom = (0..prg_banks * 8 * 1024).map(|i| (i >> 13) as u8).collect();
+    let chr_rom = (0..chr_banks * 1024).map(|i| (i >> 10) as u8).collect();
+    let memory = Memory::new(&header, prg_rom, chr_rom, vec![0; 8 * 1024]);
+    create(&header, memory).unwrap()
+}
+
+// 32 8 KiB PRG banks and 256 1 KiB CHR banks
+#[cfg(test)]
+pub fn numbered_board(mapper: u16) -> Box<dyn Mapper> {
+    numbered_board_with(mapper, 32, 256)
+}
+
+// Bank-switching logic of a cartridge board. Addresses are the ones seen on the CPU
+// bus ($4020-$FFFF) and on the PPU bus ($0000-$3EFF).
+pub trait Mapper {
+    fn memory(&self) -> &Memory;
+
+    fn memory_mut(&mut self) -> &mut Memory;
+
+    // Offset into PRG ROM of a CPU address in $8000-$FFFF
+    fn prg_rom_offset(&self, addr: u16) -> usize;
+
+    // Offset into CHR of a PPU address in $0000-$1FFF
+    fn chr_offset(&self, addr: u16) -> usize;
+
+    // Writes to $8000-$FFFF, where most boards have their registers
+    fn write_register(&mut self, addr: u16, value: u8);
+
+    fn mirroring(&self) -> Mirroring {
+        self.memory().mirroring
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        !self.memory().prg_ram.is_empty()
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        self.prg_ram_enabled()
+    }
+
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        self.cpu_peek(addr)
+    }
+
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        let memory = self.memory();
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                Some(memory.prg_ram[(addr as usize - 0x6000) % memory.prg_ram.len()])
+            }
+            0x8000..=0xFFFF => {
+                Some(memory.prg_rom[self.prg_rom_offset(addr) % memory.prg_rom.len()])
+            }
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_writable() => {
+                let prg_ram = &mut self.memory_mut().prg_ram;
+                let len = prg_ram.len();
+                prg_ram[(addr as usize - 0x6000) % len] = value;
+            }
+            0x8000..=0xFFFF => self.write_register(addr, value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, ciram: &[u8]) -> u8 {
+        self.ppu_peek(addr, ciram)
+    }
+
+    fn ppu_peek(&self, addr: u16, ciram: &[u8]) -> u8 {
+        let memory = self.memory();
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF => memory.chr[self.chr_offset(addr) % memory.chr.len()],
+            _ => memory.nametable(self.mirroring(), addr, ciram),
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, ciram: &mut [u8]) {
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF => {
+                let offset = self.chr_offset(addr);
+                let memory = self.memory_mut();
+                if memory.chr_is_ram {
+                    let len = memory.chr.len();
+                    memory.chr[offset % len] = value;
+                }
+            }
+            _ => {
+                let mirroring = self.mirroring();
+                self.memory_mut()
+                    .write_nametable(mirroring, addr, value, ciram);
+            }
+        }
+    }
+
+    // Whether the board holds the CPU's IRQ line low
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // Called once per CPU cycle
+    fn tick(&mut self) {}
+}
+
+pub fn create(header: &Header, memory: Memory) -> Result<Box<dyn Mapper>, CartridgeError> {
+    Ok(match header.mapper {
+        0 => Box::new(nrom::Nrom::new(memory)),
+        1 => Box::new(mmc1::Mmc1::new(memory)),
+        2 => Box::new(uxrom::Uxrom::new(memory)),
+        3 => Box::new(cnrom::Cnrom::new(memory)),
+        4 => Box::new(mmc3::Mmc3::new(memory)),
+        5 => Box::new(mmc5::Mmc5::new(memory)),
+        7 => Box::new(axrom::Axrom::new(memory)),
+        9 => Box::new(mmc2::Mmc2::new(memory, false)),
+        10 => Box::new(mmc2::Mmc2::new(memory, true)),
+        19 => Box::new(namco163::Namco163::new(memory)),
+        21 | 22 | 23 | 25 => Box::new(vrc::Vrc4::new(memory, header.mapper)),
+        24 | 26 => Box::new(vrc6::Vrc6::new(memory, header.mapper)),
+        69 => Box::new(fme7::Fme7::new(memory)),
+        85 => Box::new(vrc7::Vrc7::new(memory)),
+        mapper => return Err(CartridgeError::UnsupportedMapper(mapper)),
+    })
+}
@@ -0,0 +1,282 @@
+use super::{bank_offset, Mapper, Memory};
+
+const PRG_BANK_SIZE: usize = 8 * 1024;
+const CHR_BANK_SIZE: usize = 1024;
+const INTERNAL_RAM_SIZE: usize = 128;
+
+// Mapper 19: Namco 163. Every 1 KiB of PPU space, nametables included, can show either a
+// CHR ROM bank or a page of the console's CIRAM. The wavetable sound channels are not
+// emulated, but their 128 bytes of RAM are, as some games keep saves in it.
+pub struct Namco163 {
+    memory: Memory,
+    prg_banks: [u8; 3],
+    // Eight pattern table banks followed by the four nametables
+    chr_banks: [u8; 12],
+    // Pattern table banks $E0-$FF select CIRAM unless disabled for that half
+    ciram_disabled: [bool; 2],
+    internal_ram: [u8; INTERNAL_RAM_SIZE],
+    ram_addr: u8,
+    irq_counter: u16,
+    irq_pending: bool,
+}
+
+enum PpuTarget {
+    Chr(usize),
+    Ciram(usize),
+}
+
+impl Namco163 {
+    pub fn new(memory: Memory) -> Self {
+        Self {
+            memory,
+            prg_banks: [0; 3],
+            chr_banks: [0; 12],
+            ciram_disabled: [false; 2],
+            internal_ram: [0; INTERNAL_RAM_SIZE],
+            ram_addr: 0,
+            irq_counter: 0,
+            irq_pending: false,
+        }
+    }
+
+    fn ppu_target(&self, addr: u16) -> PpuTarget {
+        let slot = match addr as usize & 0x3FFF {
+            addr @ 0x0000..=0x1FFF => addr >> 10,
+            // $3000-$3EFF mirrors the nametables
+            addr => 8 + ((addr >> 10) & 0x03),
+        };
+        let bank = self.chr_banks[slot];
+        let ciram_allowed = slot >= 8 || !self.ciram_disabled[slot / 4];
+        if bank >= 0xE0 && ciram_allowed {
+            PpuTarget::Ciram((bank as usize & 0x01) * 0x400 + (addr as usize & 0x03FF))
+        } else {
+            PpuTarget::Chr(bank_offset(bank as usize, CHR_BANK_SIZE, addr) % self.memory.chr.len())
+        }
+    }
+
+    fn irq_enabled(&self) -> bool {
+        self.irq_counter & 0x8000 != 0
+    }
+}
+
+impl Mapper for Namco163 {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x8000..=0xDFFF => self.prg_banks[(addr as usize - 0x8000) / PRG_BANK_SIZE] as usize,
+            _ => (self.memory.prg_rom.len() / PRG_BANK_SIZE).saturating_sub(1),
+        };
+        bank_offset(bank, PRG_BANK_SIZE, addr)
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        bank_offset(
+            self.chr_banks[(addr as usize >> 10) & 0x07] as usize,
+            CHR_BANK_SIZE,
+            addr,
+        )
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        match addr & 0xF800 {
+            block @ 0x8000..=0xD800 => self.chr_banks[(block as usize - 0x8000) >> 11] = value,
+            0xE000 => self.prg_banks[0] = value & 0x3F,
+            0xE800 => {
+                self.prg_banks[1] = value & 0x3F;
+                self.ciram_disabled = [value & 0x40 != 0, value & 0x80 != 0];
+            }
+            0xF000 => self.prg_banks[2] = value & 0x3F,
+            _ => self.ram_addr = value,
+        }
+    }
+
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        let value = self.cpu_peek(addr);
+        match addr {
+            0x4800..=0x4FFF if self.ram_addr & 0x80 != 0 => {
+                self.ram_addr = 0x80 | (self.ram_addr.wrapping_add(1) & 0x7F)
+            }
+            0x5000..=0x5FFF => self.irq_pending = false,
+            _ => {}
+        }
+        value
+    }
+
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4800..=0x4FFF => Some(self.internal_ram[(self.ram_addr & 0x7F) as usize]),
+            0x5000..=0x57FF => Some(self.irq_counter as u8),
+            0x5800..=0x5FFF => Some((self.irq_counter >> 8) as u8),
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                Some(self.memory.prg_ram[(addr as usize - 0x6000) % self.memory.prg_ram.len()])
+            }
+            0x8000..=0xFFFF => {
+                Some(self.memory.prg_rom[self.prg_rom_offset(addr) % self.memory.prg_rom.len()])
+            }
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x4800..=0x4FFF => {
+                self.internal_ram[(self.ram_addr & 0x7F) as usize] = value;
+                if self.ram_addr & 0x80 != 0 {
+                    self.ram_addr = 0x80 | (self.ram_addr.wrapping_add(1) & 0x7F);
+                }
+            }
+            0x5000..=0x57FF => {
+                self.irq_counter = (self.irq_counter & 0xFF00) | value as u16;
+                self.irq_pending = false;
+            }
+            0x5800..=0x5FFF => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | ((value as u16) << 8);
+                self.irq_pending = false;
+            }
+            0x6000..=0x7FFF if self.prg_ram_writable() => {
+                let len = self.memory.prg_ram.len();
+                self.memory.prg_ram[(addr as usize - 0x6000) % len] = value;
+            }
+            0x8000..=0xFFFF => self.write_register(addr, value),
+            _ => {}
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16, ciram: &[u8]) -> u8 {
+        match self.ppu_target(addr) {
+            PpuTarget::Chr(offset) => self.memory.chr[offset],
+            PpuTarget::Ciram(offset) => ciram[offset],
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, value: u8, ciram: &mut [u8]) {
+        match self.ppu_target(addr) {
+            PpuTarget::Chr(offset) if self.memory.chr_is_ram => self.memory.chr[offset] = value,
+            PpuTarget::Chr(_) => {}
+            PpuTarget::Ciram(offset) => ciram[offset] = value,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn tick(&mut self) {
+        // The counter is 15 bits and stops once it reaches $7FFF
+        if self.irq_enabled() && self.irq_counter & 0x7FFF != 0x7FFF {
+            self.irq_counter += 1;
+            if self.irq_counter & 0x7FFF == 0x7FFF {
+                self.irq_pending = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nes::mappers::numbered_board;
+
+    #[test]
+    fn prg_has_three_8k_windows_and_a_fixed_last_bank() {
+        let mut namco = numbered_board(19);
+        namco.write_register(0xE000, 5);
+        namco.write_register(0xE800, 6);
+        namco.write_register(0xF000, 7);
+        assert_eq!(namco.cpu_peek(0x8000), Some(5));
+        assert_eq!(namco.cpu_peek(0xA000), Some(6));
+        assert_eq!(namco.cpu_peek(0xC000), Some(7));
+        assert_eq!(namco.cpu_peek(0xE000), Some(31));
+    }
+
+    #[test]
+    fn high_chr_banks_select_ciram_unless_disabled() {
+        let mut namco = numbered_board(19);
+        let mut ciram = [0; 0x800];
+        ciram[0x400] = 0x42;
+        namco.write_register(0x8000, 0x10);
+        namco.write_register(0xB800, 0xE1);
+        assert_eq!(namco.ppu_peek(0x0000, &ciram), 0x10);
+        assert_eq!(namco.ppu_peek(0x1C00, &ciram), 0x42);
+
+        // Bit 7 of $E800 disables CIRAM in the upper pattern table
+        namco.write_register(0xE800, 0x80);
+        assert_eq!(namco.ppu_peek(0x1C00, &ciram), 0xE1);
+    }
+
+    #[test]
+    fn nametable_banks_set_the_mirroring() {
+        let mut namco = numbered_board(19);
+        let mut ciram = [0; 0x800];
+        for (register, bank) in [
+            (0xC000, 0xE0),
+            (0xC800, 0xE1),
+            (0xD000, 0xE0),
+            (0xD800, 0xE1),
+        ] {
+            namco.write_register(register, bank);
+        }
+        namco.ppu_write(0x2400, 0x42, &mut ciram);
+        assert_eq!(ciram[0x400], 0x42);
+        assert_eq!(namco.ppu_peek(0x2C00, &ciram), 0x42);
+        assert_eq!(namco.ppu_peek(0x2000, &ciram), 0);
+        assert_eq!(namco.ppu_peek(0x3400, &ciram), 0x42);
+
+        // Nametables can also come from CHR ROM, even above bank $E0 for them
+        namco.write_register(0xD000, 0x05);
+        assert_eq!(namco.ppu_peek(0x2800, &ciram), 0x05);
+    }
+
+    #[test]
+    fn irq_fires_when_the_15_bit_counter_reaches_7fff() {
+        let mut namco = numbered_board(19);
+        namco.cpu_write(0x5000, 0xFD);
+        namco.cpu_write(0x5800, 0xFF);
+
+        namco.tick();
+        assert!(!namco.irq());
+        namco.tick();
+        assert!(namco.irq());
+
+        // The counter stops instead of wrapping into the enable bit
+        namco.tick();
+        assert_eq!(namco.cpu_peek(0x5000), Some(0xFF));
+        assert_eq!(namco.cpu_peek(0x5800), Some(0xFF));
+
+        // Writing the counter acknowledges
+        namco.cpu_write(0x5800, 0xFF);
+        assert!(!namco.irq());
+    }
+
+    #[test]
+    fn counter_only_runs_with_bit_15_set() {
+        let mut namco = numbered_board(19);
+        namco.cpu_write(0x5000, 0xFE);
+        namco.cpu_write(0x5800, 0x7F);
+        for _ in 0..4 {
+            namco.tick();
+        }
+        assert_eq!(namco.cpu_peek(0x5000), Some(0xFE));
+        assert!(!namco.irq());
+    }
+
+    #[test]
+    fn internal_ram_address_auto_increments() {
+        let mut namco = numbered_board(19);
+        namco.write_register(0xF800, 0x80 | 0x7F);
+        namco.cpu_write(0x4800, 0x11);
+        namco.cpu_write(0x4800, 0x22);
+
+        // The address wraps within the 128 bytes
+        namco.write_register(0xF800, 0x7F);
+        assert_eq!(namco.cpu_read(0x4800), Some(0x11));
+        namco.write_register(0xF800, 0x00);
+        assert_eq!(namco.cpu_read(0x4800), Some(0x22));
+    }
+}
@@ -0,0 +1,58 @@
+use super::{Mapper, Memory};
+
+// Mapper 0: no bank switching, 16 KiB of PRG ROM are mirrored to fill $8000-$FFFF
+pub struct Nrom {
+    memory: Memory,
+}
+
+impl Nrom {
+    pub fn new(memory: Memory) -> Self {
+        Self { memory }
+    }
+}
+
+impl Mapper for Nrom {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        addr as usize - 0x8000
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn write_register(&mut self, _addr: u16, _value: u8) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nes::mappers::{numbered_board_with, CIRAM};
+
+    #[test]
+    fn nrom_128_is_mirrored_into_c000() {
+        let nrom = numbered_board_with(0, 2, 8);
+        assert_eq!(nrom.cpu_peek(0x8000), Some(0));
+        assert_eq!(nrom.cpu_peek(0xA000), Some(1));
+        assert_eq!(nrom.cpu_peek(0xC000), Some(0));
+        assert_eq!(nrom.cpu_peek(0xE000), Some(1));
+    }
+
+    #[test]
+    fn writes_do_not_switch_banks() {
+        let mut nrom = numbered_board_with(0, 4, 8);
+        nrom.cpu_write(0x8000, 0xFF);
+        assert_eq!(nrom.cpu_peek(0xC000), Some(2));
+        assert_eq!(nrom.ppu_peek(0x1C00, &CIRAM), 7);
+
+        // CHR ROM is read-only
+        nrom.ppu_write(0x0000, 0xFF, &mut [0; 0x800]);
+        assert_eq!(nrom.ppu_peek(0x0000, &CIRAM), 0);
+    }
+}
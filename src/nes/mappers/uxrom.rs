@@ -0,0 +1,68 @@
+use super::{bank_offset, Mapper, Memory};
+
+const PRG_BANK_SIZE: usize = 16 * 1024;
+
+// Mapper 2: switchable 16 KiB PRG bank at $8000, last bank fixed at $C000
+pub struct Uxrom {
+    memory: Memory,
+    prg_bank: usize,
+}
+
+impl Uxrom {
+    pub fn new(memory: Memory) -> Self {
+        Self {
+            memory,
+            prg_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Uxrom {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x8000..=0xBFFF => self.prg_bank,
+            _ => (self.memory.prg_rom.len() / PRG_BANK_SIZE).saturating_sub(1),
+        };
+        bank_offset(bank, PRG_BANK_SIZE, addr)
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn write_register(&mut self, _addr: u16, value: u8) {
+        self.prg_bank = value as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nes::mappers::{numbered_board, numbered_board_with};
+
+    #[test]
+    fn switches_16k_at_8000_and_fixes_the_last_bank() {
+        let mut uxrom = numbered_board(2);
+        assert_eq!(uxrom.cpu_peek(0x8000), Some(0));
+        assert_eq!(uxrom.cpu_peek(0xC000), Some(30));
+
+        uxrom.write_register(0x8000, 5);
+        assert_eq!(uxrom.cpu_peek(0x8000), Some(10));
+        assert_eq!(uxrom.cpu_peek(0xA000), Some(11));
+        assert_eq!(uxrom.cpu_peek(0xE000), Some(31));
+    }
+
+    #[test]
+    fn prg_rom_smaller_than_one_bank_is_mirrored() {
+        let uxrom = numbered_board_with(2, 1, 8);
+        assert_eq!(uxrom.cpu_peek(0xC000), Some(0));
+        assert_eq!(uxrom.cpu_peek(0xFFFF), Some(0));
+    }
+}
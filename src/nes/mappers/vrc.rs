@@ -0,0 +1,313 @@
+use super::{bank_offset, Mapper, Memory};
+use crate::nes::cartridge::Mirroring;
+
+const PRG_BANK_SIZE: usize = 8 * 1024;
+const CHR_BANK_SIZE: usize = 1024;
+
+// IRQ counter shared by VRC4, VRC6 and VRC7. It counts up to $FF, either every CPU cycle
+// or once per scanline through a prescaler that approximates 341 PPU dots.
+#[derive(Default)]
+pub struct VrcIrq {
+    latch: u8,
+    counter: u8,
+    prescaler: i16,
+    enabled: bool,
+    enabled_after_ack: bool,
+    cycle_mode: bool,
+    pending: bool,
+}
+
+impl VrcIrq {
+    pub fn write_latch(&mut self, value: u8) {
+        self.latch = value;
+    }
+
+    // VRC4 loads the latch one nibble at a time
+    pub fn write_latch_nibble(&mut self, value: u8, high: bool) {
+        self.latch = if high {
+            (self.latch & 0x0F) | (value << 4)
+        } else {
+            (self.latch & 0xF0) | (value & 0x0F)
+        };
+    }
+
+    pub fn write_control(&mut self, value: u8) {
+        self.enabled_after_ack = value & 0x01 != 0;
+        self.enabled = value & 0x02 != 0;
+        self.cycle_mode = value & 0x04 != 0;
+        self.pending = false;
+        if self.enabled {
+            self.counter = self.latch;
+            self.prescaler = 341;
+        }
+    }
+
+    pub fn acknowledge(&mut self) {
+        self.pending = false;
+        self.enabled = self.enabled_after_ack;
+    }
+
+    pub fn pending(&self) -> bool {
+        self.pending
+    }
+
+    pub fn tick(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        if !self.cycle_mode {
+            self.prescaler -= 3;
+            if self.prescaler > 0 {
+                return;
+            }
+            self.prescaler += 341;
+        }
+
+        if self.counter == 0xFF {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+}
+
+// Decodes the mirroring register shared by the VRC boards
+pub fn vrc_mirroring(value: u8) -> Mirroring {
+    match value & 0x03 {
+        0 => Mirroring::Vertical,
+        1 => Mirroring::Horizontal,
+        2 => Mirroring::SingleScreenLower,
+        _ => Mirroring::SingleScreenUpper,
+    }
+}
+
+// Mappers 21, 22, 23 and 25: Konami VRC2 and VRC4. The boards only differ in which CPU
+// address lines drive the two register select pins, and VRC2 is a subset of VRC4.
+pub struct Vrc4 {
+    memory: Memory,
+    mapper: u16,
+    // VRC2a ignores the low bit of CHR bank numbers
+    vrc2a: bool,
+    prg_banks: [u8; 2],
+    prg_swap: bool,
+    chr_banks: [u16; 8],
+    mirroring: Mirroring,
+    irq: VrcIrq,
+}
+
+impl Vrc4 {
+    pub fn new(memory: Memory, mapper: u16) -> Self {
+        Self {
+            mirroring: memory.mirroring,
+            memory,
+            mapper,
+            vrc2a: mapper == 22,
+            prg_banks: [0; 2],
+            prg_swap: false,
+            chr_banks: [0; 8],
+            irq: VrcIrq::default(),
+        }
+    }
+
+    // Turns a board-specific address into one of the four registers of its $1000 block.
+    // Without a submapper both wirings of a mapper number are decoded at once.
+    fn register(&self, addr: u16) -> u16 {
+        let line = |bit: u16| (addr >> bit) & 0x01;
+        let (a0, a1) = match self.mapper {
+            21 => (line(1) | line(6), line(2) | line(7)),
+            22 => (line(1), line(0)),
+            23 => (line(0) | line(2), line(1) | line(3)),
+            _ => (line(1) | line(3), line(0) | line(2)),
+        };
+        (addr & 0xF000) | (a1 << 1) | a0
+    }
+}
+
+impl Mapper for Vrc4 {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        let second_last = (self.memory.prg_rom.len() / PRG_BANK_SIZE).saturating_sub(2);
+        let bank = match (addr, self.prg_swap) {
+            (0x8000..=0x9FFF, false) | (0xC000..=0xDFFF, true) => self.prg_banks[0] as usize,
+            (0x8000..=0x9FFF, true) | (0xC000..=0xDFFF, false) => second_last,
+            (0xA000..=0xBFFF, _) => self.prg_banks[1] as usize,
+            _ => second_last + 1,
+        };
+        bank_offset(bank, PRG_BANK_SIZE, addr)
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[(addr as usize >> 10) & 0x07];
+        let bank = if self.vrc2a { bank >> 1 } else { bank };
+        bank_offset(bank as usize, CHR_BANK_SIZE, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        match self.register(addr) {
+            0x8000..=0x8003 => self.prg_banks[0] = value & 0x1F,
+            0x9000 | 0x9001 => self.mirroring = vrc_mirroring(value),
+            0x9002 | 0x9003 => self.prg_swap = value & 0x02 != 0,
+            0xA000..=0xAFFF => self.prg_banks[1] = value & 0x1F,
+            register @ 0xB000..=0xEFFF => {
+                // Each 1 KiB bank is written as a low nibble then a high one
+                let bank =
+                    ((register as usize - 0xB000) >> 12) * 2 + ((register as usize >> 1) & 0x01);
+                let current = &mut self.chr_banks[bank];
+                *current = if register & 0x01 == 0 {
+                    (*current & 0x1F0) | (value & 0x0F) as u16
+                } else {
+                    (*current & 0x0F) | (((value & 0x1F) as u16) << 4)
+                };
+            }
+            0xF000 => self.irq.write_latch_nibble(value, false),
+            0xF001 => self.irq.write_latch_nibble(value, true),
+            0xF002 => self.irq.write_control(value),
+            0xF003 => self.irq.acknowledge(),
+            _ => {}
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn tick(&mut self) {
+        self.irq.tick();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::mappers::{numbered_board, CIRAM};
+
+    // Ticks until the IRQ fires, acknowledging it, and returns the number of ticks
+    fn ticks_until_irq(irq: &mut VrcIrq) -> usize {
+        let mut ticks = 0;
+        while !irq.pending() {
+            irq.tick();
+            ticks += 1;
+        }
+        irq.acknowledge();
+        ticks
+    }
+
+    // Mapper 23 has its registers at $x000-$x003
+    #[test]
+    fn prg_swap_mode_moves_the_second_last_bank() {
+        let mut vrc4 = numbered_board(23);
+        vrc4.write_register(0x8000, 5);
+        vrc4.write_register(0xA000, 9);
+        assert_eq!(vrc4.cpu_peek(0x8000), Some(5));
+        assert_eq!(vrc4.cpu_peek(0xA000), Some(9));
+        assert_eq!(vrc4.cpu_peek(0xC000), Some(30));
+        assert_eq!(vrc4.cpu_peek(0xE000), Some(31));
+
+        vrc4.write_register(0x9002, 0x02);
+        assert_eq!(vrc4.cpu_peek(0x8000), Some(30));
+        assert_eq!(vrc4.cpu_peek(0xC000), Some(5));
+    }
+
+    #[test]
+    fn chr_banks_are_written_a_nibble_at_a_time() {
+        let mut vrc4 = numbered_board(23);
+        vrc4.write_register(0xB000, 0x03);
+        vrc4.write_register(0xB001, 0x01);
+        vrc4.write_register(0xE002, 0x05);
+        vrc4.write_register(0xE003, 0x0F);
+        assert_eq!(vrc4.ppu_peek(0x0000, &CIRAM), 0x13);
+        assert_eq!(vrc4.ppu_peek(0x1C00, &CIRAM), 0xF5);
+    }
+
+    #[test]
+    fn boards_decode_their_own_register_lines() {
+        // VRC4a selects registers with A1 and A2, or A6 and A7
+        let mut vrc4 = numbered_board(21);
+        vrc4.write_register(0xB000, 0x02);
+        vrc4.write_register(0xB002, 0x01);
+        vrc4.write_register(0xB080, 0x04);
+        assert_eq!(vrc4.ppu_peek(0x0000, &CIRAM), 0x12);
+        assert_eq!(vrc4.ppu_peek(0x0400, &CIRAM), 0x04);
+
+        // VRC2a drops the low bit of CHR banks
+        let mut vrc2 = numbered_board(22);
+        vrc2.write_register(0xB000, 0x06);
+        assert_eq!(vrc2.ppu_peek(0x0000, &CIRAM), 0x03);
+    }
+
+    #[test]
+    fn mirroring_is_selected_by_9000() {
+        let mut vrc4 = numbered_board(23);
+        let modes = [
+            Mirroring::Vertical,
+            Mirroring::Horizontal,
+            Mirroring::SingleScreenLower,
+            Mirroring::SingleScreenUpper,
+        ];
+        for (value, mirroring) in modes.into_iter().enumerate() {
+            vrc4.write_register(0x9000, value as u8);
+            assert_eq!(vrc4.mirroring(), mirroring);
+        }
+    }
+
+    #[test]
+    fn cycle_mode_counts_every_cpu_cycle() {
+        let mut vrc4 = numbered_board(23);
+        vrc4.write_register(0xF000, 0x0E);
+        vrc4.write_register(0xF001, 0x0F);
+        vrc4.write_register(0xF002, 0x06);
+
+        vrc4.tick();
+        assert!(!vrc4.irq());
+        vrc4.tick();
+        assert!(vrc4.irq());
+
+        // The enable-after-acknowledge bit was clear, so acknowledging stops the counter
+        vrc4.write_register(0xF003, 0);
+        assert!(!vrc4.irq());
+        for _ in 0..0x200 {
+            vrc4.tick();
+        }
+        assert!(!vrc4.irq());
+    }
+
+    #[test]
+    fn scanline_mode_prescales_by_341_dots() {
+        let mut irq = VrcIrq::default();
+        irq.write_latch(0xFF);
+        irq.write_control(0x03);
+
+        // Each CPU cycle is 3 dots, so scanlines take 114, 114 and 113 cycles
+        assert_eq!(ticks_until_irq(&mut irq), 114);
+        assert_eq!(ticks_until_irq(&mut irq), 114);
+        assert_eq!(ticks_until_irq(&mut irq), 113);
+        assert_eq!(ticks_until_irq(&mut irq), 114);
+    }
+
+    #[test]
+    fn counter_reloads_from_the_latch_after_overflowing() {
+        let mut irq = VrcIrq::default();
+        irq.write_latch(0xFD);
+        irq.write_control(0x07);
+        assert_eq!(ticks_until_irq(&mut irq), 3);
+        assert_eq!(ticks_until_irq(&mut irq), 3);
+
+        // A new latch only takes effect on the next reload
+        irq.write_latch(0xF0);
+        assert_eq!(ticks_until_irq(&mut irq), 3);
+        assert_eq!(ticks_until_irq(&mut irq), 16);
+    }
+}
@@ -0,0 +1,184 @@
+use super::{
+    bank_offset,
+    vrc::{vrc_mirroring, VrcIrq},
+    Mapper, Memory,
+};
+use crate::nes::cartridge::Mirroring;
+
+const CHR_BANK_SIZE: usize = 1024;
+
+// Mappers 24 (VRC6a) and 26 (VRC6b, with the register select lines swapped). The
+// expansion sound channels at $9000-$B002 are not emulated.
+pub struct Vrc6 {
+    memory: Memory,
+    swapped_lines: bool,
+    // 16 KiB bank at $8000 and 8 KiB bank at $C000
+    prg_banks: [u8; 2],
+    chr_banks: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram_enabled: bool,
+    irq: VrcIrq,
+}
+
+impl Vrc6 {
+    pub fn new(memory: Memory, mapper: u16) -> Self {
+        Self {
+            mirroring: memory.mirroring,
+            memory,
+            swapped_lines: mapper == 26,
+            prg_banks: [0; 2],
+            chr_banks: [0; 8],
+            prg_ram_enabled: false,
+            irq: VrcIrq::default(),
+        }
+    }
+}
+
+impl Mapper for Vrc6 {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        match addr {
+            0x8000..=0xBFFF => bank_offset(self.prg_banks[0] as usize, 16 * 1024, addr),
+            0xC000..=0xDFFF => bank_offset(self.prg_banks[1] as usize, 8 * 1024, addr),
+            _ => {
+                let last = (self.memory.prg_rom.len() / (8 * 1024)).saturating_sub(1);
+                bank_offset(last, 8 * 1024, addr)
+            }
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[(addr as usize >> 10) & 0x07];
+        bank_offset(bank as usize, CHR_BANK_SIZE, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        let register = if self.swapped_lines {
+            (addr & 0xF000) | ((addr & 0x01) << 1) | ((addr >> 1) & 0x01)
+        } else {
+            addr & 0xF003
+        };
+
+        match register {
+            0x8000..=0x8003 => self.prg_banks[0] = value & 0x0F,
+            0xB003 => {
+                self.mirroring = vrc_mirroring(value >> 2);
+                self.prg_ram_enabled = value & 0x80 != 0;
+            }
+            0xC000..=0xC003 => self.prg_banks[1] = value & 0x1F,
+            0xD000..=0xD003 => self.chr_banks[(register & 0x03) as usize] = value,
+            0xE000..=0xE003 => self.chr_banks[4 + (register & 0x03) as usize] = value,
+            0xF000 => self.irq.write_latch(value),
+            0xF001 => self.irq.write_control(value),
+            0xF002 => self.irq.acknowledge(),
+            _ => {}
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_ram_enabled && !self.memory.prg_ram.is_empty()
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn tick(&mut self) {
+        self.irq.tick();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::mappers::{numbered_board, numbered_board_with, CIRAM};
+
+    #[test]
+    fn prg_has_a_16k_and_an_8k_window_and_a_fixed_last_bank() {
+        let mut vrc6 = numbered_board(24);
+        vrc6.write_register(0x8000, 3);
+        vrc6.write_register(0xC000, 9);
+        assert_eq!(vrc6.cpu_peek(0x8000), Some(6));
+        assert_eq!(vrc6.cpu_peek(0xA000), Some(7));
+        assert_eq!(vrc6.cpu_peek(0xC000), Some(9));
+        assert_eq!(vrc6.cpu_peek(0xE000), Some(31));
+    }
+
+    #[test]
+    fn single_bank_prg_rom_fills_the_fixed_window() {
+        let vrc6 = numbered_board_with(24, 1, 8);
+        assert_eq!(vrc6.cpu_peek(0xE000), Some(0));
+    }
+
+    #[test]
+    fn chr_has_eight_1k_windows() {
+        let mut vrc6 = numbered_board(24);
+        for (register, bank) in [
+            (0xD000, 0x10),
+            (0xD003, 0x13),
+            (0xE000, 0x20),
+            (0xE003, 0x23),
+        ] {
+            vrc6.write_register(register, bank);
+        }
+        assert_eq!(vrc6.ppu_peek(0x0000, &CIRAM), 0x10);
+        assert_eq!(vrc6.ppu_peek(0x0C00, &CIRAM), 0x13);
+        assert_eq!(vrc6.ppu_peek(0x1000, &CIRAM), 0x20);
+        assert_eq!(vrc6.ppu_peek(0x1C00, &CIRAM), 0x23);
+    }
+
+    #[test]
+    fn vrc6b_swaps_the_register_select_lines() {
+        let mut vrc6 = numbered_board(26);
+        vrc6.write_register(0xD001, 0x12);
+        vrc6.write_register(0xD002, 0x11);
+        assert_eq!(vrc6.ppu_peek(0x0400, &CIRAM), 0x11);
+        assert_eq!(vrc6.ppu_peek(0x0800, &CIRAM), 0x12);
+    }
+
+    #[test]
+    fn b003_selects_mirroring_and_enables_prg_ram() {
+        let mut vrc6 = numbered_board(24);
+        assert_eq!(vrc6.cpu_peek(0x6000), None);
+
+        vrc6.write_register(0xB003, 0x84);
+        assert_eq!(vrc6.mirroring(), Mirroring::Horizontal);
+        assert_eq!(vrc6.cpu_peek(0x6000), Some(0));
+
+        vrc6.write_register(0xB003, 0x0C);
+        assert_eq!(vrc6.mirroring(), Mirroring::SingleScreenUpper);
+        assert_eq!(vrc6.cpu_peek(0x6000), None);
+    }
+
+    #[test]
+    fn irq_counts_scanlines_until_acknowledged() {
+        let mut vrc6 = numbered_board(24);
+        vrc6.write_register(0xF000, 0xFF);
+        vrc6.write_register(0xF001, 0x02);
+
+        for _ in 0..113 {
+            vrc6.tick();
+        }
+        assert!(!vrc6.irq());
+        vrc6.tick();
+        assert!(vrc6.irq());
+
+        // The enable-after-acknowledge bit was clear, so acknowledging stops the counter
+        vrc6.write_register(0xF002, 0);
+        for _ in 0..1000 {
+            vrc6.tick();
+        }
+        assert!(!vrc6.irq());
+    }
+}
@@ -0,0 +1,162 @@
+use super::{
+    bank_offset,
+    vrc::{vrc_mirroring, VrcIrq},
+    Mapper, Memory,
+};
+use crate::nes::cartridge::Mirroring;
+
+const PRG_BANK_SIZE: usize = 8 * 1024;
+const CHR_BANK_SIZE: usize = 1024;
+
+// Mapper 85: Konami VRC7. Boards use either A4 or A3 as the register select line, both
+// are decoded. The FM synthesis sound chip is not emulated.
+pub struct Vrc7 {
+    memory: Memory,
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram_enabled: bool,
+    irq: VrcIrq,
+}
+
+impl Vrc7 {
+    pub fn new(memory: Memory) -> Self {
+        Self {
+            mirroring: memory.mirroring,
+            memory,
+            prg_banks: [0; 3],
+            chr_banks: [0; 8],
+            prg_ram_enabled: false,
+            irq: VrcIrq::default(),
+        }
+    }
+}
+
+impl Mapper for Vrc7 {
+    fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> usize {
+        let bank = match addr {
+            0x8000..=0xDFFF => self.prg_banks[(addr as usize - 0x8000) / PRG_BANK_SIZE] as usize,
+            _ => (self.memory.prg_rom.len() / PRG_BANK_SIZE).saturating_sub(1),
+        };
+        bank_offset(bank, PRG_BANK_SIZE, addr)
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank = self.chr_banks[(addr as usize >> 10) & 0x07];
+        bank_offset(bank as usize, CHR_BANK_SIZE, addr)
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        let odd = addr & 0x0018 != 0;
+        match (addr & 0xF000, odd) {
+            (0x8000, false) => self.prg_banks[0] = value & 0x3F,
+            (0x8000, true) => self.prg_banks[1] = value & 0x3F,
+            (0x9000, false) => self.prg_banks[2] = value & 0x3F,
+            (block @ 0xA000..=0xD000, odd) => {
+                let bank = ((block as usize - 0xA000) >> 12) * 2 + odd as usize;
+                self.chr_banks[bank] = value;
+            }
+            (0xE000, false) => {
+                self.mirroring = vrc_mirroring(value);
+                self.prg_ram_enabled = value & 0x80 != 0;
+            }
+            (0xE000, true) => self.irq.write_latch(value),
+            (0xF000, false) => self.irq.write_control(value),
+            (0xF000, true) => self.irq.acknowledge(),
+            _ => {}
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_ram_enabled && !self.memory.prg_ram.is_empty()
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn tick(&mut self) {
+        self.irq.tick();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nes::mappers::{numbered_board, CIRAM};
+
+    #[test]
+    fn prg_has_three_8k_windows_and_a_fixed_last_bank() {
+        let mut vrc7 = numbered_board(85);
+        vrc7.write_register(0x8000, 5);
+        vrc7.write_register(0x8010, 6);
+        vrc7.write_register(0x9000, 7);
+        assert_eq!(vrc7.cpu_peek(0x8000), Some(5));
+        assert_eq!(vrc7.cpu_peek(0xA000), Some(6));
+        assert_eq!(vrc7.cpu_peek(0xC000), Some(7));
+        assert_eq!(vrc7.cpu_peek(0xE000), Some(31));
+
+        // Boards wired to A3 instead of A4
+        vrc7.write_register(0x8008, 8);
+        assert_eq!(vrc7.cpu_peek(0xA000), Some(8));
+    }
+
+    #[test]
+    fn chr_has_eight_1k_windows() {
+        let mut vrc7 = numbered_board(85);
+        for (register, bank) in [
+            (0xA000, 0x10),
+            (0xA010, 0x11),
+            (0xC000, 0x14),
+            (0xD008, 0x17),
+        ] {
+            vrc7.write_register(register, bank);
+        }
+        assert_eq!(vrc7.ppu_peek(0x0000, &CIRAM), 0x10);
+        assert_eq!(vrc7.ppu_peek(0x0400, &CIRAM), 0x11);
+        assert_eq!(vrc7.ppu_peek(0x1000, &CIRAM), 0x14);
+        assert_eq!(vrc7.ppu_peek(0x1C00, &CIRAM), 0x17);
+    }
+
+    #[test]
+    fn e000_selects_mirroring_and_enables_prg_ram() {
+        let mut vrc7 = numbered_board(85);
+        assert_eq!(vrc7.cpu_peek(0x6000), None);
+
+        vrc7.write_register(0xE000, 0x81);
+        assert_eq!(vrc7.mirroring(), Mirroring::Horizontal);
+        assert_eq!(vrc7.cpu_peek(0x6000), Some(0));
+
+        vrc7.write_register(0xE000, 0x02);
+        assert_eq!(vrc7.mirroring(), Mirroring::SingleScreenLower);
+        assert_eq!(vrc7.cpu_peek(0x6000), None);
+    }
+
+    #[test]
+    fn irq_keeps_counting_when_reenabled_by_the_acknowledge() {
+        let mut vrc7 = numbered_board(85);
+        vrc7.write_register(0xE010, 0xFE);
+        vrc7.write_register(0xF000, 0x07);
+
+        for _ in 0..2 {
+            vrc7.tick();
+            assert!(!vrc7.irq());
+            vrc7.tick();
+            assert!(vrc7.irq());
+            vrc7.write_register(0xF010, 0);
+            assert!(!vrc7.irq());
+        }
+    }
+}
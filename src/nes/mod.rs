@@ -1,6 +1,19 @@
-pub(crate) mod addr_modes;
-pub(crate) mod bus;
-pub(crate) mod instruction_summary;
-pub(crate) mod instructions;
-pub(crate) mod mos_6502;
+pub mod addr_modes;
+pub mod apu;
+pub mod bus;
+pub mod cartridge;
+pub mod controller;
+pub mod cycle_accurate;
+pub mod device;
 pub mod disassembler;
+pub mod dma;
+pub mod instruction_summary;
+pub mod instructions;
+pub mod interrupts;
+pub mod mappers;
+pub mod mos_6502;
+pub mod palette;
+pub mod ppu;
+pub mod region;
+pub mod status_flags;
+pub mod system;
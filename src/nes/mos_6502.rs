@@ -1,24 +1,45 @@
+use std::{cell::RefCell, rc::Rc};
 
+use super::{
+    addr_modes::AddrMode, bus::Bus, dma::OAM_DMA, instruction_summary::InstructionSummary,
+    interrupts::Interrupt, status_flags::StatusFlags,
+};
 
-use std::{rc::Rc, cell::RefCell};
+pub(super) const STACK_PAGE: u16 = 0x0100;
 
-use super::{bus::Bus, instruction_summary::InstructionSummary, addr_modes::AddrMode};
+// Only the 2A03 is used by the NES, the other variants are there to reuse the core
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CpuVariant {
+    // Original NMOS 6502, with decimal mode and the undocumented opcodes
+    Nmos6502,
+    // The NES CPU: an NMOS 6502 with the decimal mode circuitry disconnected
+    #[default]
+    Ricoh2A03,
+    // CMOS 65C02, with extra opcodes and the NMOS bugs fixed
+    Cmos65C02,
+}
+
+impl CpuVariant {
+    pub fn has_decimal_mode(self) -> bool {
+        self != CpuVariant::Ricoh2A03
+    }
+}
 
-pub enum Flag {
-    Carry,
-    Zero,
-    DisableInterrupts,
-    DecimalMode,
-    Break,
-    Unused,
-    Overflow,
-    Negative,
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExecutionMode {
+    // Runs the whole instruction on its first cycle and idles for the rest
+    #[default]
+    Fast,
+    // Performs every bus read and write, dummy accesses included, on its own cycle
+    CycleAccurate,
 }
 
 pub struct Mos6502 {
+    pub variant: CpuVariant,
+    pub execution_mode: ExecutionMode,
     pub pc: u16,
-    pub status_flags: u8,
-    pub stack_ptr: u16,
+    pub status_flags: StatusFlags,
+    pub stack_ptr: u8,
     pub a: u8,
     pub x: u8,
     pub y: u8,
@@ -28,14 +49,30 @@ pub struct Mos6502 {
     pub addr_abs: u16,
     pub addr_rel: u16,
     pub opcode: u8,
+    pub(super) step: u8,
+    pub(super) pointer: u16,
+    pub(super) extra_cycles: u8,
+    pub(super) pending_interrupt: Option<Interrupt>,
+    // Last level seen on the NMI line, which only interrupts on a rising edge
+    pub(super) nmi_line: bool,
+    pub(super) nmi_edge: bool,
+    // Cycles the CPU is halted for by DMA once the current instruction is over
+    pub dma_stall: u16,
+    pub total_cycles: u64,
 }
 
 impl Mos6502 {
     pub fn new(bus: Rc<RefCell<Bus>>) -> Self {
+        Self::with_variant(bus, CpuVariant::default())
+    }
+
+    pub fn with_variant(bus: Rc<RefCell<Bus>>, variant: CpuVariant) -> Self {
         Self {
+            variant,
+            execution_mode: ExecutionMode::default(),
             pc: 0,
             stack_ptr: 0,
-            status_flags: 0,
+            status_flags: StatusFlags::UNUSED | StatusFlags::DISABLE_INTERRUPTS,
             a: 0,
             x: 0,
             y: 0,
@@ -45,16 +82,46 @@ impl Mos6502 {
             addr_abs: 0,
             addr_rel: 0,
             opcode: 0,
+            step: 0,
+            pointer: 0,
+            extra_cycles: 0,
+            pending_interrupt: None,
+            nmi_line: false,
+            nmi_edge: false,
+            dma_stall: 0,
+            total_cycles: 0,
         }
     }
 
     pub fn clock(&mut self) {
+        self.bus.borrow_mut().tick();
+        self.sample_nmi_line();
+        self.total_cycles += 1;
+
+        if self.variant == CpuVariant::Ricoh2A03 {
+            self.device_dma();
+        }
+
+        if self.dma_stall > 0 && self.at_instruction_boundary() {
+            self.dma_stall -= 1;
+            return;
+        }
+
+        if self.execution_mode == ExecutionMode::CycleAccurate {
+            self.clock_cycle_accurate();
+            return;
+        }
+
+        if self.cycles == 0 {
+            self.poll_interrupt_lines();
+        }
+
         if self.cycles == 0 {
             self.opcode = self.read_byte(self.pc);
 
-            let instruction = InstructionSummary::from(self.opcode);
+            let instruction = InstructionSummary::decode(self.opcode, self.variant);
 
-            self.pc += 1;
+            self.pc = self.pc.wrapping_add(1);
             self.cycles = instruction.cycles;
 
             let addr_mode_additional_cycles = self.handle_addr_mode(instruction.addr_mode);
@@ -66,9 +133,21 @@ impl Mos6502 {
         self.cycles -= 1;
     }
 
+    // Whether the CPU is still busy with an instruction, an interrupt sequence or DMA
+    pub fn is_busy(&self) -> bool {
+        self.cycles != 0 || self.dma_stall > 0
+    }
+
+    fn at_instruction_boundary(&self) -> bool {
+        match self.execution_mode {
+            ExecutionMode::Fast => self.cycles == 0,
+            ExecutionMode::CycleAccurate => self.step == 0,
+        }
+    }
+
     pub fn read_word_and_bytes(&self, addr: u16) -> (u16, u8, u8) {
         let low_byte = self.read_byte(addr);
-        let high_byte = self.read_byte(addr + 1);
+        let high_byte = self.read_byte(addr.wrapping_add(1));
         (
             ((high_byte as u16) << 8) | low_byte as u16,
             high_byte,
@@ -84,12 +163,64 @@ impl Mos6502 {
         self.bus.borrow_mut().read(addr)
     }
 
-    pub fn write(&self, addr: u16, value: u8) {
+    // Side-effect-free counterparts of the reads above, for the debugger and disassembler
+    pub fn peek_byte(&self, addr: u16) -> u8 {
+        self.bus.borrow().peek(addr)
+    }
+
+    pub fn peek_word(&self, addr: u16) -> u16 {
+        u16::from_le_bytes([self.peek_byte(addr), self.peek_byte(addr.wrapping_add(1))])
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
         self.bus.borrow_mut().write(addr, value);
+
+        // The DMA unit is part of the 2A03, not a device on the bus
+        if addr == OAM_DMA && self.variant == CpuVariant::Ricoh2A03 {
+            self.oam_dma(value);
+        }
+    }
+
+    pub fn push_byte(&mut self, value: u8) {
+        self.write(STACK_PAGE | self.stack_ptr as u16, value);
+        self.stack_ptr = self.stack_ptr.wrapping_sub(1);
+    }
+
+    pub fn pull_byte(&mut self) -> u8 {
+        self.stack_ptr = self.stack_ptr.wrapping_add(1);
+        self.read_byte(STACK_PAGE | self.stack_ptr as u16)
+    }
+
+    pub fn push_word(&mut self, value: u16) {
+        self.push_byte((value >> 8) as u8);
+        self.push_byte(value as u8);
+    }
+
+    pub fn pull_word(&mut self) -> u16 {
+        let low_byte = self.pull_byte() as u16;
+        let high_byte = self.pull_byte() as u16;
+        (high_byte << 8) | low_byte
+    }
+
+    pub fn push_status(&mut self, break_flag: bool) {
+        self.push_byte(self.status_flags.to_stack_byte(break_flag));
+    }
+
+    pub fn pull_status(&mut self) {
+        self.status_flags = StatusFlags::from_stack_byte(self.pull_byte());
+    }
+
+    pub fn addr_mode(&self) -> AddrMode {
+        InstructionSummary::decode(self.opcode, self.variant).addr_mode
     }
 
     pub fn fetch(&mut self) -> u8 {
-        match InstructionSummary::from(self.opcode).addr_mode {
+        // In cycle-accurate mode the operand was already read on its own cycle
+        if self.execution_mode == ExecutionMode::CycleAccurate {
+            return self.fetched;
+        }
+
+        match self.addr_mode() {
             AddrMode::Implied => {}
             _ => {
                 self.fetched = self.read_byte(self.addr_abs);
@@ -98,31 +229,280 @@ impl Mos6502 {
         self.fetched
     }
 
-    pub fn get_flag(&self, flag: Flag) -> bool {
-        let bit_mask = self.get_status_bit_mask(flag);
-        self.status_flags & bit_mask == 1
+    pub fn get_flag(&self, flag: StatusFlags) -> bool {
+        self.status_flags.contains(flag)
     }
 
-    pub fn set_flag(&mut self, flag: Flag, val: bool) {
-        let bit_mask = self.get_status_bit_mask(flag);
-        let val = val as u8;
-        if val != 0 {
-            self.status_flags |= val << bit_mask;
-        } else {
-            self.status_flags &= !(val << bit_mask);
+    pub fn set_flag(&mut self, flag: StatusFlags, val: bool) {
+        self.status_flags.set(flag, val);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A CPU on a flat 64 KiB bus with `program` at $0200, ready to run it
+    fn with_program(variant: CpuVariant, program: &[u8]) -> Mos6502 {
+        let mut cpu = Mos6502::with_variant(Rc::new(RefCell::new(Bus::bare())), variant);
+        load(&mut cpu, 0x0200, program);
+        cpu.pc = 0x0200;
+        cpu.stack_ptr = 0xFD;
+        cpu
+    }
+
+    fn load(cpu: &mut Mos6502, addr: u16, bytes: &[u8]) {
+        for (offset, &byte) in bytes.iter().enumerate() {
+            cpu.write(addr + offset as u16, byte);
+        }
+    }
+
+    // Runs one whole instruction, returning how many cycles it took
+    fn step(cpu: &mut Mos6502) -> u64 {
+        let start = cpu.total_cycles;
+        cpu.clock();
+        while cpu.is_busy() {
+            cpu.clock();
         }
+        cpu.total_cycles - start
     }
 
-    fn get_status_bit_mask(&self, flag: Flag) -> u8 {
-        match flag {
-            Flag::Carry => 0b00000001,
-            Flag::Zero => 0b00000010,
-            Flag::DisableInterrupts => 0b00000100,
-            Flag::DecimalMode => 0b00001000,
-            Flag::Break => 0b00010000,
-            Flag::Unused => 0b00100000,
-            Flag::Overflow => 0b01000000,
-            Flag::Negative => 0b10000000,
+    fn run(variant: CpuVariant, program: &[u8]) -> Mos6502 {
+        let mut cpu = with_program(variant, program);
+        while cpu.pc < 0x0200 + program.len() as u16 {
+            step(&mut cpu);
         }
+        cpu
+    }
+
+    fn flags(cpu: &Mos6502) -> (bool, bool, bool, bool) {
+        (
+            cpu.get_flag(StatusFlags::CARRY),
+            cpu.get_flag(StatusFlags::ZERO),
+            cpu.get_flag(StatusFlags::OVERFLOW),
+            cpu.get_flag(StatusFlags::NEGATIVE),
+        )
+    }
+
+    #[test]
+    fn adc_sets_carry_and_overflow() {
+        // CLC, LDA #a, ADC #b: the result, then C, Z, V and N
+        let cases = [
+            (0x50, 0x10, 0x60, (false, false, false, false)),
+            (0x50, 0x50, 0xA0, (false, false, true, true)),
+            (0xFF, 0x01, 0x00, (true, true, false, false)),
+            (0x90, 0x90, 0x20, (true, false, true, false)),
+            (0xD0, 0xD0, 0xA0, (true, false, false, true)),
+        ];
+        for (a, b, result, expected) in cases {
+            let cpu = run(CpuVariant::Nmos6502, &[0x18, 0xA9, a, 0x69, b]);
+            assert_eq!(
+                (cpu.a, flags(&cpu)),
+                (result, expected),
+                "{a:02X} + {b:02X}"
+            );
+        }
+
+        let cpu = run(CpuVariant::Nmos6502, &[0x38, 0xA9, 0x7F, 0x69, 0x00]);
+        assert_eq!((cpu.a, flags(&cpu)), (0x80, (false, false, true, true)));
+    }
+
+    #[test]
+    fn sbc_borrows_through_the_carry() {
+        // SEC, LDA #a, SBC #b: the result, then C, Z, V and N
+        let cases = [
+            (0x50, 0xF0, 0x60, (false, false, false, false)),
+            (0x50, 0xB0, 0xA0, (false, false, true, true)),
+            (0x50, 0x50, 0x00, (true, true, false, false)),
+            (0xD0, 0x70, 0x60, (true, false, true, false)),
+            (0x00, 0x01, 0xFF, (false, false, false, true)),
+        ];
+        for (a, b, result, expected) in cases {
+            let cpu = run(CpuVariant::Nmos6502, &[0x38, 0xA9, a, 0xE9, b]);
+            assert_eq!(
+                (cpu.a, flags(&cpu)),
+                (result, expected),
+                "{a:02X} - {b:02X}"
+            );
+        }
+
+        let cpu = run(CpuVariant::Nmos6502, &[0x18, 0xA9, 0x50, 0xE9, 0x10]);
+        assert_eq!((cpu.a, flags(&cpu)), (0x3F, (true, false, false, false)));
+    }
+
+    #[test]
+    fn indexed_reads_take_a_cycle_more_across_pages() {
+        // LDX #$20, LDA $02F0,X, LDA $02D0,X, STA $02D0,X
+        let mut cpu = with_program(
+            CpuVariant::Nmos6502,
+            &[
+                0xA2, 0x20, 0xBD, 0xF0, 0x02, 0xBD, 0xD0, 0x02, 0x9D, 0xD0, 0x02,
+            ],
+        );
+        step(&mut cpu);
+        assert_eq!(step(&mut cpu), 5);
+        assert_eq!(step(&mut cpu), 4);
+        // Writes always spend the extra cycle
+        assert_eq!(step(&mut cpu), 5);
+
+        // LDY #$10, LDA ($40),Y through $02F8 and through $02E0
+        let mut cpu = with_program(CpuVariant::Nmos6502, &[0xA0, 0x10, 0xB1, 0x40, 0xB1, 0x42]);
+        load(&mut cpu, 0x40, &[0xF8, 0x02, 0xE0, 0x02]);
+        step(&mut cpu);
+        assert_eq!(step(&mut cpu), 6);
+        assert_eq!(step(&mut cpu), 5);
+    }
+
+    #[test]
+    fn taken_branches_take_a_cycle_more_and_another_across_pages() {
+        // BNE +$00 twice, not taken then taken
+        let mut cpu = with_program(CpuVariant::Nmos6502, &[0xD0, 0x00, 0xD0, 0x00]);
+        cpu.set_flag(StatusFlags::ZERO, true);
+        assert_eq!(step(&mut cpu), 2);
+        cpu.set_flag(StatusFlags::ZERO, false);
+        assert_eq!(step(&mut cpu), 3);
+
+        // BNE +$20 into the next page
+        let mut cpu = with_program(CpuVariant::Nmos6502, &[]);
+        load(&mut cpu, 0x02F0, &[0xD0, 0x20]);
+        cpu.pc = 0x02F0;
+        assert_eq!(step(&mut cpu), 4);
+        assert_eq!(cpu.pc, 0x0312);
+    }
+
+    #[test]
+    fn php_and_brk_push_break_and_unused() {
+        // SEC, PHP, BRK
+        let mut cpu = with_program(CpuVariant::Nmos6502, &[0x38, 0x08, 0x00]);
+        load(&mut cpu, 0xFFFE, &[0x00, 0x03]);
+        cpu.status_flags = StatusFlags::default();
+        step(&mut cpu);
+        step(&mut cpu);
+        assert_eq!(cpu.peek_byte(0x01FD), 0b00110001);
+
+        assert_eq!(step(&mut cpu), 7);
+        assert_eq!(cpu.pc, 0x0300);
+        // BRK skips its padding byte
+        assert_eq!(cpu.peek_word(0x01FB), 0x0204);
+        assert_eq!(cpu.peek_byte(0x01FA), 0b00110001);
+        assert!(cpu.get_flag(StatusFlags::DISABLE_INTERRUPTS));
+    }
+
+    #[test]
+    fn irq_pushes_unused_without_break() {
+        let mut cpu = with_program(CpuVariant::Nmos6502, &[0xEA]);
+        load(&mut cpu, 0xFFFE, &[0x00, 0x03]);
+        cpu.status_flags = StatusFlags::CARRY;
+        cpu.irq();
+
+        assert_eq!(cpu.pc, 0x0300);
+        assert_eq!(cpu.peek_word(0x01FC), 0x0200);
+        assert_eq!(cpu.peek_byte(0x01FB), 0b00100001);
+        assert!(cpu.get_flag(StatusFlags::DISABLE_INTERRUPTS));
+    }
+
+    #[test]
+    fn plp_ignores_break_and_keeps_unused() {
+        // LDA #$D3, PHA, PLP
+        let cpu = run(CpuVariant::Nmos6502, &[0xA9, 0xD3, 0x48, 0x28]);
+        assert_eq!(cpu.status_flags.bits(), 0b11100011);
+    }
+
+    #[test]
+    fn decimal_mode_depends_on_the_variant() {
+        // SED, then CLC/SEC, LDA #a, ADC/SBC #b: the result and C
+        let cases = [
+            ([0x18, 0xA9, 0x15, 0x69, 0x27], (0x42, false), (0x3C, false)),
+            ([0x18, 0xA9, 0x99, 0x69, 0x01], (0x00, true), (0x9A, false)),
+            ([0x38, 0xA9, 0x00, 0xE9, 0x01], (0x99, false), (0xFF, false)),
+            ([0x38, 0xA9, 0x42, 0xE9, 0x15], (0x27, true), (0x2D, true)),
+        ];
+        for (program, decimal, binary) in cases {
+            let program = [&[0xF8][..], &program].concat();
+            for (variant, expected) in [
+                (CpuVariant::Nmos6502, decimal),
+                (CpuVariant::Cmos65C02, decimal),
+                (CpuVariant::Ricoh2A03, binary),
+            ] {
+                let cpu = run(variant, &program);
+                assert_eq!(
+                    (cpu.a, cpu.get_flag(StatusFlags::CARRY)),
+                    expected,
+                    "{variant:?} {program:02X?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn indirect_jmp_page_wrap_is_fixed_on_the_65c02() {
+        // JMP ($02FF)
+        for (variant, target, cycles) in [
+            (CpuVariant::Nmos6502, 0x6C34, 5),
+            (CpuVariant::Cmos65C02, 0x1234, 6),
+        ] {
+            let mut cpu = with_program(variant, &[0x6C, 0xFF, 0x02]);
+            load(&mut cpu, 0x02FF, &[0x34, 0x12]);
+            assert_eq!(step(&mut cpu), cycles, "{variant:?}");
+            assert_eq!(cpu.pc, target, "{variant:?}");
+        }
+
+        // JMP ($FFFF) reads its high byte from $0000 on the 65C02
+        let mut cpu = with_program(CpuVariant::Cmos65C02, &[0x6C, 0xFF, 0xFF]);
+        load(&mut cpu, 0xFFFF, &[0x78]);
+        load(&mut cpu, 0x0000, &[0x56]);
+        load(&mut cpu, 0xFF00, &[0x9A]);
+        step(&mut cpu);
+        assert_eq!(cpu.pc, 0x5678);
+
+        let mut cpu = with_program(CpuVariant::Nmos6502, &[0x6C, 0xFF, 0xFF]);
+        load(&mut cpu, 0xFFFF, &[0x78]);
+        load(&mut cpu, 0xFF00, &[0x9A]);
+        step(&mut cpu);
+        assert_eq!(cpu.pc, 0x9A78);
+    }
+
+    #[test]
+    fn both_execution_modes_end_in_the_same_state() {
+        #[rustfmt::skip]
+        let program = [
+            0xA7, 0x42,       // LAX $42
+            0xA0, 0xF8,       // LDY #$F8
+            0xA9, 0x80,       // LDA #$80
+            0x9D, 0x00, 0x03, // STA $0300,X
+            0x71, 0x40,       // ADC ($40),Y
+            0xEE, 0x10, 0x03, // INC $0310
+            0x2A,             // ROL A
+            0x20, 0x20, 0x02, // JSR $0220
+            0xCA,             // DEX
+            0xD0, 0xF1,       // BNE $0206
+        ];
+        // PHA, TXA, PHA, PLA, TAX, PLA, RTS
+        let subroutine = [0x48, 0x8A, 0x48, 0x68, 0xAA, 0x68, 0x60];
+
+        let [fast, cycle_accurate] =
+            [ExecutionMode::Fast, ExecutionMode::CycleAccurate].map(|mode| {
+                let mut cpu = with_program(CpuVariant::Ricoh2A03, &program);
+                cpu.execution_mode = mode;
+                load(&mut cpu, 0x0220, &subroutine);
+                load(&mut cpu, 0x0040, &[0x10, 0x03, 0x10]);
+                while cpu.pc != 0x0215 && cpu.total_cycles < 10_000 {
+                    step(&mut cpu);
+                }
+                assert_eq!(cpu.pc, 0x0215, "{mode:?} did not leave the loop");
+                let registers = (
+                    cpu.a,
+                    cpu.x,
+                    cpu.y,
+                    cpu.stack_ptr,
+                    cpu.status_flags,
+                    cpu.total_cycles,
+                );
+                let memory = cpu.bus.borrow().peek_bulk(0x0000, 0x0500);
+                (registers, memory)
+            });
+
+        assert_eq!(fast.0, cycle_accurate.0);
+        assert!(fast.1 == cycle_accurate.1, "memory differs");
     }
 }
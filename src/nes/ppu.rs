@@ -0,0 +1,622 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::{cartridge::Cartridge, device::Device, region::Region};
+
+pub const SCREEN_WIDTH: usize = 256;
+pub const SCREEN_HEIGHT: usize = 240;
+
+const LAST_DOT: u16 = 340;
+const MAX_SPRITES_PER_LINE: usize = 8;
+
+// PPUCTRL
+const CTRL_INCREMENT_32: u8 = 0x04;
+const CTRL_SPRITE_TABLE: u8 = 0x08;
+const CTRL_BACKGROUND_TABLE: u8 = 0x10;
+const CTRL_TALL_SPRITES: u8 = 0x20;
+const CTRL_NMI: u8 = 0x80;
+// PPUMASK
+const MASK_GRAYSCALE: u8 = 0x01;
+const MASK_BACKGROUND_LEFT: u8 = 0x02;
+const MASK_SPRITES_LEFT: u8 = 0x04;
+const MASK_BACKGROUND: u8 = 0x08;
+const MASK_SPRITES: u8 = 0x10;
+// PPUSTATUS
+const STATUS_OVERFLOW: u8 = 0x20;
+const STATUS_SPRITE_ZERO_HIT: u8 = 0x40;
+const STATUS_VBLANK: u8 = 0x80;
+
+// Sprite attributes
+const SPRITE_PALETTE: u8 = 0x03;
+const SPRITE_BEHIND_BACKGROUND: u8 = 0x20;
+const SPRITE_FLIP_HORIZONTAL: u8 = 0x40;
+const SPRITE_FLIP_VERTICAL: u8 = 0x80;
+
+#[derive(Clone, Copy, Default)]
+struct Sprite {
+    y: u8,
+    tile: u8,
+    attributes: u8,
+    x: u8,
+    pattern_low: u8,
+    pattern_high: u8,
+}
+
+// Ricoh 2C02, clocked one dot at a time. Background and sprite fetches go through the
+// cartridge on the cycle the hardware performs them, so mappers can watch the PPU bus.
+pub struct Ppu {
+    cartridge: Rc<RefCell<Cartridge>>,
+    // The console's 2 KiB of nametable RAM, mapped by the cartridge
+    ciram: [u8; 0x800],
+    palette: [u8; 32],
+    oam: [u8; 256],
+    oam_addr: u8,
+    ctrl: u8,
+    mask: u8,
+    status: u8,
+    // Loopy registers: current and temporary VRAM address, fine X scroll, write toggle
+    v: u16,
+    t: u16,
+    fine_x: u8,
+    w: bool,
+    // The PPU's data bus latch, which write-only registers read back
+    latch: u8,
+    read_buffer: u8,
+    // Sets the number of scanlines and where vertical blank starts
+    region: Region,
+    scanline: u16,
+    dot: u16,
+    frame: u64,
+    odd_frame: bool,
+    next_tile: u8,
+    next_attribute: u8,
+    next_pattern_low: u8,
+    next_pattern_high: u8,
+    pattern_shift_low: u16,
+    pattern_shift_high: u16,
+    attribute_shift_low: u16,
+    attribute_shift_high: u16,
+    // Sprites on the current line, replaced by the ones of the next line from dot 257
+    sprites: [Sprite; MAX_SPRITES_PER_LINE],
+    sprite_count: usize,
+    sprite_zero_on_line: bool,
+    sprite_zero_on_next_line: bool,
+    frame_buffer: Vec<u8>,
+}
+
+impl Ppu {
+    pub fn new(cartridge: Rc<RefCell<Cartridge>>, region: Region) -> Self {
+        Self {
+            cartridge,
+            ciram: [0; 0x800],
+            palette: [0; 32],
+            oam: [0; 256],
+            oam_addr: 0,
+            ctrl: 0,
+            mask: 0,
+            status: 0,
+            v: 0,
+            t: 0,
+            fine_x: 0,
+            w: false,
+            latch: 0,
+            read_buffer: 0,
+            region,
+            scanline: 0,
+            dot: 0,
+            frame: 0,
+            odd_frame: false,
+            next_tile: 0,
+            next_attribute: 0,
+            next_pattern_low: 0,
+            next_pattern_high: 0,
+            pattern_shift_low: 0,
+            pattern_shift_high: 0,
+            attribute_shift_low: 0,
+            attribute_shift_high: 0,
+            sprites: [Sprite::default(); MAX_SPRITES_PER_LINE],
+            sprite_count: 0,
+            sprite_zero_on_line: false,
+            sprite_zero_on_next_line: false,
+            frame_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+        }
+    }
+
+    pub fn scanline(&self) -> u16 {
+        self.scanline
+    }
+
+    pub fn dot(&self) -> u16 {
+        self.dot
+    }
+
+    // Number of frames completed, a frame being complete when vblank starts
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    // Indices into SYSTEM_PALETTE, one byte per pixel
+    pub fn frame_buffer(&self) -> &[u8] {
+        &self.frame_buffer
+    }
+
+    fn rendering_enabled(&self) -> bool {
+        self.mask & (MASK_BACKGROUND | MASK_SPRITES) != 0
+    }
+
+    fn sprite_height(&self) -> u16 {
+        if self.ctrl & CTRL_TALL_SPRITES != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    fn vram_increment(&self) -> u16 {
+        if self.ctrl & CTRL_INCREMENT_32 != 0 {
+            32
+        } else {
+            1
+        }
+    }
+
+    fn vram_read(&mut self, addr: u16) -> u8 {
+        let addr = addr & 0x3FFF;
+        if addr >= 0x3F00 {
+            return self.palette[palette_index(addr)];
+        }
+        self.cartridge
+            .borrow_mut()
+            .mapper
+            .ppu_read(addr, &self.ciram)
+    }
+
+    fn vram_peek(&self, addr: u16) -> u8 {
+        let addr = addr & 0x3FFF;
+        if addr >= 0x3F00 {
+            return self.palette[palette_index(addr)];
+        }
+        self.cartridge.borrow().mapper.ppu_peek(addr, &self.ciram)
+    }
+
+    fn vram_write(&mut self, addr: u16, value: u8) {
+        let addr = addr & 0x3FFF;
+        if addr >= 0x3F00 {
+            self.palette[palette_index(addr)] = value & 0x3F;
+            return;
+        }
+        self.cartridge
+            .borrow_mut()
+            .mapper
+            .ppu_write(addr, value, &mut self.ciram);
+    }
+
+    // OAMDATA, where the unused attribute bits read back as 0
+    fn oam_data(&self) -> u8 {
+        let value = self.oam[self.oam_addr as usize];
+        if self.oam_addr & 0x03 == 0x02 {
+            value & 0xE3
+        } else {
+            value
+        }
+    }
+
+    // PPUSTATUS, with the unused low bits coming from the data bus latch
+    fn status_register(&self) -> u8 {
+        (self.status & 0xE0) | (self.latch & 0x1F)
+    }
+
+    fn data_register(&self) -> u8 {
+        // Palette reads are not buffered, but still fill the buffer with the nametable
+        // byte underneath
+        if self.v & 0x3FFF >= 0x3F00 {
+            (self.latch & 0xC0) | self.vram_peek(self.v)
+        } else {
+            self.read_buffer
+        }
+    }
+
+    // Renders one dot. The system scheduler clocks the PPU rather than the bus, as it
+    // doesn't run a whole number of dots per CPU cycle on every console.
+    pub fn step(&mut self) {
+        let visible_line = self.scanline < SCREEN_HEIGHT as u16;
+        let pre_render_line = self.scanline == self.region.pre_render_scanline();
+
+        if pre_render_line && self.dot == 1 {
+            self.status &= !(STATUS_VBLANK | STATUS_SPRITE_ZERO_HIT | STATUS_OVERFLOW);
+        }
+
+        if (visible_line || pre_render_line) && self.rendering_enabled() {
+            self.render_dot(visible_line, pre_render_line);
+        }
+
+        if self.scanline == self.region.vblank_scanline() && self.dot == 1 {
+            self.status |= STATUS_VBLANK;
+            self.frame += 1;
+        }
+
+        self.advance_dot();
+    }
+
+    fn advance_dot(&mut self) {
+        self.dot += 1;
+
+        // Odd frames skip the last dot of the pre-render line while rendering
+        if self.scanline == self.region.pre_render_scanline()
+            && self.dot == LAST_DOT
+            && self.odd_frame
+            && self.region.skips_odd_frame_dot()
+            && self.rendering_enabled()
+        {
+            self.dot += 1;
+        }
+
+        if self.dot > LAST_DOT {
+            self.dot = 0;
+            self.scanline += 1;
+            if self.scanline > self.region.pre_render_scanline() {
+                self.scanline = 0;
+                self.odd_frame = !self.odd_frame;
+            }
+        }
+    }
+
+    fn render_dot(&mut self, visible_line: bool, pre_render_line: bool) {
+        let dot = self.dot;
+
+        if (2..=257).contains(&dot) || (322..=337).contains(&dot) {
+            self.shift_background();
+        }
+
+        if (1..=256).contains(&dot) || (321..=336).contains(&dot) {
+            self.fetch_background((dot - 1) % 8, dot >= 9);
+        }
+
+        match dot {
+            256 => self.increment_y(),
+            257 => {
+                self.reload_background_shifters();
+                self.v = (self.v & !0x041F) | (self.t & 0x041F);
+                self.evaluate_sprites(visible_line);
+            }
+            // Dummy nametable fetches, which the MMC5 uses to detect scanlines
+            337 | 339 => {
+                self.vram_read(0x2000 | (self.v & 0x0FFF));
+            }
+            280..=304 if pre_render_line => {
+                self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0);
+            }
+            _ => {}
+        }
+
+        if (257..=320).contains(&dot) {
+            self.oam_addr = 0;
+            self.fetch_sprite((dot as usize - 257) / 8, (dot - 257) % 8);
+        }
+
+        if visible_line && (1..=256).contains(&dot) {
+            self.render_pixel();
+        }
+    }
+
+    fn fetch_background(&mut self, phase: u16, reload: bool) {
+        match phase {
+            0 => {
+                if reload {
+                    self.reload_background_shifters();
+                }
+                self.next_tile = self.vram_read(0x2000 | (self.v & 0x0FFF));
+            }
+            2 => {
+                let v = self.v;
+                let attribute =
+                    self.vram_read(0x23C0 | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07));
+                // Each attribute byte covers 4x4 tiles, two bits per 2x2 quadrant
+                let shift = ((v >> 4) & 0x04) | (v & 0x02);
+                self.next_attribute = (attribute >> shift) & 0x03;
+            }
+            4 => self.next_pattern_low = self.vram_read(self.background_pattern_addr()),
+            6 => self.next_pattern_high = self.vram_read(self.background_pattern_addr() + 8),
+            7 => self.increment_coarse_x(),
+            _ => {}
+        }
+    }
+
+    fn background_pattern_addr(&self) -> u16 {
+        let table = if self.ctrl & CTRL_BACKGROUND_TABLE != 0 {
+            0x1000
+        } else {
+            0
+        };
+        table | ((self.next_tile as u16) << 4) | ((self.v >> 12) & 0x07)
+    }
+
+    fn reload_background_shifters(&mut self) {
+        self.pattern_shift_low = (self.pattern_shift_low & 0xFF00) | self.next_pattern_low as u16;
+        self.pattern_shift_high =
+            (self.pattern_shift_high & 0xFF00) | self.next_pattern_high as u16;
+
+        // The attribute is the same for all 8 pixels of the tile
+        let fill = |bit: u8| {
+            if self.next_attribute & bit != 0 {
+                0xFF
+            } else {
+                0x00
+            }
+        };
+        self.attribute_shift_low = (self.attribute_shift_low & 0xFF00) | fill(0x01);
+        self.attribute_shift_high = (self.attribute_shift_high & 0xFF00) | fill(0x02);
+    }
+
+    fn shift_background(&mut self) {
+        self.pattern_shift_low <<= 1;
+        self.pattern_shift_high <<= 1;
+        self.attribute_shift_low <<= 1;
+        self.attribute_shift_high <<= 1;
+    }
+
+    fn increment_coarse_x(&mut self) {
+        if self.v & 0x001F == 31 {
+            self.v &= !0x001F;
+            self.v ^= 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+
+        self.v &= !0x7000;
+        let coarse_y = match (self.v & 0x03E0) >> 5 {
+            // Row 29 is the last one of a nametable, rows 30 and 31 are attributes
+            29 => {
+                self.v ^= 0x0800;
+                0
+            }
+            31 => 0,
+            coarse_y => coarse_y + 1,
+        };
+        self.v = (self.v & !0x03E0) | (coarse_y << 5);
+    }
+
+    // Finds the sprites on the next line. The hardware spreads this over dots 65-256,
+    // but nothing outside the PPU can observe it.
+    fn evaluate_sprites(&mut self, visible_line: bool) {
+        self.sprite_count = 0;
+        self.sprite_zero_on_next_line = false;
+        if !visible_line {
+            return;
+        }
+
+        let height = self.sprite_height();
+        for index in 0..64 {
+            let entry = &self.oam[index * 4..index * 4 + 4];
+            let row = self.scanline.wrapping_sub(entry[0] as u16);
+            if row >= height {
+                continue;
+            }
+
+            if self.sprite_count == MAX_SPRITES_PER_LINE {
+                self.status |= STATUS_OVERFLOW;
+                break;
+            }
+            self.sprites[self.sprite_count] = Sprite {
+                y: entry[0],
+                tile: entry[1],
+                attributes: entry[2],
+                x: entry[3],
+                pattern_low: 0,
+                pattern_high: 0,
+            };
+            self.sprite_zero_on_next_line |= index == 0;
+            self.sprite_count += 1;
+        }
+    }
+
+    fn fetch_sprite(&mut self, slot: usize, phase: u16) {
+        match phase {
+            // Garbage nametable fetches
+            0 | 2 => {
+                self.vram_read(0x2000 | (self.v & 0x0FFF));
+            }
+            4 | 6 => {
+                // Empty slots fetch tile $FF but stay transparent
+                let addr = self.sprite_pattern_addr(slot) + if phase == 6 { 8 } else { 0 };
+                let mut pattern = self.vram_read(addr);
+                if slot >= self.sprite_count {
+                    pattern = 0;
+                } else if self.sprites[slot].attributes & SPRITE_FLIP_HORIZONTAL != 0 {
+                    pattern = pattern.reverse_bits();
+                }
+
+                if phase == 4 {
+                    self.sprites[slot].pattern_low = pattern;
+                } else {
+                    self.sprites[slot].pattern_high = pattern;
+                }
+            }
+            _ => {}
+        }
+
+        if slot == MAX_SPRITES_PER_LINE - 1 && phase == 7 {
+            self.sprite_zero_on_line = self.sprite_zero_on_next_line;
+        }
+    }
+
+    fn sprite_pattern_addr(&self, slot: usize) -> u16 {
+        let height = self.sprite_height();
+        let (tile, row) = if slot < self.sprite_count {
+            let sprite = &self.sprites[slot];
+            let row = self.scanline.wrapping_sub(sprite.y as u16);
+            let row = if sprite.attributes & SPRITE_FLIP_VERTICAL != 0 {
+                height - 1 - row
+            } else {
+                row
+            };
+            (sprite.tile, row)
+        } else {
+            (0xFF, 0)
+        };
+
+        if height == 16 {
+            // 8x16 sprites take their pattern table from bit 0 of the tile number
+            let table = (tile as u16 & 0x01) << 12;
+            let tile = (tile & 0xFE) as u16 + (row >> 3);
+            table | (tile << 4) | (row & 0x07)
+        } else {
+            let table = if self.ctrl & CTRL_SPRITE_TABLE != 0 {
+                0x1000
+            } else {
+                0
+            };
+            table | ((tile as u16) << 4) | row
+        }
+    }
+
+    fn render_pixel(&mut self) {
+        let x = self.dot as usize - 1;
+
+        let (mut background_pixel, mut background_palette) = (0, 0);
+        if self.mask & MASK_BACKGROUND != 0 && (x >= 8 || self.mask & MASK_BACKGROUND_LEFT != 0) {
+            let bit = 0x8000 >> self.fine_x;
+            let pick =
+                |low: u16, high: u16| ((high & bit != 0) as u8) << 1 | (low & bit != 0) as u8;
+            background_pixel = pick(self.pattern_shift_low, self.pattern_shift_high);
+            background_palette = pick(self.attribute_shift_low, self.attribute_shift_high);
+        }
+
+        let mut sprite = None;
+        if self.mask & MASK_SPRITES != 0 && (x >= 8 || self.mask & MASK_SPRITES_LEFT != 0) {
+            sprite = self.sprites[..self.sprite_count]
+                .iter()
+                .enumerate()
+                .find_map(|(slot, sprite)| {
+                    let column = x.checked_sub(sprite.x as usize).filter(|&c| c < 8)?;
+                    let bit = 0x80 >> column;
+                    let pixel = ((sprite.pattern_high & bit != 0) as u8) << 1
+                        | (sprite.pattern_low & bit != 0) as u8;
+                    (pixel != 0).then_some((slot, pixel, sprite.attributes))
+                });
+        }
+
+        let palette_addr = match sprite {
+            Some((slot, sprite_pixel, attributes)) => {
+                if slot == 0 && self.sprite_zero_on_line && background_pixel != 0 && x != 255 {
+                    self.status |= STATUS_SPRITE_ZERO_HIT;
+                }
+
+                if background_pixel != 0 && attributes & SPRITE_BEHIND_BACKGROUND != 0 {
+                    (background_palette << 2) | background_pixel
+                } else {
+                    0x10 | ((attributes & SPRITE_PALETTE) << 2) | sprite_pixel
+                }
+            }
+            None if background_pixel != 0 => (background_palette << 2) | background_pixel,
+            None => 0,
+        };
+
+        let mut colour = self.palette[palette_index(0x3F00 | palette_addr as u16)];
+        if self.mask & MASK_GRAYSCALE != 0 {
+            colour &= 0x30;
+        }
+        self.frame_buffer[self.scanline as usize * SCREEN_WIDTH + x] = colour;
+    }
+}
+
+// $3F10, $3F14, $3F18 and $3F1C mirror the background entries below them
+fn palette_index(addr: u16) -> usize {
+    let index = addr as usize & 0x1F;
+    if index & 0x13 == 0x10 {
+        index & !0x10
+    } else {
+        index
+    }
+}
+
+// CPU side: eight registers mirrored through $2000-$3FFF
+impl Device for Ppu {
+    fn read(&mut self, addr: u16) -> Option<u8> {
+        let value = match addr & 0x0007 {
+            0x0002 => {
+                let value = self.status_register();
+                self.status &= !STATUS_VBLANK;
+                self.w = false;
+                value
+            }
+            0x0004 => self.oam_data(),
+            0x0007 => {
+                let value = self.data_register();
+                let buffered_addr = if self.v & 0x3FFF >= 0x3F00 {
+                    self.v - 0x1000
+                } else {
+                    self.v
+                };
+                self.read_buffer = self.vram_read(buffered_addr);
+                self.v = self.v.wrapping_add(self.vram_increment());
+                value
+            }
+            _ => self.latch,
+        };
+
+        self.latch = value;
+        Some(value)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.latch = value;
+
+        match addr & 0x0007 {
+            0x0000 => {
+                self.ctrl = value;
+                self.t = (self.t & !0x0C00) | ((value as u16 & 0x03) << 10);
+            }
+            0x0001 => self.mask = value,
+            0x0003 => self.oam_addr = value,
+            0x0004 => {
+                self.oam[self.oam_addr as usize] = value;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            0x0005 => {
+                if self.w {
+                    self.t = (self.t & !0x73E0)
+                        | ((value as u16 & 0x07) << 12)
+                        | ((value as u16 & 0xF8) << 2);
+                } else {
+                    self.t = (self.t & !0x001F) | (value as u16 >> 3);
+                    self.fine_x = value & 0x07;
+                }
+                self.w = !self.w;
+            }
+            0x0006 => {
+                if self.w {
+                    self.t = (self.t & 0xFF00) | value as u16;
+                    self.v = self.t;
+                } else {
+                    self.t = (self.t & 0x00FF) | ((value as u16 & 0x3F) << 8);
+                }
+                self.w = !self.w;
+            }
+            0x0007 => {
+                self.vram_write(self.v, value);
+                self.v = self.v.wrapping_add(self.vram_increment());
+            }
+            _ => {}
+        }
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        Some(match addr & 0x0007 {
+            0x0002 => self.status_register(),
+            0x0004 => self.oam_data(),
+            0x0007 => self.data_register(),
+            _ => self.latch,
+        })
+    }
+
+    fn nmi(&self) -> bool {
+        self.status & STATUS_VBLANK != 0 && self.ctrl & CTRL_NMI != 0
+    }
+}
@@ -0,0 +1,131 @@
+use super::cartridge::Timing;
+
+// Dots on every scanline
+const DOTS_PER_SCANLINE: u64 = 341;
+
+// Console timing family. Every chip runs off one master crystal through its own
+// divider: NTSC renders 3 PPU dots per CPU cycle, PAL 3.2, and the Dendy famiclones
+// keep NTSC's 3 with PAL's 312-line frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+impl Region {
+    // Multi-region games run on any console, and NTSC is the common one
+    pub fn from_header(timing: Timing) -> Self {
+        match timing {
+            Timing::Ntsc | Timing::MultiRegion => Region::Ntsc,
+            Timing::Pal => Region::Pal,
+            Timing::Dendy => Region::Dendy,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Region::Ntsc, Region::Pal, Region::Dendy]
+            .into_iter()
+            .find(|region| format!("{:?}", region).eq_ignore_ascii_case(name))
+    }
+
+    pub fn master_clock_rate(self) -> f64 {
+        match self {
+            Region::Ntsc => 21_477_272.0,
+            Region::Pal | Region::Dendy => 26_601_712.0,
+        }
+    }
+
+    pub fn cpu_divider(self) -> u64 {
+        match self {
+            Region::Ntsc => 12,
+            Region::Pal => 16,
+            Region::Dendy => 15,
+        }
+    }
+
+    pub fn ppu_divider(self) -> u64 {
+        match self {
+            Region::Ntsc => 4,
+            Region::Pal | Region::Dendy => 5,
+        }
+    }
+
+    pub fn cpu_clock_rate(self) -> f64 {
+        self.master_clock_rate() / self.cpu_divider() as f64
+    }
+
+    pub fn scanlines(self) -> u16 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    // PAL stretches vertical blank, Dendy instead adds 50 idle lines before it
+    pub fn vblank_scanline(self) -> u16 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    pub fn pre_render_scanline(self) -> u16 {
+        self.scanlines() - 1
+    }
+
+    // Only the NTSC PPU skips a dot on odd frames while rendering
+    pub fn skips_odd_frame_dot(self) -> bool {
+        self == Region::Ntsc
+    }
+
+    pub fn dots_per_frame(self) -> u64 {
+        DOTS_PER_SCANLINE * self.scanlines() as u64
+    }
+
+    pub fn frame_rate(self) -> f64 {
+        let mut dots = self.dots_per_frame() as f64;
+        if self.skips_odd_frame_dot() {
+            dots -= 0.5;
+        }
+        self.master_clock_rate() / (self.ppu_divider() as f64 * dots)
+    }
+
+    // APU timer periods, in CPU cycles. Dendy uses the NTSC tables.
+    pub fn noise_periods(self) -> &'static [u16; 16] {
+        match self {
+            Region::Ntsc | Region::Dendy => &[
+                4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+            ],
+            Region::Pal => &[
+                4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+            ],
+        }
+    }
+
+    pub fn dmc_rates(self) -> &'static [u16; 16] {
+        match self {
+            Region::Ntsc | Region::Dendy => &[
+                428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+            ],
+            Region::Pal => &[
+                398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+            ],
+        }
+    }
+
+    // CPU cycles at which the APU frame counter clocks its units, then the sequence
+    // length, for the 4-step and 5-step modes
+    pub fn frame_counter_sequences(self) -> (&'static [u32; 5], &'static [u32; 5]) {
+        match self {
+            Region::Ntsc | Region::Dendy => (
+                &[7457, 14913, 22371, 29829, 29830],
+                &[7457, 14913, 22371, 37281, 37282],
+            ),
+            Region::Pal => (
+                &[8313, 16627, 24939, 33253, 33254],
+                &[8313, 16627, 24939, 41565, 41566],
+            ),
+        }
+    }
+}
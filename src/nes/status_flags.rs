@@ -0,0 +1,100 @@
+use std::{fmt, ops};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StatusFlags(u8);
+
+impl StatusFlags {
+    pub const CARRY: Self = Self(0b00000001);
+    pub const ZERO: Self = Self(0b00000010);
+    pub const DISABLE_INTERRUPTS: Self = Self(0b00000100);
+    pub const DECIMAL_MODE: Self = Self(0b00001000);
+    pub const BREAK: Self = Self(0b00010000);
+    pub const UNUSED: Self = Self(0b00100000);
+    pub const OVERFLOW: Self = Self(0b01000000);
+    pub const NEGATIVE: Self = Self(0b10000000);
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+
+    pub fn set(&mut self, other: Self, value: bool) {
+        if value {
+            self.insert(other);
+        } else {
+            self.remove(other);
+        }
+    }
+
+    // Break and Unused are not real latches in the CPU, they only exist on the byte
+    // pushed to the stack: Break is set by PHP/BRK and clear for IRQ/NMI, Unused is always set
+    pub fn to_stack_byte(self, break_flag: bool) -> u8 {
+        let mut status = self | Self::UNUSED;
+        status.set(Self::BREAK, break_flag);
+        status.0
+    }
+
+    pub fn from_stack_byte(byte: u8) -> Self {
+        let mut status = Self(byte);
+        status.remove(Self::BREAK);
+        status.insert(Self::UNUSED);
+        status
+    }
+}
+
+impl ops::BitOr for StatusFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for StatusFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl ops::BitAnd for StatusFlags {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl ops::Not for StatusFlags {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(!self.0)
+    }
+}
+
+// Set flags are shown in uppercase, e.g. "Nv-bdIZc"
+impl fmt::Display for StatusFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (bit, name) in "NV-BDIZC".chars().enumerate() {
+            let set = self.0 & (0b10000000 >> bit) != 0;
+            let name = match name {
+                '-' => '-',
+                _ if set => name,
+                _ => name.to_ascii_lowercase(),
+            };
+            write!(f, "{}", name)?;
+        }
+        Ok(())
+    }
+}
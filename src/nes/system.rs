@@ -0,0 +1,117 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::{
+    apu::Apu, bus::Bus, cartridge::Cartridge, controller::Controllers, mos_6502::Mos6502, ppu::Ppu,
+    region::Region,
+};
+
+// The whole console, stepped by a master clock so the PPU can run a fractional number
+// of dots per CPU cycle. Each CPU cycle runs the PPU dots that fall into it first,
+// then the CPU, which ticks the APU and the cartridge on the bus.
+pub struct System {
+    pub cpu: Mos6502,
+    pub ppu: Option<Rc<RefCell<Ppu>>>,
+    pub apu: Option<Rc<RefCell<Apu>>>,
+    pub controllers: Option<Rc<RefCell<Controllers>>>,
+    region: Region,
+    master_clock: u64,
+    ppu_clock: u64,
+}
+
+impl System {
+    pub fn new(cartridge: Cartridge, region: Region, sample_rate: f32) -> Self {
+        let cartridge = Rc::new(RefCell::new(cartridge));
+        let ppu = Rc::new(RefCell::new(Ppu::new(Rc::clone(&cartridge), region)));
+        let apu = Rc::new(RefCell::new(Apu::new(region, sample_rate)));
+        let controllers = Rc::new(RefCell::new(Controllers::default()));
+
+        let mut bus = Bus::new();
+        bus.attach(0x2000..=0x3FFF, Rc::clone(&ppu));
+        // Reads of $4017 come from the second controller, writes go to the APU frame counter
+        bus.attach(0x4016..=0x4017, Rc::clone(&controllers));
+        bus.attach_ranges(
+            [0x4000..=0x4013, 0x4015..=0x4015, 0x4017..=0x4017],
+            Rc::clone(&apu),
+        );
+        bus.insert_cartridge(cartridge);
+
+        let mut system = Self {
+            ppu: Some(ppu),
+            apu: Some(apu),
+            controllers: Some(controllers),
+            region,
+            ..Self::bare(bus)
+        };
+        system.reset();
+        system
+    }
+
+    // Just a CPU on the given bus, without the rest of the console
+    pub fn bare(bus: Bus) -> Self {
+        Self {
+            cpu: Mos6502::new(Rc::new(RefCell::new(bus))),
+            ppu: None,
+            apu: None,
+            controllers: None,
+            region: Region::Ntsc,
+            master_clock: 0,
+            ppu_clock: 0,
+        }
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+        self.finish_instruction();
+    }
+
+    // Advances by one CPU cycle
+    pub fn clock(&mut self) {
+        self.master_clock += self.region.cpu_divider();
+
+        if let Some(ppu) = &self.ppu {
+            let mut ppu = ppu.borrow_mut();
+            while self.ppu_clock + self.region.ppu_divider() <= self.master_clock {
+                self.ppu_clock += self.region.ppu_divider();
+                ppu.step();
+            }
+        }
+
+        self.cpu.clock();
+    }
+
+    // Runs until the CPU is done with its instruction, interrupt sequence or DMA
+    pub fn finish_instruction(&mut self) {
+        while self.cpu.is_busy() {
+            self.clock();
+        }
+    }
+
+    pub fn step_instruction(&mut self) {
+        self.clock();
+        self.finish_instruction();
+    }
+
+    // Runs until the PPU enters vertical blank, when its frame buffer holds a whole
+    // picture
+    pub fn run_frame(&mut self) {
+        match self.ppu.clone() {
+            Some(ppu) => {
+                let frame = ppu.borrow().frame();
+                while ppu.borrow().frame() == frame {
+                    self.clock();
+                }
+            }
+            None => {
+                let end =
+                    self.master_clock + self.region.dots_per_frame() * self.region.ppu_divider();
+                while self.master_clock < end {
+                    self.clock();
+                }
+            }
+        }
+    }
+}